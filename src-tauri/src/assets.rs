@@ -0,0 +1,214 @@
+// assets.rs
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::campaign::{AssetKind, CampaignRoot};
+use crate::refs::ChapterId;
+use crate::storage;
+use crate::store::CampaignStore;
+
+/// Asset kinds whose files are imported by the user and therefore stored under their content hash.
+/// Entity icons and hex grids are generated with names of their own.
+pub const IMPORTED_KINDS: [AssetKind; 4] = [
+    AssetKind::Landscapes,
+    AssetKind::Splashes,
+    AssetKind::Battlemaps,
+    AssetKind::IconImages,
+];
+
+/// Side table mapping stored asset names back to the name the file was imported with
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct AssetIndex {
+    /// Asset directory name -> stored name -> original name
+    #[serde(default)]
+    pub assets: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl AssetIndex {
+    pub fn load(root: &CampaignRoot) -> io::Result<AssetIndex> {
+        let path = index_path(root);
+        if !path.exists() {
+            return Ok(AssetIndex::default());
+        }
+
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Asset index '{}' is invalid: {}", path.display(), e),
+            )
+        })
+    }
+
+    pub fn save(&self, root: &CampaignRoot) -> io::Result<()> {
+        storage::write_json_atomic(&index_path(root), self)
+    }
+
+    /// Remembers the original name of a stored asset. A re-import of identical content keeps the first name.
+    fn record(&mut self, kind: AssetKind, stored_name: &str, original_name: &str) {
+        self.assets
+            .entry(kind.dir_name().to_string())
+            .or_default()
+            .entry(stored_name.to_string())
+            .or_insert_with(|| original_name.to_string());
+    }
+
+    pub fn original_names(&self, kind: AssetKind) -> BTreeMap<String, String> {
+        self.assets.get(kind.dir_name()).cloned().unwrap_or_default()
+    }
+
+    /// Forgets the stored assets whose file `is_gone`. Returns how many entries were dropped.
+    pub fn prune(&mut self, root: &CampaignRoot, mut is_gone: impl FnMut(&Path) -> bool) -> usize {
+        let assets_dir = root.path().join("assets");
+        let mut pruned = 0;
+        for (dir_name, names) in &mut self.assets {
+            let before = names.len();
+            names.retain(|stored_name, _| !is_gone(&assets_dir.join(dir_name).join(stored_name)));
+            pruned += before - names.len();
+        }
+        self.assets.retain(|_, names| !names.is_empty());
+        pruned
+    }
+}
+
+fn index_path(root: &CampaignRoot) -> PathBuf {
+    root.path().join("assets").join("index.json")
+}
+
+/// `<sha256 of contents>.<lowercase extension of the original>`
+fn content_addressed_name(bytes: &[u8], original: &Path) -> String {
+    let hash = format!("{:x}", Sha256::digest(bytes));
+    match original.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{}.{}", hash, ext.to_lowercase()),
+        None => hash,
+    }
+}
+
+fn is_content_addressed(file_name: &str) -> bool {
+    let stem = file_name.split('.').next().unwrap_or(file_name);
+    stem.len() == 64 && stem.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+fn original_file_name(path: &Path) -> io::Result<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_string())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid image file name"))
+}
+
+/// Copies `source` into the campaign's `kind` directory under its content hash and returns the stored name.
+/// Importing a file whose content is already stored does not copy it again.
+pub fn import(store: &CampaignStore, root: &CampaignRoot, kind: AssetKind, source: &Path) -> io::Result<String> {
+    let original_name = original_file_name(source)?;
+    let bytes = fs::read(source)?;
+    let stored_name = content_addressed_name(&bytes, source);
+    let destination = root.assets_dir(kind).join(&stored_name);
+
+    store.update_asset_index(root, |index| {
+        if !destination.exists() {
+            storage::write_atomic(&destination, &bytes)?;
+        }
+        index.record(kind, &stored_name, &original_name);
+        Ok(stored_name.clone())
+    })
+}
+
+/// Moves assets imported under their original filename to content-addressed names and rewrites chapter
+/// references to match. Every step can be repeated, so an interrupted migration finishes on the next open.
+pub fn migrate_to_content_addressed(store: &CampaignStore, root: &CampaignRoot) -> io::Result<()> {
+    // Step 1: Copy every legacy file to its hashed name, keeping the original for now
+    let mut renames: HashMap<AssetKind, HashMap<String, String>> = HashMap::new();
+    for kind in IMPORTED_KINDS {
+        let Ok(entries) = fs::read_dir(root.assets_dir(kind)) else {
+            continue;
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            let Some(original_name) = path.file_name().and_then(|name| name.to_str()).map(|name| name.to_string()) else {
+                continue;
+            };
+            if !path.is_file() || is_content_addressed(&original_name) || original_name.ends_with(".tmp") {
+                continue;
+            }
+
+            let bytes = fs::read(&path)?;
+            let stored_name = content_addressed_name(&bytes, &path);
+            let destination = root.assets_dir(kind).join(&stored_name);
+            if !destination.exists() {
+                storage::write_atomic(&destination, &bytes)?;
+            }
+            renames.entry(kind).or_default().insert(original_name, stored_name);
+        }
+    }
+
+    if renames.is_empty() {
+        return Ok(());
+    }
+
+    store.update_asset_index(root, |index| {
+        for (kind, names) in &renames {
+            for (original_name, stored_name) in names {
+                index.record(*kind, stored_name, original_name);
+            }
+        }
+        Ok(())
+    })?;
+
+    // Step 2: Point every chapter at the hashed names
+    let rename = |kind: AssetKind, name: &mut String| {
+        if let Some(stored_name) = renames.get(&kind).and_then(|names| names.get(name.as_str())) {
+            *name = stored_name.clone();
+        }
+    };
+    for file_name in crate::utils::list_files_in_directory(&root.chapters_dir())? {
+        let Some(chapter_id) = ChapterId::from_file_name(&file_name) else {
+            continue;
+        };
+
+        // A chapter that can't be rewritten may still use the old names, so keep the originals around
+        store
+            .update_chapter(root, &chapter_id, |chapter| {
+                chapter.landscapes.iter_mut().for_each(|landscape| rename(AssetKind::Landscapes, landscape));
+                chapter.splashes.iter_mut().for_each(|splash| rename(AssetKind::Splashes, &mut splash.image));
+                chapter.combat.iter_mut().for_each(|combat| rename(AssetKind::Battlemaps, &mut combat.battlemap));
+
+                // Identical files imported under different names are now the same asset
+                let mut seen = HashSet::new();
+                chapter.landscapes.retain(|landscape| seen.insert(landscape.clone()));
+                let mut seen = HashSet::new();
+                chapter.splashes.retain(|splash| seen.insert(splash.image.clone()));
+                Ok(())
+            })
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("Could not update asset references in chapter '{}': {}", chapter_id, e),
+                )
+            })?;
+    }
+
+    // Step 3: Drop the originals, moving hex grids along with their battlemap
+    for (kind, names) in &renames {
+        for (original_name, stored_name) in names {
+            if *kind == AssetKind::Battlemaps {
+                let hexgrids = root.assets_dir(AssetKind::Hexgrids);
+                for suffix in ["", ".meta"] {
+                    let old_path = hexgrids.join(format!("{}{}", original_name, suffix));
+                    if old_path.exists() {
+                        fs::rename(&old_path, hexgrids.join(format!("{}{}", stored_name, suffix)))?;
+                    }
+                }
+            }
+            fs::remove_file(root.assets_dir(*kind).join(original_name))?;
+        }
+        log::info!("Moved {} {} to content-addressed names", names.len(), kind.dir_name());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,207 @@
+// bestiary.rs
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::campaign::CampaignRoot;
+use crate::models::{AbilityScores, DamageType, Entity};
+use crate::refs::EntityId;
+use crate::storage;
+use crate::store::CampaignStore;
+
+/// What an instance takes from its template and keeps in step with it. Name, allegiance and size are only
+/// defaults for new instances, since those are usually changed per token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateStats {
+    pub max_hitpoints: i32,
+    pub armor_class: i32,
+    pub speed: u32,
+    /// Missing from templates saved before vision existed
+    #[serde(default)]
+    pub darkvision: u32,
+    pub initiative_bonus: i32,
+    pub abilities: AbilityScores,
+    pub player: bool,
+    pub resistances: Vec<DamageType>,
+    pub vulnerabilities: Vec<DamageType>,
+    pub immunities: Vec<DamageType>,
+    pub dm_notes: String,
+}
+
+impl TemplateStats {
+    pub fn of(entity: &Entity) -> TemplateStats {
+        TemplateStats {
+            max_hitpoints: entity.hitpoints.max,
+            armor_class: entity.armor_class,
+            speed: entity.speed,
+            darkvision: entity.darkvision,
+            initiative_bonus: entity.initiative_bonus,
+            abilities: entity.abilities,
+            player: entity.player,
+            resistances: entity.resistances.clone(),
+            vulnerabilities: entity.vulnerabilities.clone(),
+            immunities: entity.immunities.clone(),
+            dm_notes: entity.dm_notes.clone(),
+        }
+    }
+
+    /// Gives the entity these stats. An entity at full hitpoints stays at full hitpoints.
+    pub fn apply(&self, entity: &mut Entity) {
+        if entity.hitpoints.current >= entity.hitpoints.max {
+            entity.hitpoints.current = self.max_hitpoints;
+        } else {
+            entity.hitpoints.current = entity.hitpoints.current.min(self.max_hitpoints);
+        }
+        entity.hitpoints.max = self.max_hitpoints;
+        entity.armor_class = self.armor_class;
+        entity.speed = self.speed;
+        entity.darkvision = self.darkvision;
+        entity.initiative_bonus = self.initiative_bonus;
+        entity.abilities = self.abilities;
+        entity.player = self.player;
+        entity.resistances.clone_from(&self.resistances);
+        entity.vulnerabilities.clone_from(&self.vulnerabilities);
+        entity.immunities.clone_from(&self.immunities);
+        entity.dm_notes.clone_from(&self.dm_notes);
+    }
+}
+
+/// A reusable creature. Its icon lives at `bestiary/icons/<id>.png` and is copied into every instance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub id: String,
+    /// `<id>.png`
+    pub icon: String,
+    pub name: String,
+    pub allegiance: String,
+    pub size: String,
+    pub stats: TemplateStats,
+}
+
+impl Template {
+    /// A new entity with the template's stats, linked back to the template
+    pub fn instantiate(&self, icon: String, name: String) -> Entity {
+        // A fresh entity is at its full 0 hitpoints, so it starts at full health
+        let mut entity = Entity::new(icon, name, self.allegiance.clone(), self.size.clone());
+        self.stats.apply(&mut entity);
+        entity.template = Some(self.id.clone());
+        entity
+    }
+}
+
+/// Every template of the campaign, stored in `bestiary/templates.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Bestiary {
+    pub templates: Vec<Template>,
+}
+
+pub fn templates_path(root: &CampaignRoot) -> PathBuf {
+    root.bestiary_dir().join("templates.json")
+}
+
+pub fn icons_dir(root: &CampaignRoot) -> PathBuf {
+    root.bestiary_dir().join("icons")
+}
+
+impl Bestiary {
+    pub fn load(root: &CampaignRoot) -> io::Result<Bestiary> {
+        let path = templates_path(root);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Bestiary::default()),
+            Err(e) => return Err(e),
+        };
+
+        serde_json::from_str(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Bestiary file '{}' is invalid: {}", path.display(), e),
+            )
+        })
+    }
+
+    pub fn save(&self, root: &CampaignRoot) -> io::Result<()> {
+        fs::create_dir_all(root.bestiary_dir())?;
+        storage::write_json_atomic(&templates_path(root), self)
+    }
+
+    pub fn find(&self, id: &str) -> Option<&Template> {
+        self.templates.iter().find(|template| template.id == id)
+    }
+
+    pub fn find_mut(&mut self, id: &str) -> Option<&mut Template> {
+        self.templates.iter_mut().find(|template| template.id == id)
+    }
+
+    /// A random ID no template uses yet. Template IDs are kept apart from entity IDs, so garbage
+    /// collection of `entity_ids.txt` never frees one for reuse.
+    pub fn generate_id(&self) -> String {
+        let taken: HashSet<&str> = self.templates.iter().map(|template| template.id.as_str()).collect();
+        loop {
+            let id: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(10)
+                .map(char::from)
+                .collect();
+            if !taken.contains(id.as_str()) {
+                return id;
+            }
+        }
+    }
+}
+
+/// Every entity of the campaign linked to `template_id`
+pub fn instances(store: &CampaignStore, root: &CampaignRoot, template_id: &str) -> io::Result<Vec<EntityId>> {
+    let entries = match fs::read_dir(root.entities_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut instances = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue;
+        }
+        let Ok(entity_id) = EntityId::from_filename(file_name) else {
+            continue;
+        };
+
+        match store.read_entity(root, &entity_id) {
+            Ok(entity) if entity.template.as_deref() == Some(template_id) => instances.push(entity_id),
+            Ok(_) => {}
+            Err(e) => log::warn!("Skipped entity '{}' while looking for template instances: {}", file_name, e),
+        }
+    }
+
+    Ok(instances)
+}
+
+/// Runs `update` on every entity in `instances` and writes back the ones it returns true for.
+/// Returns the files (`<id>.json`) that were written.
+pub fn update_instances(
+    store: &CampaignStore,
+    root: &CampaignRoot,
+    instances: &[EntityId],
+    mut update: impl FnMut(&mut Entity) -> bool,
+) -> io::Result<Vec<String>> {
+    let mut updated = Vec::new();
+    for entity_id in instances {
+        let mut entity = store.read_entity(root, entity_id)?;
+        if update(&mut entity) {
+            store.write_entity(root, entity_id, entity)?;
+            updated.push(entity_id.json_filename());
+        }
+    }
+
+    Ok(updated)
+}
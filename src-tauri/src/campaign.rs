@@ -0,0 +1,314 @@
+// campaign.rs
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::assets;
+use crate::history::Journal;
+use crate::storage;
+use crate::store::CampaignStore;
+
+/// Maximum number of entries kept in the recent campaigns list
+const MAX_RECENT_CAMPAIGNS: usize = 10;
+
+/// Asset directories under `<campaign>/assets`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetKind {
+    Landscapes,
+    Splashes,
+    Battlemaps,
+    Entities,
+    IconImages,
+    Hexgrids,
+    /// Fog of war masks, under `fog/<chapter id>`
+    Fog,
+}
+
+impl AssetKind {
+    pub const ALL: [AssetKind; 7] = [
+        AssetKind::Landscapes,
+        AssetKind::Splashes,
+        AssetKind::Battlemaps,
+        AssetKind::Entities,
+        AssetKind::IconImages,
+        AssetKind::Hexgrids,
+        AssetKind::Fog,
+    ];
+
+    pub fn from_dir_name(name: &str) -> Option<AssetKind> {
+        AssetKind::ALL.into_iter().find(|kind| kind.dir_name() == name)
+    }
+
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            AssetKind::Landscapes => "landscapes",
+            AssetKind::Splashes => "splashes",
+            AssetKind::Battlemaps => "battlemaps",
+            AssetKind::Entities => "entities",
+            AssetKind::IconImages => "iconimages",
+            AssetKind::Hexgrids => "hexgrids",
+            AssetKind::Fog => "fog",
+        }
+    }
+}
+
+/// The directory a campaign lives in. Every campaign file path is resolved through this.
+#[derive(Debug, Clone)]
+pub struct CampaignRoot {
+    path: PathBuf,
+}
+
+impl CampaignRoot {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        CampaignRoot { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn chapters_dir(&self) -> PathBuf {
+        self.path.join("chapters")
+    }
+
+    pub fn entities_dir(&self) -> PathBuf {
+        self.path.join("entities")
+    }
+
+    /// Per-chapter combat logs, under `logs/<chapter id>`
+    pub fn logs_dir(&self) -> PathBuf {
+        self.path.join("logs")
+    }
+
+    /// Saved combat states, under `snapshots/<chapter id>`
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.path.join("snapshots")
+    }
+
+    /// Entity templates shared by every chapter, and their icons
+    pub fn bestiary_dir(&self) -> PathBuf {
+        self.path.join("bestiary")
+    }
+
+    /// Files deleted by commands that can still be undone
+    pub fn trash_dir(&self) -> PathBuf {
+        self.path.join("trash")
+    }
+
+    pub fn entity_ids_file(&self) -> PathBuf {
+        self.entities_dir().join("entity_ids.txt")
+    }
+
+    pub fn assets_dir(&self, kind: AssetKind) -> PathBuf {
+        self.path.join("assets").join(kind.dir_name())
+    }
+
+    /// A directory counts as a campaign once it has a chapters directory
+    pub fn is_campaign(&self) -> bool {
+        self.chapters_dir().is_dir()
+    }
+
+    /// Creates any missing campaign directories and the entity ID file
+    pub fn initialize(&self) -> io::Result<()> {
+        fs::create_dir_all(self.chapters_dir())?;
+        fs::create_dir_all(self.entities_dir())?;
+        for kind in AssetKind::ALL {
+            fs::create_dir_all(self.assets_dir(kind))?;
+        }
+
+        // Create entity IDs file if it doesn't exist
+        let entity_ids_file = self.entity_ids_file();
+        if !entity_ids_file.exists() {
+            File::create(entity_ids_file)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Persisted application settings
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub current_campaign: Option<PathBuf>,
+    #[serde(default)]
+    pub recent_campaigns: Vec<PathBuf>,
+}
+
+impl Settings {
+    fn load(path: &Path) -> Settings {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("Warning: Ignoring unreadable settings file '{}': {}", path.display(), e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    /// Moves `path` to the front of the recent list and makes it the current campaign
+    fn remember(&mut self, path: &Path) {
+        self.recent_campaigns.retain(|recent| recent != path);
+        self.recent_campaigns.insert(0, path.to_path_buf());
+        self.recent_campaigns.truncate(MAX_RECENT_CAMPAIGNS);
+        self.current_campaign = Some(path.to_path_buf());
+    }
+}
+
+/// Tauri managed state holding the open campaign, its file cache and the settings it was chosen from
+pub struct CampaignState {
+    root: RwLock<CampaignRoot>,
+    store: CampaignStore,
+    journal: Journal,
+    settings: Mutex<Settings>,
+    settings_path: PathBuf,
+    /// Icon images uploaded to the icon editor that it has not let go of yet
+    pending_crops: Mutex<HashSet<String>>,
+}
+
+impl CampaignState {
+    /// Opens the last used campaign, falling back to `default_root` (created if needed)
+    pub fn load(settings_path: PathBuf, default_root: PathBuf) -> io::Result<Self> {
+        let mut settings = Settings::load(&settings_path);
+
+        let root = match &settings.current_campaign {
+            Some(path) if CampaignRoot::new(path).is_campaign() => CampaignRoot::new(path),
+            _ => CampaignRoot::new(default_root),
+        };
+        root.initialize()?;
+        settings.remember(root.path());
+
+        let state = CampaignState {
+            root: RwLock::new(root),
+            store: CampaignStore::default(),
+            journal: Journal::default(),
+            settings: Mutex::new(settings),
+            settings_path,
+            pending_crops: Mutex::default(),
+        };
+        // The undo history of the last session is gone, so nothing in the trash can come back
+        state.journal.reset(&state.root());
+        state.save_settings()?;
+        state.migrate_assets();
+
+        Ok(state)
+    }
+
+    /// A snapshot of the currently open campaign root
+    pub fn root(&self) -> CampaignRoot {
+        match self.root.read() {
+            Ok(root) => root.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// The cache every chapter and entity read or write goes through
+    pub fn store(&self) -> &CampaignStore {
+        &self.store
+    }
+
+    /// Undo and redo history of the open campaign's chapters
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// Marks an uploaded icon image as in use by the icon editor, so garbage collection keeps it
+    pub fn begin_crop(&self, image: String) {
+        self.lock_pending_crops().insert(image);
+    }
+
+    pub fn end_crop(&self, image: &str) {
+        self.lock_pending_crops().remove(image);
+    }
+
+    pub fn pending_crops(&self) -> HashSet<String> {
+        self.lock_pending_crops().clone()
+    }
+
+    pub fn recent_campaigns(&self) -> Vec<PathBuf> {
+        self.lock_settings().recent_campaigns.clone()
+    }
+
+    /// Initializes a new campaign at `path` and switches to it
+    pub fn create(&self, path: &Path) -> io::Result<CampaignRoot> {
+        let root = CampaignRoot::new(path);
+        if root.is_campaign() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("'{}' already contains a campaign.", path.display()),
+            ));
+        }
+
+        root.initialize()?;
+        self.switch_to(root)
+    }
+
+    /// Switches to the existing campaign at `path`
+    pub fn open(&self, path: &Path) -> io::Result<CampaignRoot> {
+        let root = CampaignRoot::new(path);
+        if !root.is_campaign() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("'{}' does not contain a campaign.", path.display()),
+            ));
+        }
+
+        // Fill in any directories added since the campaign was created
+        root.initialize()?;
+        self.switch_to(root)
+    }
+
+    fn switch_to(&self, root: CampaignRoot) -> io::Result<CampaignRoot> {
+        self.lock_settings().remember(root.path());
+        self.save_settings()?;
+        // Undo history and trash belong to the campaign being left
+        self.journal.reset(&self.root());
+
+        match self.root.write() {
+            Ok(mut current) => *current = root.clone(),
+            Err(poisoned) => *poisoned.into_inner() = root.clone(),
+        }
+        self.store.clear();
+        self.lock_pending_crops().clear();
+        self.journal.reset(&root);
+        self.migrate_assets();
+
+        Ok(root)
+    }
+
+    /// Moves assets imported before content addressing to hashed names. A failure leaves the
+    /// campaign usable with its old names, so it is only reported.
+    fn migrate_assets(&self) {
+        let root = self.root();
+        if let Err(e) = assets::migrate_to_content_addressed(&self.store, &root) {
+            eprintln!(
+                "Warning: Failed to move assets in '{}' to content-addressed names: {}",
+                root.path().display(),
+                e
+            );
+        }
+    }
+
+    fn lock_settings(&self) -> std::sync::MutexGuard<'_, Settings> {
+        match self.settings.lock() {
+            Ok(settings) => settings,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn lock_pending_crops(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        match self.pending_crops.lock() {
+            Ok(pending) => pending,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn save_settings(&self) -> io::Result<()> {
+        let settings = self.lock_settings();
+        storage::write_json_atomic(&self.settings_path, &*settings)
+    }
+}
@@ -0,0 +1,411 @@
+// combat_log.rs
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::campaign::CampaignRoot;
+use crate::damage::HitpointChange;
+use crate::hex::Hex;
+use crate::models::Condition;
+use crate::refs::{AssetRef, ChapterId};
+use crate::storage;
+use crate::utils::now_millis;
+
+/// One entry of a combat's log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CombatEvent {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Combat round the event happened in; 0 before initiative was rolled
+    pub round: u32,
+    /// Undo step the event was recorded with. While the step is undone, the event does not count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<u64>,
+    #[serde(flatten)]
+    pub kind: CombatEventKind,
+}
+
+/// What happened. `entity` and `source` are entity files (`<id>.json`); `name` is the entity's name at the time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CombatEventKind {
+    Movement {
+        entity: String,
+        name: String,
+        from: Hex,
+        to: Hex,
+    },
+    Damage {
+        entity: String,
+        name: String,
+        /// Entity that dealt the damage, if it came from one
+        source: Option<String>,
+        change: HitpointChange,
+    },
+    Healing {
+        entity: String,
+        name: String,
+        source: Option<String>,
+        change: HitpointChange,
+    },
+    Death {
+        entity: String,
+        name: String,
+        /// Entity credited with the kill
+        source: Option<String>,
+    },
+    ConditionApplied {
+        entity: String,
+        name: String,
+        condition: Condition,
+    },
+    ConditionRemoved {
+        entity: String,
+        name: String,
+        condition: Condition,
+        /// Ran out rather than being removed by hand
+        expired: bool,
+    },
+    TurnChange {
+        previous: Option<String>,
+        active: Option<String>,
+    },
+    /// The undo step was undone; the log is append-only, so its events stay but stop counting
+    StepUndone {
+        step: u64,
+    },
+    /// The undo step was redone after being undone; its events count again
+    StepRedone {
+        step: u64,
+    },
+}
+
+impl CombatEvent {
+    pub fn now(round: u32, step: Option<u64>, kind: CombatEventKind) -> CombatEvent {
+        CombatEvent {
+            timestamp: now_millis(),
+            round,
+            step,
+            kind,
+        }
+    }
+}
+
+/// The events that still count: those of undone steps are left out, along with the undo and redo markers
+fn in_effect(events: Vec<CombatEvent>) -> Vec<CombatEvent> {
+    let mut undone = HashSet::new();
+    for event in &events {
+        match event.kind {
+            CombatEventKind::StepUndone { step } => {
+                undone.insert(step);
+            }
+            CombatEventKind::StepRedone { step } => {
+                undone.remove(&step);
+            }
+            _ => {}
+        }
+    }
+    events
+        .into_iter()
+        .filter(|event| !matches!(event.kind, CombatEventKind::StepUndone { .. } | CombatEventKind::StepRedone { .. }))
+        .filter(|event| event.step.map_or(true, |step| !undone.contains(&step)))
+        .collect()
+}
+
+/// Combat logs live at `logs/<chapter id>/<battlemap>.jsonl`, one JSON event per line
+pub fn chapter_logs_dir(root: &CampaignRoot, chapter_id: &ChapterId) -> PathBuf {
+    root.logs_dir().join(chapter_id.as_str())
+}
+
+pub fn log_path(root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> io::Result<PathBuf> {
+    let battlemap = AssetRef::parse(battlemap)?;
+    Ok(chapter_logs_dir(root, chapter_id).join(format!("{}.jsonl", battlemap)))
+}
+
+/// Adds an event to the end of the combat's log. Earlier entries are never rewritten.
+pub fn append(root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str, event: &CombatEvent) -> io::Result<()> {
+    let path = log_path(root, chapter_id, battlemap)?;
+    fs::create_dir_all(chapter_logs_dir(root, chapter_id))?;
+    storage::append_json_line(&path, event)
+}
+
+/// Reads the combat's log, oldest event first, leaving out the events of steps that were undone.
+/// A combat without events has an empty log.
+pub fn load(root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> io::Result<Vec<CombatEvent>> {
+    let path = log_path(root, chapter_id, battlemap)?;
+    let events = storage::read_json_lines(&path, |line| {
+        let mut event: Value = serde_json::from_str(line)?;
+        upgrade_movement(&mut event);
+        serde_json::from_value(event)
+    })?;
+    Ok(in_effect(events))
+}
+
+/// Movements logged before locations were hexes hold the display's grid cells; the log is append-only,
+/// so they are converted whenever it is read
+fn upgrade_movement(event: &mut Value) {
+    if event["type"] != "movement" {
+        return;
+    }
+    for end in ["from", "to"] {
+        let location = &mut event[end];
+        let coordinate = |name: &str| location.get(name).and_then(Value::as_f64).map(|value| value.round() as i32);
+        if let (Some(column), Some(row)) = (coordinate("x"), coordinate("y")) {
+            let hex = Hex::from_offset(column, row);
+            *location = json!({ "q": hex.q, "r": hex.r });
+        }
+    }
+}
+
+/// Deletes a combat's log, if it has one
+pub fn remove(root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> io::Result<()> {
+    match fs::remove_file(log_path(root, chapter_id, battlemap)?) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Deletes the logs of every combat in a chapter
+pub fn remove_chapter(root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<()> {
+    match fs::remove_dir_all(chapter_logs_dir(root, chapter_id)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Totals for one entity over an encounter
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntitySummary {
+    pub entity: String,
+    /// Last name the log saw the entity under; empty if it never logged one
+    pub name: String,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+    pub healing_received: u32,
+    pub kills: u32,
+    pub died: bool,
+    /// Hexes between the start and end of every move, added up
+    pub hexes_moved: u32,
+}
+
+impl EntitySummary {
+    fn display_name(&self) -> &str {
+        if self.name.is_empty() {
+            self.entity.strip_suffix(".json").unwrap_or(&self.entity)
+        } else {
+            &self.name
+        }
+    }
+}
+
+/// End-of-encounter totals, built from a combat's log
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CombatSummary {
+    pub battlemap: String,
+    /// Highest round the log reached
+    pub rounds: u32,
+    /// Every entity that appears in the log, in order of first appearance
+    pub entities: Vec<EntitySummary>,
+    pub deaths: Vec<DeathSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeathSummary {
+    pub round: u32,
+    pub entity: String,
+    pub source: Option<String>,
+}
+
+impl CombatSummary {
+    pub fn from_events(battlemap: &str, events: &[CombatEvent]) -> CombatSummary {
+        let mut entities: Vec<EntitySummary> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut entry = |entity: &str| -> usize {
+            *index.entry(entity.to_string()).or_insert_with(|| {
+                entities.push(EntitySummary {
+                    entity: entity.to_string(),
+                    ..EntitySummary::default()
+                });
+                entities.len() - 1
+            })
+        };
+
+        // Resolve indices first, then update, so `entry` can keep borrowing `entities`
+        let mut updates: Vec<(usize, Option<usize>, &CombatEventKind)> = Vec::new();
+        let mut deaths = Vec::new();
+        for event in events {
+            let (entity, source) = match &event.kind {
+                CombatEventKind::Movement { entity, .. }
+                | CombatEventKind::ConditionApplied { entity, .. }
+                | CombatEventKind::ConditionRemoved { entity, .. } => (entity, None),
+                CombatEventKind::Damage { entity, source, .. }
+                | CombatEventKind::Healing { entity, source, .. }
+                | CombatEventKind::Death { entity, source, .. } => (entity, source.as_ref()),
+                CombatEventKind::TurnChange { .. }
+                | CombatEventKind::StepUndone { .. }
+                | CombatEventKind::StepRedone { .. } => continue,
+            };
+            if let CombatEventKind::Death { .. } = event.kind {
+                deaths.push(DeathSummary {
+                    round: event.round,
+                    entity: entity.clone(),
+                    source: source.cloned(),
+                });
+            }
+            let entity = entry(entity);
+            let source = source.map(|source| entry(source));
+            updates.push((entity, source, &event.kind));
+        }
+
+        for (entity, source, kind) in updates {
+            match kind {
+                CombatEventKind::Movement { name, from, to, .. } => {
+                    entities[entity].name.clone_from(name);
+                    entities[entity].hexes_moved += from.distance(*to);
+                }
+                CombatEventKind::ConditionApplied { name, .. }
+                | CombatEventKind::ConditionRemoved { name, .. } => entities[entity].name.clone_from(name),
+                CombatEventKind::Damage { name, change, .. } => {
+                    entities[entity].name.clone_from(name);
+                    entities[entity].damage_taken += change.effective;
+                    if let Some(source) = source {
+                        entities[source].damage_dealt += change.effective;
+                    }
+                }
+                CombatEventKind::Healing { name, change, .. } => {
+                    entities[entity].name.clone_from(name);
+                    entities[entity].healing_received += change.effective;
+                }
+                CombatEventKind::Death { name, .. } => {
+                    entities[entity].name.clone_from(name);
+                    entities[entity].died = true;
+                    if let Some(source) = source {
+                        entities[source].kills += 1;
+                    }
+                }
+                CombatEventKind::TurnChange { .. }
+                | CombatEventKind::StepUndone { .. }
+                | CombatEventKind::StepRedone { .. } => {}
+            }
+        }
+
+        CombatSummary {
+            battlemap: battlemap.to_string(),
+            rounds: events.iter().map(|event| event.round).max().unwrap_or(0),
+            entities,
+            deaths,
+        }
+    }
+
+    /// Fills in names for entities the log never named, e.g. ones that only ever dealt damage
+    pub fn fill_names(&mut self, name_of: impl Fn(&str) -> Option<String>) {
+        for entity in self.entities.iter_mut().filter(|entity| entity.name.is_empty()) {
+            if let Some(name) = name_of(&entity.entity) {
+                entity.name = name;
+            }
+        }
+    }
+
+    fn display_name<'a>(&'a self, entity: &'a str) -> &'a str {
+        self.entities
+            .iter()
+            .find(|summary| summary.entity == entity)
+            .map_or(entity, EntitySummary::display_name)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        let _ = writeln!(markdown, "# Encounter summary: {}", self.battlemap);
+        let _ = writeln!(markdown);
+        let _ = writeln!(markdown, "Rounds: {}", self.rounds);
+        let _ = writeln!(markdown);
+        let _ = writeln!(markdown, "| Entity | Damage dealt | Damage taken | Healing received | Kills | Hexes moved | Died |");
+        let _ = writeln!(markdown, "| --- | ---: | ---: | ---: | ---: | ---: | --- |");
+        for entity in &self.entities {
+            let _ = writeln!(
+                markdown,
+                "| {} | {} | {} | {} | {} | {} | {} |",
+                escape_cell(entity.display_name()),
+                entity.damage_dealt,
+                entity.damage_taken,
+                entity.healing_received,
+                entity.kills,
+                entity.hexes_moved,
+                if entity.died { "yes" } else { "no" },
+            );
+        }
+
+        if !self.deaths.is_empty() {
+            let _ = writeln!(markdown);
+            let _ = writeln!(markdown, "## Deaths");
+            let _ = writeln!(markdown);
+        }
+        for death in &self.deaths {
+            let _ = match &death.source {
+                Some(source) => writeln!(
+                    markdown,
+                    "- Round {}: {} was killed by {}",
+                    death.round,
+                    self.display_name(&death.entity),
+                    self.display_name(source)
+                ),
+                None => writeln!(markdown, "- Round {}: {} died", death.round, self.display_name(&death.entity)),
+            };
+        }
+
+        markdown
+    }
+}
+
+/// Keeps names with a `|` from breaking the table
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movement(step: Option<u64>, to: Hex) -> CombatEvent {
+        let kind = CombatEventKind::Movement {
+            entity: "a.json".to_string(),
+            name: "Goblin".to_string(),
+            from: Hex::new(0, 0),
+            to,
+        };
+        CombatEvent::now(1, step, kind)
+    }
+
+    fn marker(kind: CombatEventKind) -> CombatEvent {
+        CombatEvent::now(0, None, kind)
+    }
+
+    #[test]
+    fn undone_steps_stop_counting_until_redone() {
+        let events = vec![
+            movement(Some(1), Hex::new(1, 0)),
+            movement(Some(2), Hex::new(3, 0)),
+            movement(None, Hex::new(2, 0)),
+            marker(CombatEventKind::StepUndone { step: 2 }),
+        ];
+        let counted = in_effect(events.clone());
+        assert_eq!(counted.len(), 2);
+        assert!(counted.iter().all(|event| event.step != Some(2)));
+        assert_eq!(CombatSummary::from_events("map", &counted).entities[0].hexes_moved, 3);
+
+        let mut redone = events;
+        redone.push(marker(CombatEventKind::StepRedone { step: 2 }));
+        let counted = in_effect(redone);
+        assert_eq!(counted.len(), 3);
+        assert_eq!(CombatSummary::from_events("map", &counted).entities[0].hexes_moved, 6);
+    }
+}
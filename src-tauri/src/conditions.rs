@@ -0,0 +1,148 @@
+// conditions.rs
+use std::collections::HashSet;
+use std::io;
+
+use serde::Serialize;
+
+use crate::campaign::CampaignRoot;
+use crate::initiative::TurnChange;
+use crate::models::{Condition, ConditionKind, Entity, ExpiryTrigger, Initiative};
+use crate::refs::EntityId;
+use crate::store::CampaignStore;
+
+/// Event emitted to the constructor window for every condition that ran out
+pub const CONDITION_EXPIRED_EVENT: &str = "conditionExpired";
+
+/// Label of the constructor window, the app's main window
+pub const CONSTRUCTOR_WINDOW: &str = "main";
+
+/// Payload of the `conditionExpired` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiredCondition {
+    /// Entity file (`<id>.json`) the condition was on
+    pub entity: String,
+    /// The entity's name, which may be empty
+    pub name: String,
+    pub condition: Condition,
+}
+
+impl Condition {
+    /// Rejects custom conditions without a label and durations of zero rounds
+    pub fn validate(&self) -> Result<(), String> {
+        if self.kind == ConditionKind::Custom && self.label.trim().is_empty() {
+            return Err("A custom condition needs a label.".to_string());
+        }
+        if self.duration == Some(0) {
+            return Err("A condition's duration must be at least one round.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Counts the duration down by `rounds`; timed conditions at zero have run out
+    fn tick(&mut self, rounds: u32) {
+        if let Some(duration) = &mut self.duration {
+            *duration = duration.saturating_sub(rounds);
+        }
+    }
+
+    fn has_run_out(&self) -> bool {
+        self.duration == Some(0)
+    }
+}
+
+impl Entity {
+    /// Counts down the conditions that expire on `trigger` and removes the ones that ran out
+    pub fn tick_conditions(&mut self, trigger: ExpiryTrigger) -> Vec<Condition> {
+        self.tick_matching(|condition| condition.expires == trigger, 1)
+    }
+
+    /// Counts down every timed condition by `rounds`, for entities that have no turn to tick on
+    pub fn tick_conditions_by_rounds(&mut self, rounds: u32) -> Vec<Condition> {
+        self.tick_matching(|_| true, rounds)
+    }
+
+    fn tick_matching(&mut self, matches: impl Fn(&Condition) -> bool, rounds: u32) -> Vec<Condition> {
+        let (expired, kept) = std::mem::take(&mut self.conditions)
+            .into_iter()
+            .map(|mut condition| {
+                if matches(&condition) {
+                    condition.tick(rounds);
+                }
+                condition
+            })
+            .partition(Condition::has_run_out);
+
+        self.conditions = kept;
+        expired
+    }
+}
+
+/// Counts down the conditions of the combat's entities after a turn change: the entity whose turn ended
+/// ticks its end-of-turn conditions, the entity whose turn started its start-of-turn ones, and entities
+/// outside the turn order tick once per round that started. Returns every condition that expired.
+pub fn tick_for_turn_change(
+    store: &CampaignStore,
+    root: &CampaignRoot,
+    entities: &[String],
+    initiative: &Initiative,
+    change: &TurnChange,
+) -> io::Result<Vec<ExpiredCondition>> {
+    let mut expired = Vec::new();
+
+    if let Some(entity) = &change.ended {
+        expired.extend(tick_entity(store, root, entity, |e| e.tick_conditions(ExpiryTrigger::EndOfTurn))?);
+    }
+    if let Some(entity) = &change.started {
+        expired.extend(tick_entity(store, root, entity, |e| e.tick_conditions(ExpiryTrigger::StartOfTurn))?);
+    }
+
+    if change.rounds_started > 0 {
+        let in_order: HashSet<&str> = initiative.order.iter().map(|entry| entry.entity.as_str()).collect();
+        for entity in entities.iter().filter(|entity| !in_order.contains(entity.as_str())) {
+            expired.extend(tick_entity(store, root, entity, |e| {
+                e.tick_conditions_by_rounds(change.rounds_started)
+            })?);
+        }
+    }
+
+    Ok(expired)
+}
+
+/// Applies `tick` to one entity file. Entities whose file is gone have nothing left to tick.
+fn tick_entity(
+    store: &CampaignStore,
+    root: &CampaignRoot,
+    filename: &str,
+    tick: impl FnOnce(&mut Entity) -> Vec<Condition>,
+) -> io::Result<Vec<ExpiredCondition>> {
+    let entity_id = EntityId::from_filename(filename)?;
+
+    // Most turns change nothing, so skip the write for entities without a timed condition
+    match store.read_entity(root, &entity_id) {
+        Ok(entity) if entity.conditions.iter().all(|condition| condition.duration.is_none()) => return Ok(Vec::new()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+        Ok(_) => {}
+    }
+
+    let result = store.update_entity(root, &entity_id, |entity| {
+        let expired = tick(entity);
+        Ok((entity.name.clone(), expired))
+    });
+
+    let (name, expired) = match result {
+        Ok(result) => result,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    Ok(expired
+        .into_iter()
+        .map(|condition| ExpiredCondition {
+            entity: filename.to_string(),
+            name: name.clone(),
+            condition,
+        })
+        .collect())
+}
@@ -0,0 +1,279 @@
+// damage.rs
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Condition, ConditionKind, DamageType, Entity, ExpiryTrigger, Hitpoints};
+
+/// Source of the unconscious condition a player character gets while dying
+pub const DYING_SOURCE: &str = "0 hitpoints";
+
+/// How an entity's resistances, vulnerabilities and immunities changed incoming damage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DamageModifier {
+    Immune,
+    Resistant,
+    Vulnerable,
+}
+
+/// The outcome of damage or healing applied to an entity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HitpointChange {
+    /// The amount asked for, before resistances
+    pub amount: u32,
+    pub damage_type: Option<DamageType>,
+    pub modifier: Option<DamageModifier>,
+    /// Damage taken after resistances, or hitpoints actually restored
+    pub effective: u32,
+    /// Part of the damage that went to temporary hitpoints
+    pub temporary_absorbed: u32,
+    pub before: Hitpoints,
+    pub after: Hitpoints,
+    pub died: bool,
+    pub fell_dying: bool,
+    /// A dying entity that was healed back above 0 hitpoints
+    pub revived: bool,
+}
+
+impl Entity {
+    /// Immunity wins over everything; a resistance and a vulnerability to the same type cancel out
+    pub fn damage_modifier(&self, damage_type: DamageType) -> Option<DamageModifier> {
+        if self.immunities.contains(&damage_type) {
+            return Some(DamageModifier::Immune);
+        }
+        match (
+            self.resistances.contains(&damage_type),
+            self.vulnerabilities.contains(&damage_type),
+        ) {
+            (true, false) => Some(DamageModifier::Resistant),
+            (false, true) => Some(DamageModifier::Vulnerable),
+            _ => None,
+        }
+    }
+
+    /// Applies damage after resistances, draining temporary hitpoints first. Non-player entities die at
+    /// 0 hitpoints; player characters start dying instead, unless the damage left over at 0 is at least
+    /// their maximum hitpoints.
+    pub fn apply_damage(&mut self, amount: u32, damage_type: Option<DamageType>) -> HitpointChange {
+        let modifier = damage_type.and_then(|damage_type| self.damage_modifier(damage_type));
+        let effective = match modifier {
+            Some(DamageModifier::Immune) => 0,
+            Some(DamageModifier::Resistant) => amount / 2,
+            Some(DamageModifier::Vulnerable) => amount.saturating_mul(2),
+            None => amount,
+        };
+
+        let before = self.hitpoints.clone();
+        let (was_dead, was_dying) = (self.dead, self.dying);
+
+        let damage = i32::try_from(effective).unwrap_or(i32::MAX);
+        let temporary_absorbed = damage.min(self.hitpoints.temporary.max(0));
+        self.hitpoints.temporary -= temporary_absorbed;
+
+        let remaining = damage - temporary_absorbed;
+        let overflow = remaining - self.hitpoints.current.max(0);
+        self.hitpoints.current = self.hitpoints.current.saturating_sub(remaining).max(0);
+
+        if remaining > 0 && self.hitpoints.current == 0 && !self.dead {
+            let massive = self.hitpoints.max > 0 && overflow >= self.hitpoints.max;
+            if !self.player || massive {
+                self.die();
+            } else if !self.dying {
+                self.dying = true;
+                self.conditions.push(Condition {
+                    kind: ConditionKind::Unconscious,
+                    label: String::new(),
+                    duration: None,
+                    source: DYING_SOURCE.to_string(),
+                    expires: ExpiryTrigger::EndOfTurn,
+                });
+            }
+        }
+
+        HitpointChange {
+            amount,
+            damage_type,
+            modifier,
+            effective,
+            temporary_absorbed: temporary_absorbed as u32,
+            before,
+            after: self.hitpoints.clone(),
+            died: !was_dead && self.dead,
+            fell_dying: !was_dying && self.dying,
+            revived: false,
+        }
+    }
+
+    /// Restores hitpoints up to the maximum. Healing a dying entity above 0 hitpoints stabilizes it.
+    pub fn apply_healing(&mut self, amount: u32) -> Result<HitpointChange, String> {
+        if self.dead {
+            return Err("A dead entity cannot be healed.".to_string());
+        }
+
+        let before = self.hitpoints.clone();
+        if self.hitpoints.current < self.hitpoints.max {
+            let healing = i32::try_from(amount).unwrap_or(i32::MAX);
+            self.hitpoints.current = self.hitpoints.current.saturating_add(healing).min(self.hitpoints.max);
+        }
+
+        let revived = self.dying && self.hitpoints.current > 0;
+        if revived {
+            self.stop_dying();
+        }
+
+        Ok(HitpointChange {
+            amount,
+            damage_type: None,
+            modifier: None,
+            effective: (self.hitpoints.current - before.current) as u32,
+            temporary_absorbed: 0,
+            before,
+            after: self.hitpoints.clone(),
+            died: false,
+            fell_dying: false,
+            revived,
+        })
+    }
+
+    fn die(&mut self) {
+        self.dead = true;
+        self.stop_dying();
+    }
+
+    fn stop_dying(&mut self) {
+        self.dying = false;
+        self.conditions
+            .retain(|condition| !(condition.kind == ConditionKind::Unconscious && condition.source == DYING_SOURCE));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(current: i32, max: i32, player: bool) -> Entity {
+        let mut entity = Entity::new("icon".to_string(), String::new(), String::new(), "medium".to_string());
+        entity.hitpoints = Hitpoints { current, max, temporary: 0 };
+        entity.player = player;
+        entity
+    }
+
+    fn is_dying_unconscious(condition: &Condition) -> bool {
+        condition.kind == ConditionKind::Unconscious && condition.source == DYING_SOURCE
+    }
+
+    #[test]
+    fn resistances_vulnerabilities_and_immunities() {
+        let mut goblin = entity(50, 50, false);
+        goblin.resistances = vec![DamageType::Fire, DamageType::Cold];
+        goblin.vulnerabilities = vec![DamageType::Radiant, DamageType::Cold];
+        goblin.immunities = vec![DamageType::Poison];
+
+        let change = goblin.apply_damage(7, Some(DamageType::Fire));
+        assert_eq!((change.modifier, change.effective), (Some(DamageModifier::Resistant), 3));
+        let change = goblin.apply_damage(5, Some(DamageType::Radiant));
+        assert_eq!((change.modifier, change.effective), (Some(DamageModifier::Vulnerable), 10));
+        let change = goblin.apply_damage(20, Some(DamageType::Poison));
+        assert_eq!((change.modifier, change.effective), (Some(DamageModifier::Immune), 0));
+        let change = goblin.apply_damage(4, Some(DamageType::Cold));
+        assert_eq!((change.modifier, change.effective), (None, 4));
+        let change = goblin.apply_damage(4, None);
+        assert_eq!((change.modifier, change.effective), (None, 4));
+        assert_eq!(goblin.hitpoints.current, 50 - 3 - 10 - 4 - 4);
+    }
+
+    #[test]
+    fn temporary_hitpoints_absorb_damage_first() {
+        let mut fighter = entity(20, 20, true);
+        fighter.hitpoints.temporary = 5;
+
+        let change = fighter.apply_damage(3, None);
+        assert_eq!(change.temporary_absorbed, 3);
+        assert_eq!((fighter.hitpoints.temporary, fighter.hitpoints.current), (2, 20));
+
+        let change = fighter.apply_damage(6, None);
+        assert_eq!(change.temporary_absorbed, 2);
+        assert_eq!((fighter.hitpoints.temporary, fighter.hitpoints.current), (0, 16));
+    }
+
+    #[test]
+    fn non_player_entities_die_at_zero() {
+        let mut goblin = entity(7, 7, false);
+        let change = goblin.apply_damage(10, None);
+        assert!(change.died);
+        assert!(!change.fell_dying);
+        assert!(goblin.dead);
+        assert_eq!(goblin.hitpoints.current, 0);
+
+        let change = goblin.apply_damage(10, None);
+        assert!(!change.died);
+    }
+
+    #[test]
+    fn player_characters_fall_dying_at_zero() {
+        let mut fighter = entity(10, 30, true);
+        let change = fighter.apply_damage(15, None);
+        assert!(change.fell_dying);
+        assert!(!change.died);
+        assert!(fighter.dying && !fighter.dead);
+        assert_eq!(fighter.conditions.iter().filter(|condition| is_dying_unconscious(condition)).count(), 1);
+
+        // Further damage while dying does not add a second condition
+        let change = fighter.apply_damage(1, None);
+        assert!(!change.fell_dying);
+        assert_eq!(fighter.conditions.iter().filter(|condition| is_dying_unconscious(condition)).count(), 1);
+    }
+
+    #[test]
+    fn massive_damage_kills_player_characters_outright() {
+        // 10 hitpoints left, so 40 damage leaves 30 over: the maximum
+        let mut fighter = entity(10, 30, true);
+        let change = fighter.apply_damage(40, None);
+        assert!(change.died);
+        assert!(!change.fell_dying);
+        assert!(fighter.dead && !fighter.dying);
+
+        let mut fighter = entity(10, 30, true);
+        let change = fighter.apply_damage(39, None);
+        assert!(change.fell_dying);
+        assert!(!fighter.dead);
+
+        // Massive damage also kills a player character who is already dying
+        let change = fighter.apply_damage(30, None);
+        assert!(change.died);
+        assert!(!fighter.dying);
+        assert!(!fighter.conditions.iter().any(is_dying_unconscious));
+    }
+
+    #[test]
+    fn damage_reduced_to_zero_does_not_kill() {
+        let mut goblin = entity(0, 7, false);
+        assert!(goblin.apply_damage(5, Some(DamageType::Fire)).died);
+
+        let mut zombie = entity(0, 7, false);
+        zombie.immunities = vec![DamageType::Poison];
+        let change = zombie.apply_damage(5, Some(DamageType::Poison));
+        assert!(!change.died);
+        assert!(!zombie.dead);
+    }
+
+    #[test]
+    fn healing_revives_dying_player_characters() {
+        let mut fighter = entity(5, 30, true);
+        fighter.apply_damage(8, None);
+        assert!(fighter.dying);
+
+        let change = fighter.apply_healing(4).unwrap();
+        assert!(change.revived);
+        assert_eq!(change.effective, 4);
+        assert!(!fighter.dying);
+        assert!(!fighter.conditions.iter().any(is_dying_unconscious));
+
+        let change = fighter.apply_healing(100).unwrap();
+        assert_eq!((change.effective, fighter.hitpoints.current), (26, 30));
+
+        fighter.apply_damage(100, None);
+        assert!(fighter.dead);
+        assert!(fighter.apply_healing(5).is_err());
+    }
+}
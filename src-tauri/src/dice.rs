@@ -0,0 +1,589 @@
+// dice.rs
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::campaign::CampaignRoot;
+use crate::models::DamageType;
+use crate::refs::ChapterId;
+use crate::storage;
+use crate::utils::now_millis;
+
+/// Event emitted to every window for rolls made in public
+pub const PUBLIC_ROLL_EVENT: &str = "publicRoll";
+
+/// Most dice a single expression may roll
+const MAX_DICE: u32 = 100;
+
+/// Most sides a die may have
+const MAX_SIDES: u32 = 1000;
+
+/// Largest flat number an expression may add or subtract
+const MAX_CONSTANT: i64 = 100_000;
+
+/// Most `+`/`-` separated terms in one expression
+const MAX_TERMS: usize = 20;
+
+/// Seeds stay below 2^53 so the frontend holds them exactly as JavaScript numbers and can pass them back
+pub const SEED_LIMIT: u64 = 1 << 53;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Advantage {
+    Advantage,
+    Disadvantage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Keep {
+    Highest(u32),
+    Lowest(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Dice { count: u32, sides: u32, keep: Option<Keep> },
+    Constant(i64),
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Dice { count, sides, keep } => {
+                write!(f, "{}d{}", count, sides)?;
+                match keep {
+                    Some(Keep::Highest(n)) => write!(f, "kh{}", n),
+                    Some(Keep::Lowest(n)) => write!(f, "kl{}", n),
+                    None => Ok(()),
+                }
+            }
+            Term::Constant(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// A parsed dice expression such as `2d6+3`, `4d6kh3`, `1d20 adv` or `8d6 fire`.
+/// Terms are dice (`NdS`, optionally keeping the highest or lowest `kh`/`kl` K) or flat numbers,
+/// joined by `+` or `-`, and may be followed by `adv`/`dis` and a damage type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiceExpression {
+    /// Each term with its sign, 1 or -1
+    terms: Vec<(i64, Term)>,
+    advantage: Option<Advantage>,
+    damage_type: Option<DamageType>,
+}
+
+/// One die that was rolled. Dice dropped by `kh`/`kl` or advantage are kept in the breakdown but not counted.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DieRoll {
+    pub sides: u32,
+    pub value: u32,
+    pub kept: bool,
+}
+
+/// The result of one term, e.g. `4d6kh3` or `+3`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TermRoll {
+    /// The term as written in normalized form, e.g. `4d6kh3`
+    pub term: String,
+    /// 1 for added terms, -1 for subtracted ones
+    pub sign: i64,
+    /// Empty for flat numbers
+    pub dice: Vec<DieRoll>,
+    /// What the term contributed to the total, sign included
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RollResult {
+    /// The expression in normalized form, e.g. `2d20kh1+5 fire`
+    pub expression: String,
+    pub total: i64,
+    pub terms: Vec<TermRoll>,
+    pub advantage: Option<Advantage>,
+    pub damage_type: Option<DamageType>,
+}
+
+impl DiceExpression {
+    pub fn parse(input: &str) -> Result<DiceExpression, String> {
+        let input = input.trim().to_lowercase();
+        if input.is_empty() {
+            return Err("The dice expression is empty.".to_string());
+        }
+
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            pos: 0,
+        };
+        let mut expression = DiceExpression {
+            terms: Vec::new(),
+            advantage: None,
+            damage_type: None,
+        };
+
+        // Terms joined by `+` and `-`; a leading sign is allowed
+        let mut sign = 1;
+        parser.skip_whitespace();
+        if parser.eat('-') {
+            sign = -1;
+        } else {
+            parser.eat('+');
+        }
+        loop {
+            let term = parser.term()?;
+            expression.terms.push((sign, term));
+
+            parser.skip_whitespace();
+            if parser.eat('+') {
+                sign = 1;
+            } else if parser.eat('-') {
+                sign = -1;
+            } else {
+                break;
+            }
+        }
+
+        // Trailing words: advantage or disadvantage, and a damage type
+        loop {
+            parser.skip_whitespace();
+            let Some(word) = parser.word() else {
+                break;
+            };
+            match word.as_str() {
+                "adv" | "advantage" | "dis" | "disadvantage" if expression.advantage.is_some() => {
+                    return Err("Advantage or disadvantage may only be given once.".to_string());
+                }
+                "adv" | "advantage" => expression.advantage = Some(Advantage::Advantage),
+                "dis" | "disadvantage" => expression.advantage = Some(Advantage::Disadvantage),
+                _ if expression.damage_type.is_some() => {
+                    return Err(format!("Unexpected '{}' after the damage type.", word));
+                }
+                _ => expression.damage_type = Some(DamageType::parse(&word)?),
+            }
+        }
+        if let Some(c) = parser.peek() {
+            return Err(format!("Unexpected '{}' in the dice expression.", c));
+        }
+
+        expression.apply_advantage()?;
+        expression.validate()?;
+        Ok(expression)
+    }
+
+    /// Advantage turns the expression's single die into two, keeping the higher (or lower) one
+    fn apply_advantage(&mut self) -> Result<(), String> {
+        let Some(advantage) = self.advantage else {
+            return Ok(());
+        };
+
+        let mut single_dice = self.terms.iter_mut().filter_map(|(_, term)| match term {
+            Term::Dice { count: 1, keep: None, .. } => Some(term),
+            _ => None,
+        });
+        let (Some(Term::Dice { count, keep, .. }), None) = (single_dice.next(), single_dice.next()) else {
+            return Err("Advantage and disadvantage need exactly one single die, e.g. '1d20+5 adv'.".to_string());
+        };
+
+        *count = 2;
+        *keep = Some(match advantage {
+            Advantage::Advantage => Keep::Highest(1),
+            Advantage::Disadvantage => Keep::Lowest(1),
+        });
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.terms.len() > MAX_TERMS {
+            return Err(format!("A dice expression may have at most {} terms.", MAX_TERMS));
+        }
+
+        let mut total_dice = 0;
+        for (_, term) in &self.terms {
+            match *term {
+                Term::Dice { count, sides, keep } => {
+                    if count == 0 || sides == 0 {
+                        return Err(format!("'{}' rolls no dice.", term));
+                    }
+                    if sides > MAX_SIDES {
+                        return Err(format!("Dice may have at most {} sides.", MAX_SIDES));
+                    }
+                    if let Some(Keep::Highest(keep) | Keep::Lowest(keep)) = keep {
+                        if keep == 0 || keep > count {
+                            return Err(format!("'{}' must keep between 1 and {} dice.", term, count));
+                        }
+                    }
+                    total_dice += count;
+                }
+                Term::Constant(value) => {
+                    if value > MAX_CONSTANT {
+                        return Err(format!("Numbers in a dice expression may be at most {}.", MAX_CONSTANT));
+                    }
+                }
+            }
+        }
+
+        if total_dice > MAX_DICE {
+            return Err(format!("A dice expression may roll at most {} dice.", MAX_DICE));
+        }
+        Ok(())
+    }
+
+    pub fn roll(&self, rng: &mut impl Rng) -> RollResult {
+        let terms: Vec<TermRoll> = self.terms.iter().map(|(sign, term)| roll_term(*sign, term, rng)).collect();
+
+        RollResult {
+            expression: self.to_string(),
+            total: terms.iter().map(|term| term.value).sum(),
+            terms,
+            advantage: self.advantage,
+            damage_type: self.damage_type,
+        }
+    }
+
+    /// Rolls with an RNG seeded from `seed`; the same expression and seed always give the same result.
+    /// ChaCha8 is used rather than `StdRng`, whose algorithm may change between versions of `rand`.
+    pub fn roll_seeded(&self, seed: u64) -> RollResult {
+        self.roll(&mut ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+impl fmt::Display for DiceExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (sign, term)) in self.terms.iter().enumerate() {
+            match (index, sign) {
+                (_, -1) => f.write_str("-")?,
+                (0, _) => {}
+                _ => f.write_str("+")?,
+            }
+            write!(f, "{}", term)?;
+        }
+        if let Some(damage_type) = &self.damage_type {
+            write!(f, " {}", damage_type)?;
+        }
+        Ok(())
+    }
+}
+
+fn roll_term(sign: i64, term: &Term, rng: &mut impl Rng) -> TermRoll {
+    let (dice, value) = match *term {
+        Term::Dice { count, sides, keep } => {
+            let mut dice: Vec<DieRoll> = (0..count)
+                .map(|_| DieRoll {
+                    sides,
+                    value: rng.gen_range(1..=sides),
+                    kept: true,
+                })
+                .collect();
+
+            if let Some(keep) = keep {
+                // Mark everything but the kept dice as dropped, leaving the dice in the order they were rolled
+                let mut by_value: Vec<usize> = (0..dice.len()).collect();
+                by_value.sort_by_key(|&index| dice[index].value);
+                let dropped = match keep {
+                    Keep::Highest(n) => &by_value[..(count - n) as usize],
+                    Keep::Lowest(n) => &by_value[n as usize..],
+                };
+                for &index in dropped {
+                    dice[index].kept = false;
+                }
+            }
+
+            let value = dice.iter().filter(|die| die.kept).map(|die| die.value as i64).sum();
+            (dice, value)
+        }
+        Term::Constant(value) => (Vec::new(), value),
+    };
+
+    TermRoll {
+        term: term.to_string(),
+        sign,
+        dice,
+        value: sign * value,
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<Option<i64>, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("'{}' is too large.", digits))
+    }
+
+    /// `N`, `NdS`, `dS`, optionally followed by `khK` or `klK`
+    fn term(&mut self) -> Result<Term, String> {
+        self.skip_whitespace();
+        let count = self.number()?;
+        if !self.eat('d') {
+            return count
+                .map(Term::Constant)
+                .ok_or_else(|| self.unexpected("a number or dice such as '2d6'"));
+        }
+
+        let sides = self.number()?.ok_or_else(|| self.unexpected("the number of sides"))?;
+        let keep = if self.eat('k') {
+            let highest = if self.eat('h') {
+                true
+            } else if self.eat('l') {
+                false
+            } else {
+                return Err(self.unexpected("'kh' or 'kl'"));
+            };
+            let n = self.number()?.ok_or_else(|| self.unexpected("the number of dice to keep"))?;
+            let n = to_u32(n)?;
+            Some(if highest { Keep::Highest(n) } else { Keep::Lowest(n) })
+        } else {
+            None
+        };
+
+        Ok(Term::Dice {
+            count: to_u32(count.unwrap_or(1))?,
+            sides: to_u32(sides)?,
+            keep,
+        })
+    }
+
+    fn word(&mut self) -> Option<String> {
+        let start = self.pos;
+        while self.peek().is_some_and(char::is_alphabetic) {
+            self.pos += 1;
+        }
+        (start != self.pos).then(|| self.chars[start..self.pos].iter().collect())
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(c) => format!("Expected {} but found '{}'.", expected, c),
+            None => format!("Expected {} at the end of the expression.", expected),
+        }
+    }
+}
+
+fn to_u32(value: i64) -> Result<u32, String> {
+    u32::try_from(value).map_err(|_| format!("'{}' is too large.", value))
+}
+
+/// A roll as stored in a chapter's roll log
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RollRecord {
+    /// The app session the roll was made in
+    pub session: String,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Rolling the same expression with this seed reproduces the result. Always below `SEED_LIMIT`.
+    pub seed: u64,
+    pub public: bool,
+    pub roll: RollResult,
+}
+
+/// Every roll made in a chapter, oldest first. Stored as `chapters/rolls_<id>.jsonl` next to the chapter file,
+/// one JSON roll per line, so a roll never rewrites the ones before it.
+#[derive(Debug, Default, Deserialize)]
+pub struct RollLog {
+    pub rolls: Vec<RollRecord>,
+}
+
+pub fn roll_log_path(root: &CampaignRoot, chapter_id: &ChapterId) -> PathBuf {
+    root.chapters_dir().join(format!("rolls_{}.jsonl", chapter_id))
+}
+
+impl RollLog {
+    pub fn load(root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<RollLog> {
+        let rolls = storage::read_json_lines(&roll_log_path(root, chapter_id), |line| serde_json::from_str(line))?;
+        Ok(RollLog { rolls })
+    }
+
+    /// Adds a roll to the end of the chapter's roll log. Earlier rolls are never rewritten.
+    fn append(root: &CampaignRoot, chapter_id: &ChapterId, record: &RollRecord) -> io::Result<()> {
+        storage::append_json_line(&roll_log_path(root, chapter_id), record)
+    }
+
+    /// Deletes a chapter's roll log, if it has one
+    pub fn remove(root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<()> {
+        match fs::remove_file(roll_log_path(root, chapter_id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The current app session, which every roll is tagged with, and the lock that keeps
+/// concurrent rolls from overwriting each other's log entries
+pub struct RollSession {
+    id: String,
+    log_lock: Mutex<()>,
+}
+
+impl RollSession {
+    /// A session named after the time it started
+    pub fn start() -> RollSession {
+        RollSession {
+            id: now_millis().to_string(),
+            log_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn lock_log(&self) -> MutexGuard<'_, ()> {
+        match self.log_lock.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Rolls `expression` and appends the roll to the chapter's roll log.
+    /// Without a `seed` a random one is picked; either way it is recorded so the roll can be replayed.
+    pub fn roll(
+        &self,
+        root: &CampaignRoot,
+        chapter_id: &ChapterId,
+        expression: &DiceExpression,
+        public: bool,
+        seed: Option<u64>,
+    ) -> io::Result<RollRecord> {
+        let seed = seed.unwrap_or_else(|| rand::thread_rng().gen_range(0..SEED_LIMIT));
+        let record = RollRecord {
+            session: self.id.clone(),
+            timestamp: now_millis(),
+            seed,
+            public,
+            roll: expression.roll_seeded(seed),
+        };
+
+        let _guard = self.lock_log();
+        RollLog::append(root, chapter_id, &record)?;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dice_values(result: &RollResult) -> Vec<(u32, bool)> {
+        result
+            .terms
+            .iter()
+            .flat_map(|term| term.dice.iter().map(|die| (die.value, die.kept)))
+            .collect()
+    }
+
+    #[test]
+    fn parses_dice_and_constants() {
+        let expression = DiceExpression::parse("2d6+3").unwrap();
+        assert_eq!(
+            expression.terms,
+            vec![(1, Term::Dice { count: 2, sides: 6, keep: None }), (1, Term::Constant(3))]
+        );
+        assert_eq!(expression.to_string(), "2d6+3");
+
+        assert_eq!(DiceExpression::parse(" -D8 - 2 ").unwrap().to_string(), "-1d8-2");
+    }
+
+    #[test]
+    fn parses_keep_highest() {
+        let expression = DiceExpression::parse("4d6kh3").unwrap();
+        assert_eq!(expression.terms, vec![(1, Term::Dice { count: 4, sides: 6, keep: Some(Keep::Highest(3)) })]);
+
+        let result = expression.roll_seeded(7);
+        let dice = dice_values(&result);
+        assert_eq!(dice.len(), 4);
+        assert_eq!(dice.iter().filter(|(_, kept)| *kept).count(), 3);
+        let dropped = dice.iter().find(|(_, kept)| !kept).unwrap().0;
+        assert!(dice.iter().all(|(value, _)| *value >= dropped));
+        assert_eq!(result.total, dice.iter().filter(|(_, kept)| *kept).map(|(value, _)| *value as i64).sum::<i64>());
+    }
+
+    #[test]
+    fn advantage_rolls_two_dice_and_keeps_one() {
+        let expression = DiceExpression::parse("1d20 adv").unwrap();
+        assert_eq!(expression.advantage, Some(Advantage::Advantage));
+        assert_eq!(expression.to_string(), "2d20kh1");
+
+        let expression = DiceExpression::parse("1d20+5 dis").unwrap();
+        assert_eq!(expression.to_string(), "2d20kl1+5");
+
+        assert!(DiceExpression::parse("2d20 adv").is_err());
+        assert!(DiceExpression::parse("1d20 adv dis").is_err());
+    }
+
+    #[test]
+    fn parses_damage_types() {
+        let expression = DiceExpression::parse("8d6 fire").unwrap();
+        assert_eq!(expression.damage_type, Some(DamageType::Fire));
+        assert_eq!(expression.to_string(), "8d6 fire");
+        assert_eq!(expression.roll_seeded(1).damage_type, Some(DamageType::Fire));
+
+        assert!(DiceExpression::parse("8d6 banana").is_err());
+        assert!(DiceExpression::parse("8d6 fire cold").is_err());
+    }
+
+    #[test]
+    fn rejects_expressions_over_the_limits() {
+        assert!(DiceExpression::parse("100d6").is_ok());
+        assert!(DiceExpression::parse("101d6").is_err());
+        assert!(DiceExpression::parse("60d6+41d4").is_err());
+        assert!(DiceExpression::parse("1d1001").is_err());
+        assert!(DiceExpression::parse("1d6+100001").is_err());
+        assert!(DiceExpression::parse("99999999999999999999d6").is_err());
+        assert!(DiceExpression::parse(&vec!["1"; MAX_TERMS + 1].join("+")).is_err());
+        assert!(DiceExpression::parse("4d6kh5").is_err());
+        assert!(DiceExpression::parse("0d6").is_err());
+        assert!(DiceExpression::parse("").is_err());
+    }
+
+    #[test]
+    fn seeded_rolls_are_reproducible() {
+        let expression = DiceExpression::parse("10d20+4d6kl2-3").unwrap();
+        let first = expression.roll_seeded(1234);
+        let second = expression.roll_seeded(1234);
+        assert_eq!(first.total, second.total);
+        assert_eq!(dice_values(&first), dice_values(&second));
+        assert!(dice_values(&first).iter().all(|(value, _)| (1..=20).contains(value)));
+
+        let others: Vec<i64> = (0..20).map(|seed| expression.roll_seeded(seed).total).collect();
+        assert!(others.iter().any(|total| *total != others[0]));
+    }
+}
@@ -0,0 +1,72 @@
+// duplicate.rs
+use std::collections::HashSet;
+
+use crate::hex::{Hex, Layout};
+
+/// Most copies `duplicate_entity` makes at once
+pub const MAX_COPIES: u32 = 50;
+
+/// Names for `count` copies of an entity named `name`, numbered after the highest number already in use.
+/// Copies of "Goblin" or "Goblin 2" next to "Goblin" and "Goblin 2" are "Goblin 3", "Goblin 4" and so on.
+/// Copies of an unnamed entity stay unnamed.
+pub fn numbered_names<'a>(name: &str, existing: impl IntoIterator<Item = &'a str>, count: u32) -> Vec<String> {
+    let base = strip_number(name.trim());
+    if base.is_empty() {
+        return vec![String::new(); count as usize];
+    }
+
+    let highest = existing
+        .into_iter()
+        .filter_map(|existing| {
+            let existing = existing.trim();
+            if existing == base {
+                return Some(1);
+            }
+            existing
+                .strip_prefix(base)
+                .and_then(|rest| rest.strip_prefix(' '))
+                .and_then(|number| number.parse::<u32>().ok())
+        })
+        .max()
+        .unwrap_or(1);
+
+    // Widened, so numbering past "Goblin 4294967295" keeps counting up instead of overflowing
+    (1..=count)
+        .map(|offset| format!("{} {}", base, u64::from(highest) + u64::from(offset)))
+        .collect()
+}
+
+/// "Goblin 2" -> "Goblin"
+pub fn strip_number(name: &str) -> &str {
+    match name.rsplit_once(' ') {
+        Some((base, number)) if !base.is_empty() && number.parse::<u32>().is_ok() => base.trim_end(),
+        _ => name,
+    }
+}
+
+/// The `count` hexes on the display closest to `origin` that no entity stands on, nearest first.
+/// Fewer are returned if the display runs out of free hexes.
+pub fn free_hexes_around(layout: &Layout, origin: Hex, occupied: impl IntoIterator<Item = Hex>, count: u32) -> Vec<Hex> {
+    let occupied: HashSet<Hex> = occupied.into_iter().collect();
+    // Rings further out than the furthest hex on the display hold no hex on it
+    let furthest = layout
+        .display_hexes()
+        .into_iter()
+        .map(|hex| origin.distance(hex))
+        .max()
+        .unwrap_or(0);
+
+    let mut free = Vec::new();
+    for radius in 1..=furthest {
+        for hex in origin.ring(radius) {
+            if !layout.on_display(hex) || occupied.contains(&hex) {
+                continue;
+            }
+            free.push(hex);
+            if free.len() == count as usize {
+                return free;
+            }
+        }
+    }
+    free
+}
@@ -0,0 +1,140 @@
+// error.rs
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+pub type TableauResult<T> = Result<T, TableauError>;
+
+/// Error returned by every Tauri command. Serializes as `{ "code", "message", "context" }`
+/// so the frontend can branch on `code` instead of parsing the message.
+#[derive(Debug, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum TableauError {
+    /// A chapter, battlemap, entity or file does not exist
+    NotFound(ErrorDetails),
+    /// Creating something that is already there
+    AlreadyExists(ErrorDetails),
+    /// A campaign file exists but its contents are unusable
+    InvalidData(ErrorDetails),
+    /// Reading or writing the disk failed
+    Io(ErrorDetails),
+    /// The user closed a file or folder dialog without choosing anything
+    Cancelled(ErrorDetails),
+    /// The arguments passed to the command were rejected
+    Validation(ErrorDetails),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorDetails {
+    pub message: String,
+    pub context: ErrorContext,
+}
+
+/// What the failing command was working on, when known
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chapter_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battlemap: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl ErrorDetails {
+    fn new(message: impl Into<String>) -> Self {
+        ErrorDetails {
+            message: message.into(),
+            context: ErrorContext::default(),
+        }
+    }
+}
+
+impl TableauError {
+    pub fn not_found(message: impl Into<String>) -> Self {
+        TableauError::NotFound(ErrorDetails::new(message))
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        TableauError::Cancelled(ErrorDetails::new(message))
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        TableauError::Validation(ErrorDetails::new(message))
+    }
+
+    /// Wraps an I/O error as `"<action>: <error>"`, keeping not found, already exists and
+    /// invalid data distinguishable from other disk failures.
+    pub fn io(action: &str, err: io::Error) -> Self {
+        let mut error = TableauError::from(err);
+        let details = error.details_mut();
+        details.message = format!("{}: {}", action, details.message);
+        error
+    }
+
+    /// Wraps a failure that is not an `io::Error`, such as an image that failed to encode
+    pub fn other(action: &str, err: impl fmt::Display) -> Self {
+        TableauError::Io(ErrorDetails::new(format!("{}: {}", action, err)))
+    }
+
+    pub fn with_chapter(mut self, chapter_id: impl AsRef<str>) -> Self {
+        self.details_mut().context.chapter_id = Some(chapter_id.as_ref().to_string());
+        self
+    }
+
+    pub fn with_battlemap(mut self, battlemap: &str) -> Self {
+        self.details_mut().context.battlemap = Some(battlemap.to_string());
+        self
+    }
+
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.details_mut().context.path = Some(path.as_ref().display().to_string());
+        self
+    }
+
+    pub fn details(&self) -> &ErrorDetails {
+        match self {
+            TableauError::NotFound(details)
+            | TableauError::AlreadyExists(details)
+            | TableauError::InvalidData(details)
+            | TableauError::Io(details)
+            | TableauError::Cancelled(details)
+            | TableauError::Validation(details) => details,
+        }
+    }
+
+    fn details_mut(&mut self) -> &mut ErrorDetails {
+        match self {
+            TableauError::NotFound(details)
+            | TableauError::AlreadyExists(details)
+            | TableauError::InvalidData(details)
+            | TableauError::Io(details)
+            | TableauError::Cancelled(details)
+            | TableauError::Validation(details) => details,
+        }
+    }
+}
+
+impl fmt::Display for TableauError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.details().message)
+    }
+}
+
+impl std::error::Error for TableauError {}
+
+impl From<io::Error> for TableauError {
+    fn from(err: io::Error) -> Self {
+        let details = ErrorDetails::new(err.to_string());
+        match err.kind() {
+            io::ErrorKind::NotFound => TableauError::NotFound(details),
+            io::ErrorKind::AlreadyExists => TableauError::AlreadyExists(details),
+            io::ErrorKind::InvalidData => TableauError::InvalidData(details),
+            io::ErrorKind::InvalidInput => TableauError::Validation(details),
+            _ => TableauError::Io(details),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// fog.rs
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::campaign::{AssetKind, CampaignRoot};
+use crate::hex::{Hex, Layout};
+use crate::models::Coordinates;
+use crate::refs::{AssetRef, ChapterId};
+
+/// Largest radius `reveal_fog` and `hide_fog` take
+pub const MAX_RADIUS: u32 = 50;
+
+/// How far the party sees in a new combat's light, in feet
+pub const DEFAULT_VISION_RADIUS: u32 = 60;
+
+/// Fog of war over a combat's map. While enabled, the display hides every hex that has not been revealed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fog {
+    pub enabled: bool,
+    pub revealed: BTreeSet<Hex>,
+    /// Missing, like `vision_radius`, from snapshots taken before vision existed
+    #[serde(default)]
+    pub mode: FogMode,
+    /// How far the party sees in the combat's light, in feet. Darkvision can see farther.
+    #[serde(default = "default_vision_radius")]
+    pub vision_radius: u32,
+}
+
+fn default_vision_radius() -> u32 {
+    DEFAULT_VISION_RADIUS
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Fog {
+            enabled: false,
+            revealed: BTreeSet::new(),
+            mode: FogMode::Manual,
+            vision_radius: DEFAULT_VISION_RADIUS,
+        }
+    }
+}
+
+/// How the fog follows what the party sees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FogMode {
+    /// Only the DM reveals and hides hexes
+    #[default]
+    Manual,
+    /// Exactly what the party sees right now is revealed; hexes it can no longer see are hidden again
+    Dynamic,
+    /// What the party sees is revealed and stays revealed, so explored areas remain visible
+    Sticky,
+}
+
+impl Fog {
+    /// Updates the revealed hexes to what the party sees, as the mode says. Does nothing while the fog is off.
+    pub fn apply_vision(&mut self, seen: BTreeSet<Hex>) {
+        if !self.enabled {
+            return;
+        }
+        match self.mode {
+            FogMode::Manual => {}
+            FogMode::Dynamic => self.revealed = seen,
+            FogMode::Sticky => self.revealed.extend(seen),
+        }
+    }
+
+    pub fn reveal(&mut self, hexes: impl IntoIterator<Item = Hex>) {
+        self.revealed.extend(hexes);
+    }
+
+    pub fn hide(&mut self, hexes: impl IntoIterator<Item = Hex>) {
+        for hex in hexes {
+            self.revealed.remove(&hex);
+        }
+    }
+}
+
+/// Hexes to reveal or hide
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum FogArea {
+    Hex { hex: Hex },
+    /// Every hex at most `radius` steps from `center`
+    Radius { center: Hex, radius: u32 },
+    /// Every hex on the display whose center lies inside the polygon, given in pixels of the reference container
+    Polygon { points: Vec<Coordinates> },
+}
+
+impl FogArea {
+    pub fn hexes(&self, layout: &Layout) -> Result<Vec<Hex>, String> {
+        match self {
+            FogArea::Hex { hex } => Ok(vec![*hex]),
+            FogArea::Radius { center, radius } => {
+                if *radius > MAX_RADIUS {
+                    return Err(format!("The radius can be at most {} hexes.", MAX_RADIUS));
+                }
+                Ok(center.within(*radius))
+            }
+            FogArea::Polygon { points } => {
+                if points.len() < 3 {
+                    return Err("A polygon needs at least three points.".to_string());
+                }
+                Ok(layout
+                    .display_hexes()
+                    .into_iter()
+                    .filter(|&hex| contains(points, layout.center(hex)))
+                    .collect())
+            }
+        }
+    }
+}
+
+/// Even-odd test: a point is inside if a ray from it crosses the outline an odd number of times
+fn contains(polygon: &[Coordinates], point: Coordinates) -> bool {
+    let mut inside = false;
+    let mut previous = polygon[polygon.len() - 1];
+    for &current in polygon {
+        if (current.y > point.y) != (previous.y > point.y) {
+            let crossing = previous.x + (point.y - previous.y) / (current.y - previous.y) * (current.x - previous.x);
+            if point.x < crossing {
+                inside = !inside;
+            }
+        }
+        previous = current;
+    }
+    inside
+}
+
+/// Fog masks are rendered to `assets/fog/<chapter id>/<battlemap>.png`, where the display can load them
+pub fn chapter_masks_dir(root: &CampaignRoot, chapter_id: &ChapterId) -> PathBuf {
+    root.assets_dir(AssetKind::Fog).join(chapter_id.as_str())
+}
+
+pub fn mask_path(root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> io::Result<PathBuf> {
+    let battlemap = AssetRef::parse(battlemap)?;
+    Ok(chapter_masks_dir(root, chapter_id).join(format!("{}.png", battlemap)))
+}
+
+/// Deletes a combat's fog mask, if it has one
+pub fn remove(root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> io::Result<()> {
+    match fs::remove_file(mask_path(root, chapter_id, battlemap)?) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Deletes the fog masks of every combat in a chapter
+pub fn remove_chapter(root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<()> {
+    match fs::remove_dir_all(chapter_masks_dir(root, chapter_id)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,384 @@
+// gc.rs
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use crate::bestiary::{self, Bestiary};
+use crate::campaign::{AssetKind, CampaignRoot};
+use crate::history::Journal;
+use crate::models::Chapter;
+use crate::refs::{ChapterId, EntityId};
+use crate::snapshots;
+use crate::storage;
+use crate::store::CampaignStore;
+
+/// Files modified more recently than this are never collected. `add_entity` writes the icon and
+/// entity file before the chapter references them, so a brand new entity briefly looks orphaned.
+const GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// The groups of campaign files the collector looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GcCategory {
+    Landscapes,
+    Splashes,
+    Battlemaps,
+    Hexgrids,
+    EntityIcons,
+    EntityFiles,
+    /// Uploaded images entity icons are cropped from
+    IconImages,
+    TemplateIcons,
+    /// Entity icons copied into a chapter's snapshots
+    SnapshotIcons,
+}
+
+impl GcCategory {
+    const ALL: [GcCategory; 9] = [
+        GcCategory::Landscapes,
+        GcCategory::Splashes,
+        GcCategory::Battlemaps,
+        GcCategory::Hexgrids,
+        GcCategory::EntityIcons,
+        GcCategory::EntityFiles,
+        GcCategory::IconImages,
+        GcCategory::TemplateIcons,
+        GcCategory::SnapshotIcons,
+    ];
+
+    fn directories(self, root: &CampaignRoot) -> io::Result<Vec<PathBuf>> {
+        let directory = match self {
+            GcCategory::Landscapes => root.assets_dir(AssetKind::Landscapes),
+            GcCategory::Splashes => root.assets_dir(AssetKind::Splashes),
+            GcCategory::Battlemaps => root.assets_dir(AssetKind::Battlemaps),
+            GcCategory::Hexgrids => root.assets_dir(AssetKind::Hexgrids),
+            GcCategory::EntityIcons => root.assets_dir(AssetKind::Entities),
+            GcCategory::EntityFiles => root.entities_dir(),
+            GcCategory::IconImages => root.assets_dir(AssetKind::IconImages),
+            GcCategory::TemplateIcons => bestiary::icons_dir(root),
+            GcCategory::SnapshotIcons => {
+                return Ok(snapshots::chapters_with_snapshots(root)?
+                    .iter()
+                    .map(|chapter_id| snapshots::icons_dir(root, chapter_id))
+                    .collect())
+            }
+        };
+        Ok(vec![directory])
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrphanedFile {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryReport {
+    pub category: GcCategory,
+    pub files: Vec<OrphanedFile>,
+    pub total_bytes: u64,
+}
+
+/// What a collection found, and removed unless it was a dry run
+#[derive(Debug, Serialize)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub categories: Vec<CategoryReport>,
+    pub total_bytes: u64,
+    /// Entries in `entity_ids.txt` whose entity no longer exists
+    pub stale_entity_ids: usize,
+    /// Entries in the asset index whose file no longer exists
+    pub stale_asset_names: usize,
+}
+
+/// Every file name the campaign's chapters, bestiary and snapshots point at
+#[derive(Default)]
+struct References {
+    landscapes: HashSet<String>,
+    splashes: HashSet<String>,
+    battlemaps: HashSet<String>,
+    entity_ids: HashSet<String>,
+    /// Icon images still open in the icon editor. Nothing refers to an icon image once its crop is done.
+    icon_images: HashSet<String>,
+    template_icons: HashSet<String>,
+    snapshot_icons: HashSet<PathBuf>,
+}
+
+impl References {
+    fn add_chapter(&mut self, chapter: Chapter) {
+        self.landscapes.extend(chapter.landscapes);
+        self.splashes.extend(chapter.splashes.into_iter().map(|splash| splash.image));
+        for combat in chapter.combat {
+            self.battlemaps.insert(combat.battlemap);
+            self.entity_ids.extend(combat.entities.iter().map(|entity| entity_id_of(entity).to_string()));
+        }
+    }
+
+    fn contains(&self, category: GcCategory, path: &Path, file_name: &str) -> bool {
+        match category {
+            GcCategory::Landscapes => self.landscapes.contains(file_name),
+            GcCategory::Splashes => self.splashes.contains(file_name),
+            GcCategory::Battlemaps => self.battlemaps.contains(file_name),
+            // Hex grids are named after their battlemap, with a `.meta` file alongside
+            GcCategory::Hexgrids => self.battlemaps.contains(file_name.strip_suffix(".meta").unwrap_or(file_name)),
+            GcCategory::EntityIcons | GcCategory::EntityFiles => self.entity_ids.contains(entity_id_of(file_name)),
+            GcCategory::IconImages => self.icon_images.contains(file_name),
+            GcCategory::TemplateIcons => self.template_icons.contains(file_name),
+            GcCategory::SnapshotIcons => self.snapshot_icons.contains(path),
+        }
+    }
+}
+
+/// Entity files are `<id>.json` and `<id>.png`, plus backups such as `<id>.json.last-good`
+fn entity_id_of(file_name: &str) -> &str {
+    file_name.split('.').next().unwrap_or(file_name)
+}
+
+/// Reads every chapter and collects the files they reference.
+/// Fails if any chapter cannot be read, since its files would otherwise look unreferenced.
+fn collect_references(store: &CampaignStore, root: &CampaignRoot) -> io::Result<References> {
+    let mut references = References::default();
+
+    for file_name in crate::utils::list_files_in_directory(&root.chapters_dir())? {
+        let Some(chapter_id) = ChapterId::from_file_name(&file_name) else {
+            continue;
+        };
+
+        let chapter = store.read_chapter(root, &chapter_id).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Cannot tell which files are in use because chapter '{}' failed to load: {}", chapter_id, e),
+            )
+        })?;
+
+        references.add_chapter(chapter);
+    }
+
+    Ok(references)
+}
+
+/// Adds the icons the bestiary's templates and the chapters' snapshots use.
+/// Fails if the bestiary or a snapshot file cannot be read, for the same reason as `collect_references`.
+fn collect_icon_references(root: &CampaignRoot, references: &mut References) -> io::Result<()> {
+    let bestiary = Bestiary::load(root).map_err(|e| {
+        io::Error::new(e.kind(), format!("Cannot tell which files are in use because the bestiary failed to load: {}", e))
+    })?;
+    references.template_icons.extend(bestiary.templates.into_iter().map(|template| template.icon));
+
+    for chapter_id in snapshots::chapters_with_snapshots(root)? {
+        let icons = snapshots::referenced_icons(root, &chapter_id).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Cannot tell which files are in use because the snapshots of chapter '{}' failed to load: {}", chapter_id, e),
+            )
+        })?;
+        references.snapshot_icons.extend(icons);
+    }
+
+    Ok(())
+}
+
+fn is_recent(metadata: &fs::Metadata, now: SystemTime) -> bool {
+    match metadata.modified() {
+        Ok(modified) => now.duration_since(modified).map_or(true, |age| age < GRACE_PERIOD),
+        Err(_) => true,
+    }
+}
+
+fn scan_category(
+    root: &CampaignRoot,
+    category: GcCategory,
+    references: &References,
+    now: SystemTime,
+) -> io::Result<CategoryReport> {
+    let mut report = CategoryReport {
+        category,
+        files: Vec::new(),
+        total_bytes: 0,
+    };
+
+    let entity_ids_file = root.entity_ids_file();
+    for directory in category.directories(root)? {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if !metadata.is_file()
+                || path == entity_ids_file
+                || references.contains(category, &path, file_name)
+                || is_recent(&metadata, now)
+            {
+                continue;
+            }
+
+            report.total_bytes += metadata.len();
+            report.files.push(OrphanedFile {
+                path: path.display().to_string(),
+                size: metadata.len(),
+            });
+        }
+    }
+
+    report.files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+/// Finds every asset and entity file no chapter, template or snapshot references and, unless `dry_run` is set,
+/// deletes them and drops deleted entities from `entity_ids.txt` and deleted assets from the asset index.
+/// Files an undo or redo step can bring back into a chapter count as referenced. The trash only holds
+/// copies that the journal restores by itself, so it is left alone. `pending_crops` are the icon images
+/// still open in the icon editor.
+pub fn collect_garbage(
+    store: &CampaignStore,
+    root: &CampaignRoot,
+    journal: &Journal,
+    pending_crops: HashSet<String>,
+    dry_run: bool,
+) -> io::Result<GcReport> {
+    let mut references = collect_references(store, root)?;
+    collect_icon_references(root, &mut references)?;
+    references.icon_images = pending_crops;
+    let (chapters, entities) = journal.held();
+    chapters.into_iter().for_each(|chapter| references.add_chapter(chapter));
+    references.entity_ids.extend(entities.iter().map(EntityId::to_string));
+
+    let now = SystemTime::now();
+
+    let categories = GcCategory::ALL
+        .iter()
+        .map(|&category| scan_category(root, category, &references, now))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let orphaned: HashSet<PathBuf> = categories
+        .iter()
+        .flat_map(|category| category.files.iter().map(|file| PathBuf::from(&file.path)))
+        .collect();
+
+    let stale_entity_ids = prune_entity_ids(root, &references, &orphaned, dry_run, now)?;
+    let is_gone = |path: &Path| orphaned.contains(path) || !path.exists();
+    let stale_asset_names = store.read_asset_index(root)?.prune(root, is_gone);
+
+    if !dry_run {
+        for path in &orphaned {
+            remove_if_exists(path)?;
+            store.evict(path);
+        }
+        if stale_asset_names > 0 {
+            store.update_asset_index(root, |index| Ok(index.prune(root, is_gone)))?;
+        }
+    }
+
+    Ok(GcReport {
+        dry_run,
+        total_bytes: categories.iter().map(|category| category.total_bytes).sum(),
+        categories,
+        stale_entity_ids,
+        stale_asset_names,
+    })
+}
+
+/// Counts (and unless `dry_run`, removes) IDs whose entity is unreferenced and has no files left
+/// once `orphaned` is gone. Skipped while an entity may be in the middle of being added.
+fn prune_entity_ids(
+    root: &CampaignRoot,
+    references: &References,
+    orphaned: &HashSet<PathBuf>,
+    dry_run: bool,
+    now: SystemTime,
+) -> io::Result<usize> {
+    let path = root.entity_ids_file();
+    let metadata = match fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    if is_recent(&metadata, now) {
+        return Ok(0);
+    }
+
+    let survives = |file: PathBuf| file.exists() && !orphaned.contains(&file);
+    let content = fs::read_to_string(&path)?;
+    let (kept, stale): (Vec<&str>, Vec<&str>) = content.lines().filter(|id| !id.is_empty()).partition(|id| {
+        references.entity_ids.contains(*id)
+            || survives(root.entities_dir().join(format!("{}.json", id)))
+            || survives(root.assets_dir(AssetKind::Entities).join(format!("{}.png", id)))
+    });
+
+    if !dry_run && !stale.is_empty() {
+        let mut new_content = kept.join("\n");
+        new_content.push('\n');
+        storage::write_atomic(&path, new_content.as_bytes())?;
+    }
+
+    Ok(stale.len())
+}
+
+/// Deletes the files of the given entities (`<id>.json` filenames) that no chapter references any more.
+/// Used to cascade the removal of a combat or chapter to its entities.
+pub fn remove_unreferenced_entities(
+    store: &CampaignStore,
+    root: &CampaignRoot,
+    entity_filenames: &[String],
+) -> io::Result<()> {
+    if entity_filenames.is_empty() {
+        return Ok(());
+    }
+
+    let references = collect_references(store, root)?;
+
+    let doomed: HashSet<&str> = entity_filenames
+        .iter()
+        .map(|filename| entity_id_of(filename))
+        .filter(|id| !references.entity_ids.contains(*id))
+        .collect();
+    if doomed.is_empty() {
+        return Ok(());
+    }
+
+    let directories = [GcCategory::EntityIcons, GcCategory::EntityFiles]
+        .iter()
+        .map(|category| category.directories(root))
+        .collect::<io::Result<Vec<_>>>()?;
+    for directory in directories.into_iter().flatten() {
+        let Ok(entries) = fs::read_dir(directory) else {
+            continue;
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            let belongs_to_doomed = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| doomed.contains(entity_id_of(name)));
+
+            if belongs_to_doomed && path != root.entity_ids_file() {
+                remove_if_exists(&path)?;
+                store.evict(&path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
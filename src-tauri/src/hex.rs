@@ -0,0 +1,208 @@
+// hex.rs
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Combat, Coordinates};
+
+/// A hex of a battlemap's grid in axial coordinates. Hexes are pointy-topped: `r` counts rows downwards and
+/// `q` counts hexes to the right along a row; hex `(q, r + 1)` sits half a hex to the right of `(q, r)`.
+/// The third cube coordinate is `s = -q - r`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct Hex {
+    pub q: i32,
+    pub r: i32,
+}
+
+/// Longest line `line_to` is asked for by the `hex_line` command, in steps. Far more hexes than fit across
+/// the display at any usable grid size, while keeping the line's allocation small.
+pub const MAX_LINE_LENGTH: u32 = 1000;
+
+/// Steps to the six neighbors, clockwise from east
+const DIRECTIONS: [Hex; 6] = [
+    Hex::new(1, 0),
+    Hex::new(0, 1),
+    Hex::new(-1, 1),
+    Hex::new(-1, 0),
+    Hex::new(0, -1),
+    Hex::new(1, -1),
+];
+
+impl Hex {
+    pub const fn new(q: i32, r: i32) -> Hex {
+        Hex { q, r }
+    }
+
+    /// Widened, since `-q - r` does not fit an `i32` for every hex
+    pub fn s(self) -> i64 {
+        -i64::from(self.q) - i64::from(self.r)
+    }
+
+    /// The hex at `column`, `row` of a grid whose odd rows are shifted half a hex to the right,
+    /// the layout the display's grid cells use
+    pub fn from_offset(column: i32, row: i32) -> Hex {
+        Hex::new(column - (row - (row & 1)) / 2, row)
+    }
+
+    /// The hex containing a fractional axial position
+    pub fn round(q: f64, r: f64) -> Hex {
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+
+        // Rounding each coordinate separately can leave q + r + s != 0; fix the one that moved most
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+        Hex::new(rq as i32, rr as i32)
+    }
+
+    pub fn neighbors(self) -> [Hex; 6] {
+        DIRECTIONS.map(|direction| self + direction)
+    }
+
+    /// Every hex at most `radius` steps away, nearest first
+    pub fn within(self, radius: u32) -> Vec<Hex> {
+        (0..=radius).flat_map(|ring| self.ring(ring)).collect()
+    }
+
+    /// Number of steps between two hexes, saturating at `u32::MAX` for hexes further apart than that
+    pub fn distance(self, other: Hex) -> u32 {
+        let dq = i64::from(self.q) - i64::from(other.q);
+        let dr = i64::from(self.r) - i64::from(other.r);
+        let ds = self.s() - other.s();
+        let steps = dq.unsigned_abs().max(dr.unsigned_abs()).max(ds.unsigned_abs());
+        u32::try_from(steps).unwrap_or(u32::MAX)
+    }
+
+    /// The hexes exactly `radius` steps away, clockwise from the north-west one. A ring of radius 0 is the hex itself.
+    pub fn ring(self, radius: u32) -> Vec<Hex> {
+        if radius == 0 {
+            return vec![self];
+        }
+
+        let radius = radius as i32;
+        let mut hex = self + DIRECTIONS[4] * radius;
+        let mut ring = Vec::with_capacity(6 * radius as usize);
+        for direction in DIRECTIONS {
+            for _ in 0..radius {
+                ring.push(hex);
+                hex = hex + direction;
+            }
+        }
+        ring
+    }
+
+    /// The hexes a straight line from this hex to `other` passes through, both ends included
+    pub fn line_to(self, other: Hex) -> Vec<Hex> {
+        let steps = self.distance(other);
+        if steps == 0 {
+            return vec![self];
+        }
+
+        // Nudge the line off hex edges, so points exactly between two hexes always round the same way
+        const NUDGE: f64 = 1e-6;
+        let (q0, r0) = (self.q as f64 + NUDGE, self.r as f64 + NUDGE);
+        let (q1, r1) = (other.q as f64 + NUDGE, other.r as f64 + NUDGE);
+        (0..=steps)
+            .map(|step| {
+                let t = step as f64 / steps as f64;
+                Hex::round(q0 + (q1 - q0) * t, r0 + (r1 - r0) * t)
+            })
+            .collect()
+    }
+}
+
+impl std::ops::Add for Hex {
+    type Output = Hex;
+
+    fn add(self, other: Hex) -> Hex {
+        Hex::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl std::ops::Mul<i32> for Hex {
+    type Output = Hex;
+
+    fn mul(self, factor: i32) -> Hex {
+        Hex::new(self.q * factor, self.r * factor)
+    }
+}
+
+/// Size of the display's reference container in pixels; the display scales everything from it
+pub const DISPLAY_WIDTH: f32 = 1667.0;
+pub const DISPLAY_HEIGHT: f32 = 953.0;
+
+/// Where hexes sit on a battlemap's display: `size` is the distance from a hex's center to its corners and
+/// `origin` the pixel position of hex (0, 0). Pixels are those of the display's reference container.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub size: f32,
+    pub origin: Coordinates,
+}
+
+impl Layout {
+    pub fn new(size: f32, origin: Coordinates) -> Layout {
+        Layout { size, origin }
+    }
+
+    /// The layout of a combat's grid, from its `gridsize` and `gridoffset`
+    pub fn of(combat: &Combat) -> Layout {
+        Layout::new(
+            combat.gridsize as f32,
+            Coordinates {
+                x: combat.gridoffset.x as f32,
+                y: combat.gridoffset.y as f32,
+            },
+        )
+    }
+
+    /// The pixel position of a hex's center
+    pub fn center(&self, hex: Hex) -> Coordinates {
+        let sqrt3 = 3.0_f32.sqrt();
+        Coordinates {
+            x: self.origin.x + self.size * (sqrt3 * hex.q as f32 + sqrt3 / 2.0 * hex.r as f32),
+            y: self.origin.y + self.size * (1.5 * hex.r as f32),
+        }
+    }
+
+    /// Whether the hex's center lies on the display
+    pub fn on_display(&self, hex: Hex) -> bool {
+        let center = self.center(hex);
+        (0.0..=DISPLAY_WIDTH).contains(&center.x) && (0.0..=DISPLAY_HEIGHT).contains(&center.y)
+    }
+
+    /// Every hex whose center lies on the display, row by row
+    pub fn display_hexes(&self) -> Vec<Hex> {
+        let (width, height) = (3.0_f32.sqrt() * self.size, 1.5 * self.size);
+        let rows = (-self.origin.y / height).ceil() as i32..=((DISPLAY_HEIGHT - self.origin.y) / height).floor() as i32;
+        rows.flat_map(|r| {
+            let first = (-self.origin.x / width - r as f32 / 2.0).ceil() as i32;
+            let last = ((DISPLAY_WIDTH - self.origin.x) / width - r as f32 / 2.0).floor() as i32;
+            (first..=last).map(move |q| Hex::new(q, r))
+        })
+        .filter(|&hex| self.on_display(hex))
+        .collect()
+    }
+
+    /// The hex containing a pixel
+    pub fn hex_at(&self, point: Coordinates) -> Hex {
+        let x = f64::from((point.x - self.origin.x) / self.size);
+        let y = f64::from((point.y - self.origin.y) / self.size);
+        let q = 3.0_f64.sqrt() / 3.0 * x - y / 3.0;
+        let r = 2.0 / 3.0 * y;
+        Hex::round(q, r)
+    }
+
+    /// The pixel positions of a hex's six corners, clockwise from the top one
+    pub fn corners(&self, hex: Hex) -> [Coordinates; 6] {
+        let center = self.center(hex);
+        std::array::from_fn(|corner| {
+            let angle = (60.0 * corner as f32 - 90.0).to_radians();
+            Coordinates {
+                x: center.x + self.size * angle.cos(),
+                y: center.y + self.size * angle.sin(),
+            }
+        })
+    }
+}
@@ -0,0 +1,552 @@
+// history.rs
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::campaign::CampaignRoot;
+use crate::models::{Chapter, Combat, Entity};
+use crate::refs::{ChapterId, EntityId};
+use crate::store::CampaignStore;
+
+/// Most recent changes kept per chapter. Older ones are dropped along with their copies in the trash.
+const MAX_HISTORY: usize = 50;
+
+/// Coalescing transactions with the same label this close together become a single undo step
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+/// One file's contents before and after a change
+enum Change {
+    /// `None` if the chapter file did not exist
+    Chapter { chapter_id: ChapterId, before: Option<Value>, after: Option<Value> },
+    /// `None` if the entity file did not exist
+    Entity { id: EntityId, before: Option<Value>, after: Option<Value> },
+    /// Any other file, e.g. an entity icon. The versions are copies in the trash; `None` if the file did not exist.
+    File { path: PathBuf, before: Option<PathBuf>, after: Option<PathBuf> },
+}
+
+impl Change {
+    /// Puts the file back to its contents before (`undo`) or after the change
+    fn apply(&self, store: &CampaignStore, root: &CampaignRoot, undo: bool) -> io::Result<()> {
+        match self {
+            Change::Chapter { chapter_id, before, after } => match if undo { before } else { after } {
+                Some(value) => store.write_chapter(root, chapter_id, from_value::<Chapter>(value)?),
+                None => remove_if_exists(&chapter_id.file_path(root), |path| store.remove_chapter_file(path)),
+            },
+            Change::Entity { id, before, after } => match if undo { before } else { after } {
+                Some(value) => store.write_entity(root, id, from_value::<Entity>(value)?),
+                None => remove_if_exists(&id.json_path(root), |path| store.remove_entity_file(path)),
+            },
+            Change::File { path, before, after } => match if undo { before } else { after } {
+                Some(copy) => {
+                    // The directory may have been deleted along with the file
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::copy(copy, path).map(|_| ())
+                }
+                None => remove_if_exists(path, |path| fs::remove_file(path)),
+            },
+        }
+    }
+
+    /// The file the change is to
+    fn path(&self, root: &CampaignRoot) -> PathBuf {
+        match self {
+            Change::Chapter { chapter_id, .. } => chapter_id.file_path(root),
+            Change::Entity { id, .. } => id.json_path(root),
+            Change::File { path, .. } => path.clone(),
+        }
+    }
+
+    /// Whether the file is still the way the change left it (`undo`) or the way it was before the change (redo)
+    fn is_current(&self, store: &CampaignStore, root: &CampaignRoot, undo: bool) -> io::Result<bool> {
+        Ok(match self {
+            Change::Chapter { chapter_id, before, after } => {
+                read_chapter_value(store, root, chapter_id)? == *if undo { after } else { before }
+            }
+            Change::Entity { id, before, after } => read_entity_value(store, root, id)? == *if undo { after } else { before },
+            Change::File { path, before, after } => same_contents(if undo { after } else { before }.as_deref(), path),
+        })
+    }
+
+    /// Deletes the trash copies this change holds on to
+    fn discard(&self) {
+        if let Change::File { before, after, .. } = self {
+            for copy in [before, after].into_iter().flatten() {
+                if let Err(e) = remove_if_exists(copy, |path| fs::remove_file(path)) {
+                    log::warn!("Failed to empty '{}' from the trash: {}", copy.display(), e);
+                }
+            }
+        }
+    }
+}
+
+fn from_value<T: serde::de::DeserializeOwned>(value: &Value) -> io::Result<T> {
+    serde_json::from_value(value.clone()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn remove_if_exists(path: &Path, remove: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<()> {
+    match remove(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// One undo step: everything a single command changed
+struct Entry {
+    label: String,
+    coalesce: bool,
+    updated: Instant,
+    changes: Vec<Change>,
+    /// Step and battlemap of every combat log the command recorded events in
+    combat_logs: Vec<(u64, String)>,
+}
+
+impl Entry {
+    fn discard(&self) {
+        self.changes.iter().for_each(Change::discard);
+    }
+
+    /// Folds a later change to the same files into this entry, keeping this entry's `before` states
+    fn merge(&mut self, later: Entry) {
+        for change in later.changes {
+            let existing = self.changes.iter_mut().find(|existing| match (&**existing, &change) {
+                (Change::Chapter { chapter_id: a, .. }, Change::Chapter { chapter_id: b, .. }) => a == b,
+                (Change::Entity { id: a, .. }, Change::Entity { id: b, .. }) => a == b,
+                _ => false,
+            });
+            match (existing, change) {
+                (Some(Change::Chapter { after, .. }), Change::Chapter { after: later, .. }) => *after = later,
+                (Some(Change::Entity { after, .. }), Change::Entity { after: later, .. }) => *after = later,
+                (_, change) => self.changes.push(change),
+            }
+        }
+        self.combat_logs.extend(later.combat_logs);
+        self.updated = later.updated;
+    }
+}
+
+#[derive(Default)]
+struct History {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+}
+
+/// A step that `undo` or `redo` went over
+pub struct SteppedOver {
+    pub label: String,
+    /// Step and battlemap of every combat log the step recorded events in
+    pub combat_logs: Vec<(u64, String)>,
+}
+
+/// What the history panel shows: the labels of the steps that can be undone and redone, most recent first
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryInfo {
+    pub undo: Vec<String>,
+    pub redo: Vec<String>,
+}
+
+/// Undo and redo history of every chapter of the open campaign. Kept in memory for the session; files
+/// deleted by a recorded command wait in `<campaign>/trash` until their step falls out of the history.
+#[derive(Default)]
+pub struct Journal {
+    histories: Mutex<HashMap<ChapterId, History>>,
+    next_id: AtomicU64,
+}
+
+impl Journal {
+    fn lock(&self) -> MutexGuard<'_, HashMap<ChapterId, History>> {
+        match self.histories.lock() {
+            Ok(histories) => histories,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Starts recording a command that changes `chapter_id`, capturing the chapter as it is now.
+    /// The chapter need not exist yet, so creating it can be recorded too.
+    pub fn begin<'a>(
+        &self,
+        store: &'a CampaignStore,
+        root: &CampaignRoot,
+        chapter_id: &ChapterId,
+        label: &str,
+    ) -> io::Result<Transaction<'a>> {
+        let chapter = read_chapter_value(store, root, chapter_id)?;
+        Ok(Transaction {
+            store,
+            root: root.clone(),
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            chapter_id: chapter_id.clone(),
+            label: label.to_string(),
+            coalesce: false,
+            chapter,
+            entities: Vec::new(),
+            files: Vec::new(),
+            // Combat logs outlive the session, so steps are told apart by a random number rather than a counter.
+            // It stays below 2^53 so the frontend reads it exactly.
+            step: rand::thread_rng().gen_range(0..1 << 53),
+            combat_logs: Vec::new(),
+            committed: false,
+        })
+    }
+
+    fn push(&self, chapter_id: &ChapterId, entry: Entry) {
+        let mut histories = self.lock();
+        let history = histories.entry(chapter_id.clone()).or_default();
+
+        // Anything undone is gone for good once something new happens
+        for entry in history.redo.drain(..) {
+            entry.discard();
+        }
+
+        if let Some(last) = history.undo.last_mut() {
+            if entry.coalesce && last.coalesce && last.label == entry.label && last.updated.elapsed() < COALESCE_WINDOW {
+                last.merge(entry);
+                return;
+            }
+        }
+
+        history.undo.push(entry);
+        if history.undo.len() > MAX_HISTORY {
+            history.undo.remove(0).discard();
+        }
+    }
+
+    /// Reverts the chapter's most recent step. Returns the step, or `None` if there is nothing to undo.
+    pub fn undo(&self, store: &CampaignStore, root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<Option<SteppedOver>> {
+        self.step(store, root, chapter_id, true)
+    }
+
+    /// Reapplies the chapter's most recently undone step. Returns the step, or `None` if there is nothing to redo.
+    pub fn redo(&self, store: &CampaignStore, root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<Option<SteppedOver>> {
+        self.step(store, root, chapter_id, false)
+    }
+
+    fn step(
+        &self,
+        store: &CampaignStore,
+        root: &CampaignRoot,
+        chapter_id: &ChapterId,
+        undo: bool,
+    ) -> io::Result<Option<SteppedOver>> {
+        let mut histories = self.lock();
+        let Some(history) = histories.get_mut(chapter_id) else {
+            return Ok(None);
+        };
+        let (from, to) = if undo {
+            (&mut history.undo, &mut history.redo)
+        } else {
+            (&mut history.redo, &mut history.undo)
+        };
+        let Some(entry) = from.pop() else {
+            return Ok(None);
+        };
+
+        // Undo in reverse so a file changed twice ends up at its first `before`
+        let changes: Vec<&Change> = if undo {
+            entry.changes.iter().rev().collect()
+        } else {
+            entry.changes.iter().collect()
+        };
+        let result = check_current(store, root, &changes, undo)
+            .and_then(|_| changes.iter().try_for_each(|change| change.apply(store, root, undo)));
+        if let Err(e) = result {
+            // Every change writes whole files, so trying the step again later is safe
+            from.push(entry);
+            return Err(e);
+        }
+
+        let stepped = SteppedOver {
+            label: entry.label.clone(),
+            combat_logs: entry.combat_logs.clone(),
+        };
+        to.push(entry);
+        Ok(Some(stepped))
+    }
+
+    /// Every chapter state and entity some step can bring back, so garbage collection keeps the files they point at
+    pub fn held(&self) -> (Vec<Chapter>, Vec<EntityId>) {
+        let (mut chapters, mut entities) = (Vec::new(), Vec::new());
+        let histories = self.lock();
+        let changes = histories
+            .values()
+            .flat_map(|history| history.undo.iter().chain(&history.redo))
+            .flat_map(|entry| &entry.changes);
+        for change in changes {
+            match change {
+                Change::Chapter { chapter_id, before, after } => {
+                    for value in [before, after].into_iter().flatten() {
+                        match from_value(value) {
+                            Ok(chapter) => chapters.push(chapter),
+                            Err(e) => log::warn!("Skipped a held state of chapter '{}': {}", chapter_id, e),
+                        }
+                    }
+                }
+                Change::Entity { id, before, after } if before.is_some() || after.is_some() => entities.push(id.clone()),
+                _ => {}
+            }
+        }
+        (chapters, entities)
+    }
+
+    pub fn info(&self, chapter_id: &ChapterId) -> HistoryInfo {
+        let histories = self.lock();
+        let labels = |entries: &[Entry]| entries.iter().rev().map(|entry| entry.label.clone()).collect();
+        match histories.get(chapter_id) {
+            Some(history) => HistoryInfo {
+                undo: labels(&history.undo),
+                redo: labels(&history.redo),
+            },
+            None => HistoryInfo { undo: Vec::new(), redo: Vec::new() },
+        }
+    }
+
+    /// Drops every history and empties the campaign's trash, e.g. on startup or before switching campaigns
+    pub fn reset(&self, root: &CampaignRoot) {
+        self.lock().clear();
+        if let Err(e) = remove_if_exists(&root.trash_dir(), |path| fs::remove_dir_all(path)) {
+            log::warn!("Failed to empty the trash in '{}': {}", root.path().display(), e);
+        }
+    }
+}
+
+/// A command being recorded. Capture every file before changing it, then `commit` to add the step to the history.
+/// A transaction that is dropped without being committed, e.g. when the command fails part way through, records
+/// nothing and puts every captured file back the way it was.
+pub struct Transaction<'a> {
+    store: &'a CampaignStore,
+    root: CampaignRoot,
+    id: u64,
+    chapter_id: ChapterId,
+    label: String,
+    coalesce: bool,
+    chapter: Option<Value>,
+    entities: Vec<(EntityId, Option<Value>)>,
+    files: Vec<(PathBuf, Option<PathBuf>)>,
+    step: u64,
+    combat_logs: Vec<String>,
+    committed: bool,
+}
+
+impl Transaction<'_> {
+    /// Merges this step into the previous one if it has the same label and follows it closely, e.g. for slider drags
+    pub fn coalescing(mut self) -> Self {
+        self.coalesce = true;
+        self
+    }
+
+    /// Captures an entity file as it is now. Capturing an entity again keeps the first capture.
+    pub fn capture_entity(&mut self, id: &EntityId) -> io::Result<()> {
+        if self.entities.iter().any(|(captured, _)| captured == id) {
+            return Ok(());
+        }
+        let entity = read_entity_value(self.store, &self.root, id)?;
+        self.entities.push((id.clone(), entity));
+        Ok(())
+    }
+
+    /// Captures an entity file along with its icon, for commands that may delete the entity
+    pub fn capture_entity_with_icon(&mut self, id: &EntityId) -> io::Result<()> {
+        self.capture_entity(id)?;
+        let icon = id.icon_path(&self.root);
+        self.capture_file(&icon)
+    }
+
+    /// Captures every entity file of a combat
+    pub fn capture_combat_entities(&mut self, combat: &Combat) -> io::Result<()> {
+        for filename in &combat.entities {
+            self.capture_entity(&EntityId::from_filename(filename)?)?;
+        }
+        Ok(())
+    }
+
+    /// Notes that the command records events in the combat's log. Returns the step to tag them with, so
+    /// undoing the step takes them back out.
+    pub fn log_events(&mut self, battlemap: &str) -> u64 {
+        if !self.combat_logs.iter().any(|logged| logged == battlemap) {
+            self.combat_logs.push(battlemap.to_string());
+        }
+        self.step
+    }
+
+    /// Captures every file under a directory, for commands that delete the whole directory
+    pub fn capture_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                self.capture_dir(&entry.path())?;
+            } else {
+                self.capture_file(&entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies a file into the trash as it is now, so it can be brought back after it is changed or deleted
+    pub fn capture_file(&mut self, path: &Path) -> io::Result<()> {
+        if self.files.iter().any(|(captured, _)| captured == path) {
+            return Ok(());
+        }
+        let copy = self.copy_to_trash(path, self.files.len(), "before")?;
+        self.files.push((path.to_path_buf(), copy));
+        Ok(())
+    }
+
+    /// Copies the file to `trash/<transaction>_<file>_<version>_<name>`, if it exists
+    fn copy_to_trash(&self, path: &Path, index: usize, version: &str) -> io::Result<Option<PathBuf>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let trash = self.root.trash_dir();
+        fs::create_dir_all(&trash)?;
+
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("file");
+        let copy = trash.join(format!("{}_{}_{}_{}", self.id, index, version, name));
+        fs::copy(path, &copy)?;
+        Ok(Some(copy))
+    }
+
+    /// Captures the state after the command and adds the step to the chapter's history.
+    /// Files the command left unchanged are left out; a command that changed nothing adds no step.
+    pub fn commit(mut self, journal: &Journal) -> io::Result<()> {
+        let changes = self.changes()?;
+        self.committed = true;
+        if !changes.is_empty() {
+            journal.push(
+                &self.chapter_id,
+                Entry {
+                    label: std::mem::take(&mut self.label),
+                    coalesce: self.coalesce,
+                    updated: Instant::now(),
+                    changes,
+                    combat_logs: self.combat_logs.iter().map(|battlemap| (self.step, battlemap.clone())).collect(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Puts every captured file back the way it was when it was captured
+    fn roll_back(&self) -> io::Result<()> {
+        let changes = self.changes()?;
+        let result = changes
+            .iter()
+            .rev()
+            .try_for_each(|change| change.apply(self.store, &self.root, true));
+        changes.iter().for_each(Change::discard);
+        result
+    }
+
+    /// What changed in the captured files since they were captured
+    fn changes(&self) -> io::Result<Vec<Change>> {
+        let mut changes = Vec::new();
+
+        let chapter = read_chapter_value(self.store, &self.root, &self.chapter_id)?;
+        if chapter != self.chapter {
+            changes.push(Change::Chapter {
+                chapter_id: self.chapter_id.clone(),
+                before: self.chapter.clone(),
+                after: chapter,
+            });
+        }
+
+        for (id, before) in &self.entities {
+            let after = read_entity_value(self.store, &self.root, id)?;
+            if after != *before {
+                changes.push(Change::Entity {
+                    id: id.clone(),
+                    before: before.clone(),
+                    after,
+                });
+            }
+        }
+
+        for (index, (path, before)) in self.files.iter().enumerate() {
+            let after = self.copy_to_trash(path, index, "after")?;
+            let change = Change::File {
+                path: path.clone(),
+                before: before.clone(),
+                after,
+            };
+            if same_contents(before.as_deref(), path) {
+                change.discard();
+            } else {
+                changes.push(change);
+            }
+        }
+        Ok(changes)
+    }
+}
+
+/// Entities, the bestiary and other campaign-wide files are shared by every chapter's history. A step is only
+/// stepped over while its files are as it expects, so it never overwrites a later change made from another chapter.
+fn check_current(store: &CampaignStore, root: &CampaignRoot, changes: &[&Change], undo: bool) -> io::Result<()> {
+    let mut checked = HashSet::new();
+    for change in changes {
+        // A file changed twice in a coalesced step is checked against the change applied to it first
+        let path = change.path(root);
+        if checked.insert(path.clone()) && !change.is_current(store, root, undo)? {
+            let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "'{}' was changed since this step, so it can't be {} without losing that change",
+                    name,
+                    if undo { "undone" } else { "redone" }
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            if let Err(e) = self.roll_back() {
+                log::warn!("Failed to roll back '{}' in chapter '{}': {}", self.label, self.chapter_id, e);
+            }
+        }
+    }
+}
+
+fn read_chapter_value(store: &CampaignStore, root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<Option<Value>> {
+    match store.read_chapter(root, chapter_id) {
+        Ok(chapter) => Ok(Some(serde_json::to_value(chapter)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn read_entity_value(store: &CampaignStore, root: &CampaignRoot, id: &EntityId) -> io::Result<Option<Value>> {
+    match store.read_entity(root, id) {
+        Ok(entity) => Ok(Some(serde_json::to_value(entity)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether the trash copy `before` and the file at `path` hold the same bytes, counting two missing files as equal
+fn same_contents(before: Option<&Path>, path: &Path) -> bool {
+    match (before, path.exists()) {
+        (None, exists) => !exists,
+        (Some(_), false) => false,
+        (Some(before), true) => match (fs::read(before), fs::read(path)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        },
+    }
+}
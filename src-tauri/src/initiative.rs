@@ -0,0 +1,493 @@
+// initiative.rs
+use std::collections::HashSet;
+use std::io;
+
+use rand::Rng;
+use serde::Serialize;
+
+use crate::campaign::CampaignRoot;
+use crate::models::{Initiative, InitiativeEntry};
+use crate::refs::EntityId;
+use crate::store::CampaignStore;
+
+/// Event emitted to every window whenever the active turn or the round changes
+pub const TURN_CHANGED_EVENT: &str = "turnChanged";
+
+/// The turn that ended and the turn that started when the tracker moved
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnChange {
+    pub previous: Option<String>,
+    pub active: Option<String>,
+    pub round: u32,
+    /// Rounds that started during this change; one per wrap past the end of the order
+    pub rounds_started: u32,
+    /// Entity whose turn ended, if the change passed the turn on rather than moving it back or aside
+    #[serde(skip)]
+    pub ended: Option<String>,
+    /// Entity whose turn started, under the same rule as `ended`
+    #[serde(skip)]
+    pub started: Option<String>,
+}
+
+/// Payload of the `turnChanged` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnChanged<'a> {
+    pub chapter_id: &'a str,
+    pub battlemap: &'a str,
+    #[serde(flatten)]
+    pub change: &'a TurnChange,
+}
+
+/// Rolls a d20 plus the entity's bonus
+pub fn roll(bonus: i32) -> i32 {
+    rand::thread_rng().gen_range(1..=20) + bonus
+}
+
+/// Entity files (`<id>.json`) among `entities` that cannot take a turn: dead ones, and ones whose file is gone
+pub fn inactive_entities(store: &CampaignStore, root: &CampaignRoot, entities: &[String]) -> io::Result<HashSet<String>> {
+    let mut inactive = HashSet::new();
+    for filename in entities {
+        let entity_id = EntityId::from_filename(filename)?;
+        match store.read_entity(root, &entity_id) {
+            Ok(entity) if !entity.dead => {}
+            Ok(_) => {
+                inactive.insert(filename.clone());
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                inactive.insert(filename.clone());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(inactive)
+}
+
+impl Initiative {
+    fn position(&self, entity: &str) -> Option<usize> {
+        self.order.iter().position(|entry| entry.entity == entity)
+    }
+
+    fn sort(&mut self) {
+        // Highest initiative first, then the order resuming set up, then highest bonus, then a stable order for exact ties
+        self.order.sort_by(|a, b| {
+            b.initiative
+                .cmp(&a.initiative)
+                .then(b.tiebreak.cmp(&a.tiebreak))
+                .then(b.bonus.cmp(&a.bonus))
+                .then(a.entity.cmp(&b.entity))
+        });
+    }
+
+    fn can_act(entry: &InitiativeEntry, skip: &HashSet<String>) -> bool {
+        !entry.delayed && !skip.contains(&entry.entity)
+    }
+
+    fn first_eligible(&self, skip: &HashSet<String>) -> Option<String> {
+        self.order
+            .iter()
+            .find(|entry| Initiative::can_act(entry, skip))
+            .map(|entry| entry.entity.clone())
+    }
+
+    /// Adds or replaces an entity's initiative, keeping the order sorted
+    pub fn set(&mut self, entity: &str, initiative: i32, bonus: i32) {
+        match self.position(entity) {
+            Some(index) => {
+                let entry = &mut self.order[index];
+                if entry.initiative != initiative {
+                    entry.tiebreak = 0;
+                }
+                entry.initiative = initiative;
+                entry.bonus = bonus;
+            }
+            None => self.order.push(InitiativeEntry {
+                entity: entity.to_string(),
+                initiative,
+                bonus,
+                delayed: false,
+                tiebreak: 0,
+            }),
+        }
+        self.sort();
+    }
+
+    /// Drops an entity from the order. If it was its turn, the turn passes to the next entity
+    /// and the change is returned.
+    pub fn remove(&mut self, entity: &str, skip: &HashSet<String>) -> Option<TurnChange> {
+        let change = if self.active.as_deref() == Some(entity) {
+            self.advance(skip).ok()
+        } else {
+            None
+        };
+        self.order.retain(|entry| entry.entity != entity);
+        if self.active.as_deref() == Some(entity) {
+            self.active = None;
+            return Some(self.change_from(Some(entity.to_string())));
+        }
+        change
+    }
+
+    /// Starts round 1 with the first entity able to act
+    pub fn start(&mut self, skip: &HashSet<String>) -> TurnChange {
+        let previous = self.active.take();
+        for entry in &mut self.order {
+            entry.delayed = false;
+        }
+        self.round = 1;
+        self.active = self.first_eligible(skip);
+        self.passed_on(previous, false, 1)
+    }
+
+    /// Clears the turn order and the round counter
+    pub fn reset(&mut self) -> TurnChange {
+        let previous = self.active.take();
+        self.order.clear();
+        self.round = 0;
+        self.change_from(previous)
+    }
+
+    /// Passes the turn to the next entity that can act, wrapping into the next round
+    pub fn advance(&mut self, skip: &HashSet<String>) -> Result<TurnChange, String> {
+        if self.round == 0 {
+            return Ok(self.start(skip));
+        }
+        if self.first_eligible(skip).is_none() {
+            return Err("No entity in the turn order can act.".to_string());
+        }
+
+        let previous = self.active.clone();
+        let len = self.order.len();
+        let mut index = previous.as_deref().and_then(|active| self.position(active));
+        let mut rounds_started = 0;
+
+        loop {
+            let next = match index {
+                Some(i) if i + 1 < len => i + 1,
+                Some(_) => {
+                    self.round += 1;
+                    rounds_started += 1;
+                    0
+                }
+                None => 0,
+            };
+            index = Some(next);
+            if Initiative::can_act(&self.order[next], skip) {
+                break;
+            }
+        }
+
+        self.active = index.map(|i| self.order[i].entity.clone());
+        Ok(self.passed_on(previous, true, rounds_started))
+    }
+
+    /// Hands the turn back to the previous entity that can act, stopping at the first turn of round 1
+    pub fn rewind(&mut self, skip: &HashSet<String>) -> Result<TurnChange, String> {
+        let Some(active) = self.active.clone() else {
+            return Err("Initiative has not been rolled yet.".to_string());
+        };
+        let Some(start) = self.position(&active) else {
+            return Err("Initiative has not been rolled yet.".to_string());
+        };
+
+        let mut round = self.round;
+        let mut index = start;
+        loop {
+            if index == 0 {
+                if round <= 1 {
+                    return Err("Already at the first turn of the combat.".to_string());
+                }
+                round -= 1;
+                index = self.order.len();
+            }
+            index -= 1;
+            if Initiative::can_act(&self.order[index], skip) {
+                break;
+            }
+        }
+
+        self.round = round;
+        self.active = Some(self.order[index].entity.clone());
+        Ok(self.change_from(Some(active)))
+    }
+
+    /// Skips straight to the first turn of the next round
+    pub fn advance_round(&mut self, skip: &HashSet<String>) -> Result<TurnChange, String> {
+        if self.round == 0 {
+            return Ok(self.start(skip));
+        }
+        let active = self
+            .first_eligible(skip)
+            .ok_or_else(|| "No entity in the turn order can act.".to_string())?;
+
+        let previous = self.active.replace(active);
+        self.round += 1;
+        Ok(self.passed_on(previous, true, 1))
+    }
+
+    /// Goes back to the first turn of the previous round
+    pub fn rewind_round(&mut self, skip: &HashSet<String>) -> Result<TurnChange, String> {
+        if self.round <= 1 {
+            return Err("Already in the first round of the combat.".to_string());
+        }
+        let active = self
+            .first_eligible(skip)
+            .ok_or_else(|| "No entity in the turn order can act.".to_string())?;
+
+        let previous = self.active.replace(active);
+        self.round -= 1;
+        Ok(self.change_from(previous))
+    }
+
+    /// The active entity holds its action; its turns are skipped until `resume` brings it back.
+    /// Its turn has not ended, so it does not count as `ended` in the change.
+    pub fn delay(&mut self, skip: &HashSet<String>) -> Result<TurnChange, String> {
+        let active = self
+            .active
+            .clone()
+            .ok_or_else(|| "There is no active turn to delay.".to_string())?;
+
+        let mut change = self.advance(skip)?;
+        if let Some(index) = self.position(&active) {
+            self.order[index].delayed = true;
+        }
+        change.ended = None;
+        Ok(change)
+    }
+
+    /// A delayed entity acts now: it takes the initiative of the active entity and goes right before it.
+    /// Its tiebreak keeps it there when the order is sorted again; entities tied with the active one that
+    /// were already before it move up to stay ahead.
+    /// The delayed turn already started before it was held, so the change neither ends nor starts one.
+    pub fn resume(&mut self, entity: &str) -> Result<TurnChange, String> {
+        let index = self
+            .position(entity)
+            .filter(|&index| self.order[index].delayed)
+            .ok_or_else(|| format!("'{}' has not delayed its turn.", entity))?;
+
+        let mut entry = self.order.remove(index);
+        entry.delayed = false;
+
+        let insert_at = match self.active.as_deref().and_then(|active| self.position(active)) {
+            Some(active_index) => {
+                let active = &self.order[active_index];
+                let (initiative, tiebreak) = (active.initiative, active.tiebreak);
+                for ahead in self.order[..active_index].iter_mut().filter(|ahead| ahead.initiative == initiative) {
+                    ahead.tiebreak += 2;
+                }
+                entry.initiative = initiative;
+                entry.tiebreak = tiebreak + 1;
+                active_index
+            }
+            None => self.order.len(),
+        };
+        self.order.insert(insert_at, entry);
+
+        let previous = self.active.replace(entity.to_string());
+        Ok(self.change_from(previous))
+    }
+
+    /// A change from `previous` to the current turn that does not pass the turn on, e.g. a rewind
+    pub fn change_from(&self, previous: Option<String>) -> TurnChange {
+        TurnChange {
+            previous,
+            active: self.active.clone(),
+            round: self.round,
+            rounds_started: 0,
+            ended: None,
+            started: None,
+        }
+    }
+
+    /// A change that passed the turn from `previous` to the current turn
+    fn passed_on(&self, previous: Option<String>, end_previous: bool, rounds_started: u32) -> TurnChange {
+        TurnChange {
+            ended: previous.clone().filter(|_| end_previous),
+            started: self.active.clone(),
+            rounds_started,
+            ..self.change_from(previous)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A started combat with `a` (20), `b` (15) and `c` (10) in round 1, `a` to act
+    fn started() -> Initiative {
+        let mut initiative = Initiative::default();
+        initiative.set("c", 10, 0);
+        initiative.set("a", 20, 0);
+        initiative.set("b", 15, 0);
+        initiative.start(&HashSet::new());
+        initiative
+    }
+
+    fn skipping(entities: &[&str]) -> HashSet<String> {
+        entities.iter().map(|entity| entity.to_string()).collect()
+    }
+
+    fn order(initiative: &Initiative) -> Vec<&str> {
+        initiative.order.iter().map(|entry| entry.entity.as_str()).collect()
+    }
+
+    #[test]
+    fn sorts_by_initiative_then_bonus() {
+        let mut initiative = started();
+        initiative.set("d", 15, 2);
+        initiative.set("e", 15, 2);
+        assert_eq!(order(&initiative), vec!["a", "d", "e", "b", "c"]);
+    }
+
+    #[test]
+    fn advance_wraps_into_the_next_round() {
+        let mut initiative = started();
+        assert_eq!(initiative.active.as_deref(), Some("a"));
+        assert_eq!(initiative.round, 1);
+
+        let change = initiative.advance(&HashSet::new()).unwrap();
+        assert_eq!(change.ended.as_deref(), Some("a"));
+        assert_eq!(change.started.as_deref(), Some("b"));
+        assert_eq!(change.rounds_started, 0);
+
+        initiative.advance(&HashSet::new()).unwrap();
+        let change = initiative.advance(&HashSet::new()).unwrap();
+        assert_eq!(change.previous.as_deref(), Some("c"));
+        assert_eq!(change.active.as_deref(), Some("a"));
+        assert_eq!(change.round, 2);
+        assert_eq!(change.rounds_started, 1);
+    }
+
+    #[test]
+    fn advance_skips_entities_that_cannot_act() {
+        let mut initiative = started();
+        let change = initiative.advance(&skipping(&["b"])).unwrap();
+        assert_eq!(change.active.as_deref(), Some("c"));
+
+        let change = initiative.advance(&skipping(&["a"])).unwrap();
+        assert_eq!(change.active.as_deref(), Some("b"));
+        assert_eq!(change.round, 2);
+
+        assert!(initiative.advance(&skipping(&["a", "b", "c"])).is_err());
+    }
+
+    #[test]
+    fn advance_before_the_first_round_starts_combat() {
+        let mut initiative = Initiative::default();
+        initiative.set("a", 12, 0);
+        let change = initiative.advance(&HashSet::new()).unwrap();
+        assert_eq!(change.round, 1);
+        assert_eq!(change.rounds_started, 1);
+        assert_eq!(change.started.as_deref(), Some("a"));
+        assert_eq!(change.ended, None);
+    }
+
+    #[test]
+    fn rewind_goes_back_across_rounds_but_not_past_the_first_turn() {
+        let mut initiative = started();
+        assert!(initiative.rewind(&HashSet::new()).is_err());
+
+        initiative.advance(&HashSet::new()).unwrap();
+        initiative.advance(&HashSet::new()).unwrap();
+        initiative.advance(&HashSet::new()).unwrap();
+        assert_eq!((initiative.active.as_deref(), initiative.round), (Some("a"), 2));
+
+        let change = initiative.rewind(&skipping(&["c"])).unwrap();
+        assert_eq!(change.active.as_deref(), Some("b"));
+        assert_eq!(change.round, 1);
+        assert_eq!(change.ended, None);
+        assert_eq!(change.started, None);
+
+        initiative.rewind(&HashSet::new()).unwrap();
+        assert!(initiative.rewind(&HashSet::new()).is_err());
+        assert_eq!((initiative.active.as_deref(), initiative.round), (Some("a"), 1));
+    }
+
+    #[test]
+    fn rewind_needs_an_active_turn() {
+        let mut initiative = Initiative::default();
+        initiative.set("a", 12, 0);
+        assert!(initiative.rewind(&HashSet::new()).is_err());
+    }
+
+    #[test]
+    fn delay_passes_the_turn_without_ending_it() {
+        let mut initiative = started();
+        let change = initiative.delay(&HashSet::new()).unwrap();
+        assert_eq!(change.active.as_deref(), Some("b"));
+        assert_eq!(change.ended, None);
+        assert_eq!(change.started.as_deref(), Some("b"));
+        assert!(initiative.order[0].delayed);
+
+        // A delayed entity's turns are skipped
+        initiative.advance(&HashSet::new()).unwrap();
+        let change = initiative.advance(&HashSet::new()).unwrap();
+        assert_eq!(change.active.as_deref(), Some("b"));
+        assert_eq!(change.round, 2);
+    }
+
+    #[test]
+    fn resume_acts_right_before_the_active_entity() {
+        let mut initiative = started();
+        initiative.delay(&HashSet::new()).unwrap();
+        initiative.advance(&HashSet::new()).unwrap();
+        assert_eq!(initiative.active.as_deref(), Some("c"));
+
+        let change = initiative.resume("a").unwrap();
+        assert_eq!(change.previous.as_deref(), Some("c"));
+        assert_eq!(change.active.as_deref(), Some("a"));
+        assert_eq!(change.ended, None);
+        assert_eq!(change.started, None);
+        assert_eq!(order(&initiative), vec!["b", "a", "c"]);
+        assert!(!initiative.order[1].delayed);
+        assert_eq!(initiative.order[1].initiative, 10);
+
+        let change = initiative.advance(&HashSet::new()).unwrap();
+        assert_eq!(change.active.as_deref(), Some("c"));
+        assert_eq!(change.round, 1);
+
+        assert!(initiative.resume("a").is_err());
+        assert!(initiative.resume("missing").is_err());
+    }
+
+    #[test]
+    fn resumed_entity_keeps_its_place_when_the_order_is_sorted_again() {
+        let mut initiative = started();
+        initiative.set("d", 10, 5);
+        assert_eq!(order(&initiative), vec!["a", "b", "d", "c"]);
+
+        // `a` interrupts `c`, which is tied with `d` but loses on bonus
+        initiative.delay(&HashSet::new()).unwrap();
+        initiative.advance(&HashSet::new()).unwrap();
+        initiative.advance(&HashSet::new()).unwrap();
+        assert_eq!(initiative.active.as_deref(), Some("c"));
+        initiative.resume("a").unwrap();
+        assert_eq!(order(&initiative), vec!["b", "d", "a", "c"]);
+
+        // Setting an unrelated entry sorts the order again
+        initiative.set("b", 16, 0);
+        initiative.set("e", 10, -1);
+        assert_eq!(order(&initiative), vec!["b", "d", "a", "c", "e"]);
+
+        let change = initiative.advance(&HashSet::new()).unwrap();
+        assert_eq!(change.active.as_deref(), Some("c"));
+
+        // A new initiative for the resumed entity drops its tiebreak
+        initiative.set("a", 10, 0);
+        assert_eq!(order(&initiative), vec!["b", "d", "a", "c", "e"]);
+        initiative.set("a", 12, 0);
+        initiative.set("a", 10, -2);
+        assert_eq!(order(&initiative), vec!["b", "d", "c", "e", "a"]);
+    }
+
+    #[test]
+    fn start_clears_delays() {
+        let mut initiative = started();
+        initiative.delay(&HashSet::new()).unwrap();
+        initiative.start(&HashSet::new());
+        assert!(initiative.order.iter().all(|entry| !entry.delayed));
+        assert_eq!((initiative.active.as_deref(), initiative.round), (Some("a"), 1));
+    }
+}
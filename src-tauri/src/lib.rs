@@ -1,20 +1,68 @@
 mod utils;
 mod models;
-
-use std::fs;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
-use std::path::{Path};
-
-use serde_json::{Value, to_string_pretty};
-
-use crate::models::{TransformStateObject};
+mod migrations;
+mod storage;
+mod campaign;
+mod store;
+mod error;
+mod gc;
+mod assets;
+mod refs;
+mod initiative;
+mod conditions;
+mod dice;
+mod damage;
+mod combat_log;
+mod snapshots;
+mod history;
+mod duplicate;
+mod hex;
+mod movement;
+mod terrain;
+mod fog;
+mod vision;
+mod bestiary;
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::bestiary::{Bestiary, Template, TemplateStats};
+use crate::campaign::{AssetKind, CampaignRoot, CampaignState};
+use crate::combat_log::{CombatEvent, CombatEventKind, CombatSummary};
+use crate::conditions::{CONDITION_EXPIRED_EVENT, CONSTRUCTOR_WINDOW};
+use crate::damage::HitpointChange;
+use crate::dice::{DiceExpression, RollLog, RollRecord, RollSession, PUBLIC_ROLL_EVENT};
+use crate::error::{TableauError, TableauResult};
+use crate::fog::{Fog, FogArea, FogMode};
+use crate::gc::GcReport;
+use crate::history::{HistoryInfo, SteppedOver, Transaction};
+use crate::hex::{Hex, Layout};
+use crate::initiative::{TurnChange, TurnChanged, TURN_CHANGED_EVENT};
+use crate::movement::{MovementMap, MovePath, Obstacles, ReachableHex};
+use crate::refs::{AssetRef, ChapterId, EntityId};
+use crate::snapshots::{CombatSnapshots, SnapshotInfo};
+use crate::models::{Chapter, Combat, CombatPatch, DamageType, EntityPatch, Initiative, TransformStateObject};
+use crate::storage::CorruptFile;
+use crate::terrain::{Terrain, TerrainKind, Wall};
+
+/// Corrupt files found by the startup scan that have not been restored yet
+struct CorruptFileReport(Mutex<Vec<CorruptFile>>);
+
+impl CorruptFileReport {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<CorruptFile>> {
+        match self.0.lock() {
+            Ok(corrupt_files) => corrupt_files,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
 
-    initialize_directory_structure().expect("Failed to initialize directory structure");
-
   tauri::Builder::default()
     .setup(|app| {
       if cfg!(debug_assertions) {
@@ -24,6 +72,20 @@ pub fn run() {
             .build(),
         )?;
       }
+
+      // Open the last used campaign, defaulting to one in the platform app-data directory.
+      // A campaign left in the old working-directory location is picked up instead if present.
+      let settings_path = app.path().app_config_dir()?.join("settings.json");
+      let default_root = match std::fs::canonicalize("../tableau") {
+          Ok(legacy_root) if CampaignRoot::new(&legacy_root).is_campaign() => legacy_root,
+          _ => app.path().app_data_dir()?.join("campaigns").join("default"),
+      };
+      let campaign = CampaignState::load(settings_path, default_root)?;
+
+      let corrupt_files = scan_campaign_for_corrupt_files(&campaign.root());
+      app.manage(CorruptFileReport(Mutex::new(corrupt_files)));
+      app.manage(campaign);
+      app.manage(RollSession::start());
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
@@ -38,137 +100,190 @@ pub fn run() {
       change_splash_allegiance,
       create_combat,
       remove_combat,
-      update_battlemap_size,
-      update_battlemap_xoffset,
-      update_battlemap_yoffset,
-      update_grid_size,
-      update_grid_xoffset,
-      update_grid_yoffset,
+      patch_combat,
+      paint_terrain,
+      erase_terrain,
+      add_walls,
+      remove_walls,
+      set_fog_enabled,
+      set_fog_vision,
+      reveal_fog,
+      hide_fog,
+      render_fog_mask,
       upload_icon_image,
+      finish_icon_crop,
       add_entity,
       get_entities,
       update_entity,
       remove_entity,
+      duplicate_entity,
+      get_templates,
+      create_template,
+      create_template_from_entity,
+      update_template,
+      remove_template,
+      add_entity_from_template,
       get_entity,
-      generate_hexgrid
+      generate_hexgrid,
+      get_corrupt_files,
+      restore_last_good_copy,
+      get_current_campaign,
+      get_recent_campaigns,
+      create_campaign,
+      open_campaign,
+      switch_campaign,
+      collect_garbage,
+      get_asset_names,
+      roll_initiative,
+      set_initiative,
+      advance_turn,
+      rewind_turn,
+      advance_round,
+      rewind_round,
+      delay_turn,
+      resume_turn,
+      clear_initiative,
+      roll_dice,
+      get_roll_log,
+      apply_damage,
+      apply_healing,
+      move_entity,
+      hex_at,
+      hex_line,
+      get_reachable_hexes,
+      find_path,
+      add_condition,
+      remove_condition,
+      get_combat_log,
+      get_combat_summary,
+      export_combat_summary,
+      create_snapshot,
+      list_snapshots,
+      restore_snapshot,
+      remove_snapshot,
+      undo,
+      redo,
+      get_history
       ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
-}
-
-fn initialize_directory_structure() -> Result<(), std::io::Error> {
-    // Create the base tableau directory
-    let base_dir = Path::new("../tableau");
-    create_directory_if_not_exists(base_dir)?;
-
-    // Create subdirectories
-    let directories = [
-        "chapters",
-        "assets",
-        "assets/landscapes",
-        "assets/splashes",
-        "assets/battlemaps",
-        "assets/entities",
-        "assets/iconimages",
-        "assets/hexgrids",
-        "entities"
-    ];
-
-    for dir in directories.iter() {
-        create_directory_if_not_exists(&base_dir.join(dir))?;
-    }
-
-    // Create entity IDs file if it doesn't exist
-    let entity_ids_file = base_dir.join("entities").join("entity_ids.txt");
-    if !entity_ids_file.exists() {
-        File::create(entity_ids_file)?;
-    }
-
-    Ok(())
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app, event| {
+      // Write out combat patches still waiting for their debounce delay
+      if let tauri::RunEvent::Exit = event {
+        app.state::<CampaignState>().store().flush();
+      }
+    });
 }
 
-fn create_directory_if_not_exists(path: &Path) -> Result<(), std::io::Error> {
-    if !path.exists() {
-        fs::create_dir_all(path)?;
+/// Reports campaign files left corrupt by a crash so the frontend can offer the last good copy
+fn scan_campaign_for_corrupt_files(root: &CampaignRoot) -> Vec<CorruptFile> {
+    let corrupt_files = storage::scan_for_corrupt_files(&[root.chapters_dir(), root.entities_dir()]);
+    for corrupt_file in &corrupt_files {
+        eprintln!("Warning: Corrupt campaign file '{}': {}", corrupt_file.path, corrupt_file.error);
     }
-    Ok(())
+    corrupt_files
 }
 
 #[tauri::command]
-fn create_chapter(chapter_id: String) -> Result<String, String> {
-    // Format the chapter ID into the correct file path
-    let path = utils::format_chapter_id(&chapter_id);
-
-    // Check if the file already exists to avoid overwriting
-    if path.exists() {
-        return Err(format!("Chapter '{}' already exists.", chapter_id));
-    }
-
-    // Define the default chapter structure
-    let chapter_data = serde_json::json!({
-        "combat": [],
-        "landscapes": [],
-        "splashes": []
-    });
-
-    // Convert the chapter data into a pretty JSON string
-    let chapter_json = match serde_json::to_string_pretty(&chapter_data) {
-        Ok(json) => json,
-        Err(err) => return Err(format!("Failed to serialize chapter data: {}", err)),
-    };
-
-    // Ensure the directory exists
-    if let Some(parent) = path.parent() {
-        if let Err(err) = std::fs::create_dir_all(parent) {
-            return Err(format!("Failed to create directory: {}", err));
-        }
-    }
-
-    // Write the JSON to the file
-    let mut file = match std::fs::File::create(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to create chapter file: {}", err)),
-    };
-
-    if let Err(err) = file.write_all(chapter_json.as_bytes()) {
-        return Err(format!("Failed to write chapter file: {}", err));
-    }
+fn create_chapter(chapter_id: String, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let transaction = begin_change(&campaign, &chapter_id, "Create chapter")?;
+
+    // Write the default (empty) chapter structure, refusing to overwrite an existing chapter
+    campaign
+        .store()
+        .create_chapter(&root, &chapter_id, Chapter::default())
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => TableauError::from(e),
+            _ => TableauError::io("Failed to write chapter file", e),
+        }.with_chapter(&chapter_id))?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
 
     // Return success message
     Ok(format!("Chapter '{}' created successfully.", chapter_id))
 }
 
+/// Deletes a chapter. With `delete_entities`, the entities of its combats are deleted too.
 #[tauri::command]
-fn remove_chapter(chapter_id: String) -> Result<String, String> {
-    // Convert the 'Chapter Id' format to 'chapter_id.json'
-    let formatted_id = chapter_id.to_lowercase().replace(" ", "_");
-    let file_path = format!("../tableau/chapters/{}.json", formatted_id);
+fn remove_chapter(chapter_id: String, delete_entities: Option<bool>, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let root = campaign.root();
 
-    // Create a PathBuf from the file path
-    let path = std::path::Path::new(&file_path);
+    // Convert the 'Chapter Id' format to the ID the other commands use, without the 'chapter_' prefix
+    let id = ChapterId::from_display_name(&chapter_id)?;
+    let path = id.file_path(&root);
 
     // Check if the file exists
     if !path.exists() {
-        return Err(format!("Chapter file for '{}' not found.", chapter_id));
+        return Err(TableauError::not_found(format!("Chapter file for '{}' not found.", chapter_id))
+            .with_chapter(&chapter_id)
+            .with_path(&path));
     }
 
+    // Note the chapter's entities before the chapter is gone
+    let entities: Vec<String> = match delete_entities {
+        Some(true) => campaign
+            .store()
+            .read_chapter(&root, &id)
+            .map_err(|e| TableauError::io("Failed to load chapter", e).with_chapter(&chapter_id))?
+            .combat
+            .into_iter()
+            .flat_map(|combat| combat.entities)
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    // Keep copies of everything the removal deletes, so undo can bring the chapter back whole
+    let mut transaction = begin_change(&campaign, &id, "Remove chapter")?;
+    let record = |transaction: &mut Transaction| -> std::io::Result<()> {
+        for filename in &entities {
+            transaction.capture_entity_with_icon(&EntityId::from_filename(filename)?)?;
+        }
+        transaction.capture_file(&dice::roll_log_path(&root, &id))?;
+        transaction.capture_dir(&combat_log::chapter_logs_dir(&root, &id))?;
+        transaction.capture_dir(&snapshots::chapter_snapshots_dir(&root, &id))?;
+        transaction.capture_dir(&fog::chapter_masks_dir(&root, &id))
+    };
+    record(&mut transaction).map_err(history_error(&id))?;
+
     // Attempt to remove the file
-    match std::fs::remove_file(path) {
-        Ok(_) => Ok(format!("Chapter '{}' deleted successfully.", chapter_id)),
-        Err(err) => Err(format!("Failed to delete chapter '{}': {}", chapter_id, err)),
-    }
+    campaign.store().remove_chapter_file(&path).map_err(|e| {
+        TableauError::io(&format!("Failed to delete chapter '{}'", chapter_id), e)
+            .with_chapter(&chapter_id)
+            .with_path(&path)
+    })?;
+
+    gc::remove_unreferenced_entities(campaign.store(), &root, &entities).map_err(|e| {
+        TableauError::io("Chapter deleted, but failed to delete its entities", e).with_chapter(&chapter_id)
+    })?;
+
+    RollLog::remove(&root, &id).map_err(|e| {
+        TableauError::io("Chapter deleted, but failed to delete its roll log", e).with_chapter(&chapter_id)
+    })?;
+    combat_log::remove_chapter(&root, &id).map_err(|e| {
+        TableauError::io("Chapter deleted, but failed to delete its combat logs", e).with_chapter(&chapter_id)
+    })?;
+    CombatSnapshots::remove_chapter(&root, &id).map_err(|e| {
+        TableauError::io("Chapter deleted, but failed to delete its snapshots", e).with_chapter(&chapter_id)
+    })?;
+    fog::remove_chapter(&root, &id).map_err(|e| {
+        TableauError::io("Chapter deleted, but failed to delete its fog masks", e).with_chapter(&chapter_id)
+    })?;
+    transaction.commit(campaign.journal()).map_err(history_error(&id))?;
+
+    Ok(format!("Chapter '{}' deleted successfully.", chapter_id))
 }
 
 
 
 // Tauri command to get the list of chapters
 #[tauri::command]
-fn get_chapters() -> Result<Vec<String>, String> {
+fn get_chapters(campaign: State<'_, CampaignState>) -> TableauResult<Vec<String>> {
   // Specify the directory path
-  let directory_path = "../tableau/chapters";
+  let directory_path = campaign.root().chapters_dir();
 
   // Fetch the list of files in the directory
-  match utils::list_files_in_directory(directory_path) {
+  match utils::list_files_in_directory(&directory_path) {
       Ok(files) => {
           // Extract and format the chapter names
           let chapters: Vec<String> = files
@@ -180,610 +295,551 @@ fn get_chapters() -> Result<Vec<String>, String> {
           Ok(chapters)
       }
       Err(err) => {
-          // Return the error message if there's an issue reading the directory
-          Err(format!("Failed to read directory: {}", err))
+          // Return the error if there's an issue reading the directory
+          Err(TableauError::io("Failed to read directory", err).with_path(&directory_path))
       }
   }
 }
 
 #[tauri::command]
-fn get_chapter_data(chapter_id: String) -> Result<Value, String> {
-    // Get the file path using format_chapter_id
-    let path = utils::format_chapter_id(&chapter_id);
-
-    // Check if the file exists
-    if !path.exists() {
-        return Err(format!("Chapter file for ID '{}' not found.", chapter_id));
-    }
-
-    // Open the file and read its contents
-    let mut file = match File::open(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to open file: {}", err)),
-    };
-
-    let mut content = String::new();
-    if let Err(err) = file.read_to_string(&mut content) {
-        return Err(format!("Failed to read file: {}", err));
-    }
-
-    // Parse the JSON data
-    match serde_json::from_str(&content) {
-        Ok(json_value) => Ok(json_value),
-        Err(err) => Err(format!("Failed to parse JSON: {}", err)),
-    }
+fn get_chapter_data(chapter_id: String, campaign: State<'_, CampaignState>) -> TableauResult<Chapter> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    campaign
+        .store()
+        .read_chapter(&root, &chapter_id)
+        .map_err(|e| TableauError::io("Failed to load chapter", e).with_chapter(&chapter_id))
 }
 
 // Tauri command to upload landscapes
 #[tauri::command]
-fn upload_landscapes(chapter_id: String) -> Result<String, String> {
+fn upload_landscapes(chapter_id: String, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let transaction = begin_change(&campaign, &chapter_id, "Upload landscapes")?;
 
     match utils::select_image_files() {
         Some(paths) => {
             let mut landscapes: Vec<String> = Vec::new();
-            let landscapes_directory = "../tableau/assets/landscapes";
 
             // Loop through the selected files
             for path in paths {
-                // Save the image to the specified directory
-                if let Err(e) = utils::save_image_to_directory(&path, Path::new(landscapes_directory)) {
-                    return Err(format!("Failed to save image: {}", e));
-                }
-
-                // Get the image filename and push it to the landscapes vector
-                match utils::get_image_filename(&path) {
-                    Some(filename) => landscapes.push(filename),
-                    None => return Err("Failed to extract image filename".to_string()),
+                // Store the image under its content hash and push the stored name to the landscapes vector
+                match assets::import(campaign.store(), &root, AssetKind::Landscapes, &path) {
+                    Ok(stored_name) => landscapes.push(stored_name),
+                    Err(e) => return Err(TableauError::io("Failed to save image", e).with_path(&path)),
                 }
             }
 
             // Update the landscapes in the JSON for the specified chapter
-            if let Err(e) = utils::update_landscapes(chapter_id, landscapes) {
-                return Err(format!("Failed to update landscapes: {}", e));
+            if let Err(e) = utils::update_landscapes(campaign.store(), &root, &chapter_id, landscapes) {
+                return Err(TableauError::io("Failed to update landscapes", e).with_chapter(&chapter_id));
             }
 
+            transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
             // If everything is successful, return a success message
             Ok("Landscapes uploaded and updated successfully.".to_string())
         }
         None => {
             // No files were selected
-            Err("No images were selected.".to_string())
+            Err(TableauError::cancelled("No images were selected."))
         }
     }
 }
 
 #[tauri::command]
-fn remove_landscape(chapter_id: String, filename: String) -> Result<String, String> {
-    // Get the file path using format_chapter_id
-    let path = utils::format_chapter_id(&chapter_id);
-
-    // Check if the file exists
-    if !path.exists() {
-        return Err(format!("Chapter file for ID '{}' not found.", chapter_id));
-    }
-
-    // Open the file and read its contents
-    let mut file = match File::open(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to open file: {}", err)),
-    };
-
-    let mut content = String::new();
-    if let Err(err) = file.read_to_string(&mut content) {
-        return Err(format!("Failed to read file: {}", err));
-    }
-
-    // Parse the JSON data
-    let mut json_value: Value = match serde_json::from_str(&content) {
-        Ok(json_value) => json_value,
-        Err(err) => return Err(format!("Failed to parse JSON: {}", err)),
-    };
-
-    // Get the landscapes array and remove the filename if it exists
-    if let Some(landscapes) = json_value.get_mut("landscapes") {
-        if landscapes.is_array() {
-            let landscapes_array = landscapes.as_array_mut().unwrap();
-            
-            // Find and remove the filename from the array
-            landscapes_array.retain(|landscape| landscape != &Value::String(filename.clone()));
-        } else {
-            return Err("The landscapes field is not an array.".to_string());
-        }
-    } else {
-        return Err("The JSON file does not contain a landscapes field.".to_string());
-    }
-
-    // Open the file for writing and write the updated JSON data
-    let mut file = match File::create(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to open file for writing: {}", err)),
-    };
-
-    let updated_content = match serde_json::to_string_pretty(&json_value) {
-        Ok(content) => content,
-        Err(err) => return Err(format!("Failed to serialize updated JSON: {}", err)),
-    };
-
-    if let Err(err) = file.write_all(updated_content.as_bytes()) {
-        return Err(format!("Failed to write updated file: {}", err));
-    }
+fn remove_landscape(chapter_id: String, filename: String, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let filename = AssetRef::parse(&filename)?;
+    let transaction = begin_change(&campaign, &chapter_id, "Remove landscape")?;
+
+    campaign
+        .store()
+        .update_chapter(&root, &chapter_id, |chapter| {
+            // Find and remove the filename from the landscapes array
+            chapter.landscapes.retain(|landscape| landscape != filename.as_str());
+            Ok(())
+        })
+        .map_err(|e| TableauError::io("Failed to update chapter", e).with_chapter(&chapter_id))?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
 
     Ok(format!("Successfully removed '{}' from the landscapes array.", filename))
 }
 
 // Tauri command to upload splashes
 #[tauri::command]
-fn upload_splashes(chapter_id: String) -> Result<String, String> {
+fn upload_splashes(chapter_id: String, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let transaction = begin_change(&campaign, &chapter_id, "Upload splashes")?;
 
     match utils::select_image_files() {
         Some(paths) => {
             let mut splashes: Vec<String> = Vec::new();
-            let splashes_directory = "../tableau/assets/splashes";
 
             // Loop through the selected files
             for path in paths {
-                // Save the image to the specified directory
-                if let Err(e) = utils::save_image_to_directory(&path, Path::new(splashes_directory)) {
-                    return Err(format!("Failed to save image: {}", e));
-                }
-
-                // Get the image filename and push it to the splashes vector
-                match utils::get_image_filename(&path) {
-                    Some(filename) => splashes.push(filename),
-                    None => return Err("Failed to extract image filename".to_string()),
+                // Store the image under its content hash and push the stored name to the splashes vector
+                match assets::import(campaign.store(), &root, AssetKind::Splashes, &path) {
+                    Ok(stored_name) => splashes.push(stored_name),
+                    Err(e) => return Err(TableauError::io("Failed to save image", e).with_path(&path)),
                 }
             }
 
             // Update the splashes in the JSON for the specified chapter
-            if let Err(e) = utils::update_splashes(chapter_id, splashes) {
-                return Err(format!("Failed to update splashes: {}", e));
+            if let Err(e) = utils::update_splashes(campaign.store(), &root, &chapter_id, splashes) {
+                return Err(TableauError::io("Failed to update splashes", e).with_chapter(&chapter_id));
             }
 
+            transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
             // If everything is successful, return a success message
             Ok("Splashes uploaded and updated successfully.".to_string())
         }
         None => {
             // No files were selected
-            Err("No images were selected.".to_string())
+            Err(TableauError::cancelled("No images were selected."))
         }
     }
 }
 
 #[tauri::command]
-fn remove_splash(chapter_id: String, filename: String) -> Result<String, String> {
-    // Get the file path using format_chapter_id
-    let path = utils::format_chapter_id(&chapter_id);
-
-    // Check if the file exists
-    if !path.exists() {
-        return Err(format!("Chapter file for ID '{}' not found.", chapter_id));
-    }
-
-    // Open the file and read its contents
-    let mut file = match File::open(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to open file: {}", err)),
-    };
-
-    let mut content = String::new();
-    if let Err(err) = file.read_to_string(&mut content) {
-        return Err(format!("Failed to read file: {}", err));
-    }
-
-    // Parse the JSON data
-    let mut json_value: Value = match serde_json::from_str(&content) {
-        Ok(json_value) => json_value,
-        Err(err) => return Err(format!("Failed to parse JSON: {}", err)),
-    };
-
-    // Get the splashes array and remove the splash with the matching filename
-    if let Some(splashes) = json_value.get_mut("splashes") {
-        if let Some(array) = splashes.as_array_mut() {
-            // Find and remove the object with the matching 'image' field
-            array.retain(|splash| {
-                if let Some(image) = splash.get("image").and_then(|img| img.as_str()) {
-                    image != filename  // Retain items where the image doesn't match the filename
-                } else {
-                    true  // If no image field, retain it (shouldn't happen with valid data)
-                }
-            });
-        } else {
-            return Err("The splashes field is not an array.".to_string());
-        }
-    } else {
-        return Err("The JSON file does not contain a splashes field.".to_string());
-    }
-
-    // Open the file for writing and write the updated JSON data
-    let mut file = match File::create(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to open file for writing: {}", err)),
-    };
-
-    let updated_content = match serde_json::to_string_pretty(&json_value) {
-        Ok(content) => content,
-        Err(err) => return Err(format!("Failed to serialize updated JSON: {}", err)),
-    };
-
-    if let Err(err) = file.write_all(updated_content.as_bytes()) {
-        return Err(format!("Failed to write updated file: {}", err));
-    }
+fn remove_splash(chapter_id: String, filename: String, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let filename = AssetRef::parse(&filename)?;
+    let transaction = begin_change(&campaign, &chapter_id, "Remove splash")?;
+
+    campaign
+        .store()
+        .update_chapter(&root, &chapter_id, |chapter| {
+            // Find and remove the splash with the matching image
+            chapter.splashes.retain(|splash| splash.image != filename.as_str());
+            Ok(())
+        })
+        .map_err(|e| TableauError::io("Failed to update chapter", e).with_chapter(&chapter_id))?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
 
     Ok(format!("Successfully removed splash with image '{}' from the splashes array.", filename))
 }
 
 #[tauri::command]
-fn change_splash_allegiance(chapter_id: String, filename: String) -> Result<String, String> {
-    // Get the file path using format_chapter_id
-    let path = utils::format_chapter_id(&chapter_id);
-
-    // Check if the file exists
-    if !path.exists() {
-        return Err(format!("Chapter file for ID '{}' not found.", chapter_id));
-    }
-
-    // Open the file and read its contents
-    let mut file = match File::open(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to open file: {}", err)),
-    };
-
-    let mut content = String::new();
-    if let Err(err) = file.read_to_string(&mut content) {
-        return Err(format!("Failed to read file: {}", err));
-    }
-
-    // Parse the JSON data
-    let mut json_value: Value = match serde_json::from_str(&content) {
-        Ok(json_value) => json_value,
-        Err(err) => return Err(format!("Failed to parse JSON: {}", err)),
-    };
-
-    // Get the splashes array and find the splash object with the matching filename
-    if let Some(splashes) = json_value.get_mut("splashes") {
-        if let Some(array) = splashes.as_array_mut() {
-            for splash in array.iter_mut() {
-                // Check if the splash object has the matching 'image' field
-                if let Some(image) = splash.get("image").and_then(|img| img.as_str()) {
-                    if image == filename {
-                        // Found the splash object, now toggle the allegiance
-                        if let Some(allegiance) = splash.get_mut("allegiance") {
-                            if allegiance == "neutral" {
-                                *allegiance = Value::String("evil".to_string());
-                            } else if allegiance == "evil" {
-                                *allegiance = Value::String("neutral".to_string());
-                            }
-                        } else {
-                            // If no 'allegiance' field exists, default it to 'neutral'
-                            splash["allegiance"] = Value::String("neutral".to_string());
-                        }
-                    }
+fn change_splash_allegiance(chapter_id: String, filename: String, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let filename = AssetRef::parse(&filename)?;
+    let transaction = begin_change(&campaign, &chapter_id, "Change splash allegiance")?;
+
+    campaign
+        .store()
+        .update_chapter(&root, &chapter_id, |chapter| {
+            // Find the splash object with the matching image and toggle its allegiance
+            for splash in chapter.splashes.iter_mut().filter(|splash| splash.image == filename.as_str()) {
+                if splash.allegiance == "neutral" {
+                    splash.allegiance = "evil".to_string();
+                } else if splash.allegiance == "evil" {
+                    splash.allegiance = "neutral".to_string();
                 }
             }
-        } else {
-            return Err("The splashes field is not an array.".to_string());
-        }
-    } else {
-        return Err("The JSON file does not contain a splashes field.".to_string());
-    }
-
-    // Open the file for writing and write the updated JSON data
-    let mut file = match File::create(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to open file for writing: {}", err)),
-    };
-
-    let updated_content = match serde_json::to_string_pretty(&json_value) {
-        Ok(content) => content,
-        Err(err) => return Err(format!("Failed to serialize updated JSON: {}", err)),
-    };
-
-    if let Err(err) = file.write_all(updated_content.as_bytes()) {
-        return Err(format!("Failed to write updated file: {}", err));
-    }
+            Ok(())
+        })
+        .map_err(|e| TableauError::io("Failed to update chapter", e).with_chapter(&chapter_id))?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
 
     Ok(format!("Successfully updated the allegiance for splash '{}'.", filename))
 }
 
 #[tauri::command]
-fn create_combat(chapter_id: String) -> Result<String, String> {
+fn create_combat(chapter_id: String, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let transaction = begin_change(&campaign, &chapter_id, "Create combat")?;
 
     match utils::select_image_file() { 
         Some(path) => { 
-            let battlemaps_directory = "../tableau/assets/battlemaps";
-            let battlemap: String;
-
-            // Save the image to the specified directory
-            if let Err(e) = utils::save_image_to_directory(&path, Path::new(battlemaps_directory)) {
-                return Err(format!("Failed to save image: {}", e));
-            }
-
-            // Get the image filename
-            match utils::get_image_filename(&path) {
-                Some(filename) => battlemap = filename,
-                None => return Err("Failed to extract image filename".to_string()),
-            }
+            // Store the image under its content hash
+            let battlemap = match assets::import(campaign.store(), &root, AssetKind::Battlemaps, &path) {
+                Ok(stored_name) => stored_name,
+                Err(e) => return Err(TableauError::io("Failed to save image", e).with_path(&path)),
+            };
 
             // Update the battlemap in the JSON for the specified chapter
-            if let Err(e) = utils::update_combat(chapter_id, battlemap) {
-                return Err(format!("Failed to update combat: {}", e));
+            if let Err(e) = utils::update_combat(campaign.store(), &root, &chapter_id, battlemap.clone()) {
+                return Err(TableauError::io("Failed to update combat", e)
+                    .with_chapter(&chapter_id)
+                    .with_battlemap(&battlemap));
             }
 
+            transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
             // If everything is successful, return a success message
             Ok("Combat created successfully.".to_string())
         }
         None => {
             // No file was selected
-            Err("No image was selected.".to_string())
+            Err(TableauError::cancelled("No image was selected."))
         }
     }
 }
 
+/// Removes a combat from a chapter. With `delete_entities`, its entities are deleted too.
 #[tauri::command]
-fn remove_combat(chapter_id: String, battlemap: String) -> Result<String, String> {
-    // Get the file path using format_chapter_id
-    let path = utils::format_chapter_id(&chapter_id);
-
-    // Check if the file exists
-    if !path.exists() {
-        return Err(format!("Chapter file for ID '{}' not found.", chapter_id));
-    }
-
-    // Open the file and read its contents
-    let mut file = match File::open(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to open file: {}", err)),
-    };
-
-    let mut content = String::new();
-    if let Err(err) = file.read_to_string(&mut content) {
-        return Err(format!("Failed to read file: {}", err));
-    }
-
-    // Parse the JSON data
-    let mut json_value: Value = match serde_json::from_str(&content) {
-        Ok(json_value) => json_value,
-        Err(err) => return Err(format!("Failed to parse JSON: {}", err)),
-    };
-
-    // Get the combat array and remove the object with the matching battlemap
-    if let Some(combat_array) = json_value.get_mut("combat") {
-        if combat_array.is_array() {
-            let array = combat_array.as_array_mut().unwrap();
-            array.retain(|combat| combat["battlemap"] != battlemap);
-        } else {
-            return Err("The 'combat' field is not an array.".to_string());
+fn remove_combat(chapter_id: String, battlemap: String, delete_entities: Option<bool>, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+
+    // Keep copies of everything the removal may delete, so undo can bring the combat back whole
+    let mut transaction = begin_change(&campaign, &chapter_id, "Remove combat")?;
+    let record = |transaction: &mut Transaction| -> std::io::Result<()> {
+        if delete_entities == Some(true) {
+            let chapter = campaign.store().read_chapter(&root, &chapter_id)?;
+            for filename in chapter.find_combat(&battlemap).into_iter().flat_map(|combat| &combat.entities) {
+                transaction.capture_entity_with_icon(&EntityId::from_filename(filename)?)?;
+            }
         }
-    } else {
-        return Err("The JSON file does not contain a 'combat' field.".to_string());
-    }
-
-    // Open the file for writing and write the updated JSON data
-    let mut file = match File::create(&path) {
-        Ok(file) => file,
-        Err(err) => return Err(format!("Failed to open file for writing: {}", err)),
+        transaction.capture_file(&combat_log::log_path(&root, &chapter_id, &battlemap)?)?;
+        transaction.capture_file(&snapshots::snapshots_path(&root, &chapter_id, &battlemap)?)
     };
-
-    let updated_content = match serde_json::to_string_pretty(&json_value) {
-        Ok(content) => content,
-        Err(err) => return Err(format!("Failed to serialize updated JSON: {}", err)),
-    };
-
-    if let Err(err) = file.write_all(updated_content.as_bytes()) {
-        return Err(format!("Failed to write updated file: {}", err));
+    record(&mut transaction).map_err(history_error(&chapter_id))?;
+
+    let entities = campaign
+        .store()
+        .update_chapter(&root, &chapter_id, |chapter| {
+            // Remove the combat object with the matching battlemap, keeping hold of its entities
+            let (removed, kept) = std::mem::take(&mut chapter.combat)
+                .into_iter()
+                .partition::<Vec<_>, _>(|combat| combat.battlemap == battlemap);
+            chapter.combat = kept;
+            Ok(removed.into_iter().flat_map(|combat| combat.entities).collect::<Vec<_>>())
+        })
+        .map_err(|e| {
+            TableauError::io("Failed to update chapter", e)
+                .with_chapter(&chapter_id)
+                .with_battlemap(&battlemap)
+        })?;
+
+    if delete_entities == Some(true) {
+        gc::remove_unreferenced_entities(campaign.store(), &root, &entities).map_err(|e| {
+            TableauError::io("Combat removed, but failed to delete its entities", e)
+                .with_chapter(&chapter_id)
+                .with_battlemap(&battlemap)
+        })?;
     }
 
+    combat_log::remove(&root, &chapter_id, &battlemap).map_err(|e| {
+        TableauError::io("Combat removed, but failed to delete its log", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+    CombatSnapshots::remove(&root, &chapter_id, &battlemap).map_err(|e| {
+        TableauError::io("Combat removed, but failed to delete its snapshots", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+    remove_unused_snapshot_icons(&mut transaction, &root, &chapter_id)?;
+    fog::remove(&root, &chapter_id, &battlemap).map_err(|e| {
+        TableauError::io("Combat removed, but failed to delete its fog mask", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
     Ok(format!("Successfully removed combat with battlemap '{}'.", battlemap))
 }
 
-#[tauri::command]
-fn update_battlemap_size(chapter_id: String, battlemap: String, size: u32) -> Result<(), String> {
-    // Get the path of the chapter JSON file
-    let chapter_path = utils::format_chapter_id(&chapter_id);
-
-    // Read existing JSON content into a `serde_json::Value`
-    let mut file = File::open(&chapter_path).map_err(|e| e.to_string())?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).map_err(|e| e.to_string())?;
-
-    // Parse the content into a `Value`
-    let mut json_value: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
-
-    // Find the matching battlemap object in the `combat` array and update the size
-    let combat_array = json_value.get_mut("combat").unwrap().as_array_mut().unwrap();
-    for combat in combat_array.iter_mut() {
-        if combat.get("battlemap").unwrap().as_str().unwrap() == battlemap {
-            // Update the `mapsize` field
-            combat["mapsize"] = Value::Number(serde_json::Number::from(size));
-            break;
-        }
-    }
-
-    // Write the updated JSON content back to the file
-    let updated_content = serde_json::to_string_pretty(&json_value).map_err(|e| e.to_string())?;
-    let mut file = File::create(&chapter_path).map_err(|e| e.to_string())?;
-    file.write_all(updated_content.as_bytes()).map_err(|e| e.to_string())?;
-
-    Ok(())
+fn battlemap_not_found(battlemap: &str, chapter_id: &ChapterId) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("Battlemap '{}' not found in the chapter '{}'", battlemap, chapter_id),
+    )
 }
 
+/// Applies a partial update of `mapsize`, `mapoffset`, `gridsize` and `gridoffset` with JSON merge patch semantics.
+/// The change is visible to reads immediately; bursts of patches (e.g. from a slider) are written to disk once.
 #[tauri::command]
-fn update_battlemap_xoffset(chapter_id: String, battlemap: String, xoffset: i32) -> Result<(), String> {
-    // Get the path of the chapter JSON file
-    let chapter_path = utils::format_chapter_id(&chapter_id);
-
-    // Read existing JSON content into a `serde_json::Value`
-    let mut file = File::open(&chapter_path).map_err(|e| e.to_string())?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).map_err(|e| e.to_string())?;
-
-    // Parse the content into a `Value`
-    let mut json_value: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
-
-    // Find the matching battlemap object in the `combat` array and update the x offset
-    let combat_array = json_value.get_mut("combat").unwrap().as_array_mut().unwrap();
-    for combat in combat_array.iter_mut() {
-        if combat.get("battlemap").unwrap().as_str().unwrap() == battlemap {
-            // Update the `mapoffset.x` field
-            combat["mapoffset"]["x"] = Value::Number(serde_json::Number::from(xoffset));
-            break;
-        }
-    }
+fn patch_combat(chapter_id: String, battlemap: String, patch: serde_json::Value, campaign: State<'_, CampaignState>) -> TableauResult<()> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let patch = CombatPatch::from_json(&patch).map_err(|e| {
+        TableauError::validation(e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+    let root = campaign.root();
+    let transaction = begin_change(&campaign, &chapter_id, "Adjust map and grid")?.coalescing();
+
+    campaign
+        .store()
+        .update_chapter_deferred_reading_entities(&root, &chapter_id, |chapter, read_entity| {
+            let combat = chapter
+                .find_combat_mut(&battlemap)
+                .ok_or_else(|| battlemap_not_found(&battlemap, &chapter_id))?;
+            patch.apply(combat);
+            // The grid decides which hexes the party's sight covers
+            apply_party_vision(combat, read_entity)
+        })
+        .map_err(|e| {
+            TableauError::io("Failed to update combat", e)
+                .with_chapter(&chapter_id)
+                .with_battlemap(&battlemap)
+        })?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))
+}
 
-    // Write the updated JSON content back to the file
-    let updated_content = serde_json::to_string_pretty(&json_value).map_err(|e| e.to_string())?;
-    let mut file = File::create(&chapter_path).map_err(|e| e.to_string())?;
-    file.write_all(updated_content.as_bytes()).map_err(|e| e.to_string())?;
+/// Runs `update` on the terrain of a combat as one undoable change and returns the new terrain
+fn update_terrain(
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    battlemap: &str,
+    label: &str,
+    update: impl FnOnce(&mut Terrain),
+) -> TableauResult<Terrain> {
+    let transaction = begin_change(campaign, chapter_id, label)?;
+    let terrain = campaign
+        .store()
+        .update_chapter_reading_entities(&campaign.root(), chapter_id, |chapter, read_entity| {
+            let combat = chapter
+                .find_combat_mut(battlemap)
+                .ok_or_else(|| battlemap_not_found(battlemap, chapter_id))?;
+            update(&mut combat.terrain);
+            // Walls block the party's sight
+            apply_party_vision(combat, read_entity)?;
+            Ok(combat.terrain.clone())
+        })
+        .map_err(|e| {
+            TableauError::io("Failed to update terrain", e)
+                .with_chapter(chapter_id)
+                .with_battlemap(battlemap)
+        })?;
+    transaction.commit(campaign.journal()).map_err(history_error(chapter_id))?;
+    Ok(terrain)
+}
 
-    Ok(())
+/// Walls given as pairs of neighboring hexes
+fn parse_walls(walls: &[(Hex, Hex)]) -> TableauResult<Vec<Wall>> {
+    walls
+        .iter()
+        .map(|&(a, b)| Wall::between(a, b).map_err(TableauError::validation))
+        .collect()
 }
 
+/// Paints blocked, difficult, water or hazard terrain on hexes of the combat's map, replacing what was there
 #[tauri::command]
-fn update_battlemap_yoffset(chapter_id: String, battlemap: String, yoffset: i32) -> Result<(), String> {
-    // Get the path of the chapter JSON file
-    let chapter_path = utils::format_chapter_id(&chapter_id);
+fn paint_terrain(
+    chapter_id: String,
+    battlemap: String,
+    hexes: Vec<Hex>,
+    kind: TerrainKind,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Terrain> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    kind.validate().map_err(|e| TableauError::validation(e).with_chapter(&chapter_id).with_battlemap(&battlemap))?;
+    update_terrain(&campaign, &chapter_id, &battlemap, "Paint terrain", |terrain| terrain.paint(&hexes, &kind))
+}
 
-    // Read existing JSON content into a `serde_json::Value`
-    let mut file = File::open(&chapter_path).map_err(|e| e.to_string())?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).map_err(|e| e.to_string())?;
+/// Clears the terrain painted on hexes of the combat's map
+#[tauri::command]
+fn erase_terrain(chapter_id: String, battlemap: String, hexes: Vec<Hex>, campaign: State<'_, CampaignState>) -> TableauResult<Terrain> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    update_terrain(&campaign, &chapter_id, &battlemap, "Erase terrain", |terrain| terrain.erase(&hexes))
+}
 
-    // Parse the content into a `Value`
-    let mut json_value: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+/// Puts walls between pairs of neighboring hexes of the combat's map
+#[tauri::command]
+fn add_walls(chapter_id: String, battlemap: String, walls: Vec<(Hex, Hex)>, campaign: State<'_, CampaignState>) -> TableauResult<Terrain> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let walls = parse_walls(&walls)?;
+    update_terrain(&campaign, &chapter_id, &battlemap, "Add walls", |terrain| terrain.add_walls(&walls))
+}
 
-    // Find the matching battlemap object in the `combat` array and update the y offset
-    let combat_array = json_value.get_mut("combat").unwrap().as_array_mut().unwrap();
-    for combat in combat_array.iter_mut() {
-        if combat.get("battlemap").unwrap().as_str().unwrap() == battlemap {
-            // Update the `mapoffset.y` field
-            combat["mapoffset"]["y"] = Value::Number(serde_json::Number::from(yoffset));
-            break;
-        }
-    }
+/// Takes down walls between pairs of neighboring hexes of the combat's map
+#[tauri::command]
+fn remove_walls(chapter_id: String, battlemap: String, walls: Vec<(Hex, Hex)>, campaign: State<'_, CampaignState>) -> TableauResult<Terrain> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let walls = parse_walls(&walls)?;
+    update_terrain(&campaign, &chapter_id, &battlemap, "Remove walls", |terrain| terrain.remove_walls(&walls))
+}
 
-    // Write the updated JSON content back to the file
-    let updated_content = serde_json::to_string_pretty(&json_value).map_err(|e| e.to_string())?;
-    let mut file = File::create(&chapter_path).map_err(|e| e.to_string())?;
-    file.write_all(updated_content.as_bytes()).map_err(|e| e.to_string())?;
+/// Runs `update` on the fog of war of a combat as one undoable change and returns the new fog.
+/// With `follow_party`, the fog is then updated to what the party sees, if its mode says so.
+fn update_fog(
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    battlemap: &str,
+    label: &str,
+    follow_party: bool,
+    update: impl FnOnce(&mut Fog),
+) -> TableauResult<Fog> {
+    let transaction = begin_change(campaign, chapter_id, label)?;
+    let fog = campaign
+        .store()
+        .update_chapter_reading_entities(&campaign.root(), chapter_id, |chapter, read_entity| {
+            let combat = chapter
+                .find_combat_mut(battlemap)
+                .ok_or_else(|| battlemap_not_found(battlemap, chapter_id))?;
+            update(&mut combat.fog);
+            if follow_party {
+                apply_party_vision(combat, read_entity)?;
+            }
+            Ok(combat.fog.clone())
+        })
+        .map_err(|e| {
+            TableauError::io("Failed to update fog of war", e)
+                .with_chapter(chapter_id)
+                .with_battlemap(battlemap)
+        })?;
+    transaction.commit(campaign.journal()).map_err(history_error(chapter_id))?;
+    Ok(fog)
+}
 
-    Ok(())
+/// The hexes of the combat's map an area covers
+fn fog_area_hexes(campaign: &CampaignState, chapter_id: &ChapterId, battlemap: &str, area: &FogArea) -> TableauResult<Vec<Hex>> {
+    let combat = read_combat(campaign, chapter_id, battlemap)?;
+    area.hexes(&Layout::of(&combat))
+        .map_err(|e| TableauError::validation(e).with_chapter(chapter_id).with_battlemap(battlemap))
 }
 
+/// Turns the fog of war of a combat on or off. What has been revealed is kept either way; turning it on in
+/// dynamic or sticky mode also reveals what the party sees.
 #[tauri::command]
-fn update_grid_size(chapter_id: String, battlemap: String, size: u32) -> Result<(), String> {
-    // Get the path of the chapter JSON file
-    let chapter_path = utils::format_chapter_id(&chapter_id);
-
-    // Read existing JSON content into a `serde_json::Value`
-    let mut file = File::open(&chapter_path).map_err(|e| e.to_string())?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).map_err(|e| e.to_string())?;
+fn set_fog_enabled(chapter_id: String, battlemap: String, enabled: bool, campaign: State<'_, CampaignState>) -> TableauResult<Fog> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let label = if enabled { "Enable fog of war" } else { "Disable fog of war" };
+    update_fog(&campaign, &chapter_id, &battlemap, label, true, |fog| fog.enabled = enabled)
+}
 
-    // Parse the content into a `Value`
-    let mut json_value: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+/// Sets whether the fog of war of a combat follows what the party sees, and how far the party sees in its light.
+/// In dynamic or sticky mode the fog is updated to what the party sees right away.
+#[tauri::command]
+fn set_fog_vision(
+    chapter_id: String,
+    battlemap: String,
+    mode: FogMode,
+    vision_radius: u32,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Fog> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    if vision_radius > vision::MAX_VISION {
+        return Err(TableauError::validation(format!("The vision radius can be at most {} feet.", vision::MAX_VISION))
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap));
+    }
+    update_fog(&campaign, &chapter_id, &battlemap, "Change fog vision", true, |fog| {
+        fog.mode = mode;
+        fog.vision_radius = vision_radius;
+    })
+}
 
-    // Find the matching battlemap object in the `combat` array and update the gridsize
-    let combat_array = json_value.get_mut("combat").unwrap().as_array_mut().unwrap();
-    for combat in combat_array.iter_mut() {
-        if combat.get("battlemap").unwrap().as_str().unwrap() == battlemap {
-            // Update the `gridsize` field
-            combat["gridsize"] = Value::Number(serde_json::Number::from(size));
-            break;
+/// Updates the combat's fog of war to what its party sees, reading the entities under the chapter's lock
+/// so the vision matches the state it is written with
+fn apply_party_vision(
+    combat: &mut Combat,
+    read_entity: &mut dyn FnMut(&EntityId) -> std::io::Result<models::Entity>,
+) -> std::io::Result<()> {
+    if !combat.fog.enabled || combat.fog.mode == FogMode::Manual {
+        return Ok(());
+    }
+    let mut entities = Vec::new();
+    for entity_filename in &combat.entities {
+        match read_entity(&EntityId::from_filename(entity_filename)?) {
+            Ok(entity) => entities.push(entity),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
         }
     }
-
-    // Write the updated JSON content back to the file
-    let updated_content = serde_json::to_string_pretty(&json_value).map_err(|e| e.to_string())?;
-    let mut file = File::create(&chapter_path).map_err(|e| e.to_string())?;
-    file.write_all(updated_content.as_bytes()).map_err(|e| e.to_string())?;
-
+    let seen = vision::party_vision(&Layout::of(combat), &combat.terrain, &entities, combat.fog.vision_radius);
+    combat.fog.apply_vision(seen);
     Ok(())
 }
 
-#[tauri::command]
-fn update_grid_xoffset(chapter_id: String, battlemap: String, xoffset: i32) -> Result<(), String> {
-    // Get the path of the chapter JSON file
-    let chapter_path = utils::format_chapter_id(&chapter_id);
-
-    // Read existing JSON content into a `serde_json::Value`
-    let mut file = File::open(&chapter_path).map_err(|e| e.to_string())?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).map_err(|e| e.to_string())?;
-
-    // Parse the content into a `Value`
-    let mut json_value: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
-
-    // Find the matching battlemap object in the `combat` array and update the grid x offset
-    let combat_array = json_value.get_mut("combat").unwrap().as_array_mut().unwrap();
-    for combat in combat_array.iter_mut() {
-        if combat.get("battlemap").unwrap().as_str().unwrap() == battlemap {
-            // Update the `gridoffset.x` field
-            combat["gridoffset"]["x"] = Value::Number(serde_json::Number::from(xoffset));
-            break;
-        }
+/// Updates the combat's fog of war to what the party sees now, if it follows the party's vision.
+/// Records no step of its own, so call it inside the change that moved, added or removed party members,
+/// or changed what they can see.
+fn refresh_vision(campaign: &CampaignState, chapter_id: &ChapterId, battlemap: &str) -> TableauResult<()> {
+    let fog = read_combat(campaign, chapter_id, battlemap)?.fog;
+    if !fog.enabled || fog.mode == FogMode::Manual {
+        return Ok(());
     }
-
-    // Write the updated JSON content back to the file
-    let updated_content = serde_json::to_string_pretty(&json_value).map_err(|e| e.to_string())?;
-    let mut file = File::create(&chapter_path).map_err(|e| e.to_string())?;
-    file.write_all(updated_content.as_bytes()).map_err(|e| e.to_string())?;
-
-    Ok(())
+    campaign
+        .store()
+        .update_chapter_reading_entities(&campaign.root(), chapter_id, |chapter, read_entity| {
+            let combat = chapter
+                .find_combat_mut(battlemap)
+                .ok_or_else(|| battlemap_not_found(battlemap, chapter_id))?;
+            apply_party_vision(combat, read_entity)
+        })
+        .map_err(|e| {
+            TableauError::io("Failed to update fog of war", e)
+                .with_chapter(chapter_id)
+                .with_battlemap(battlemap)
+        })
 }
 
+/// Reveals a hex, every hex within a radius or every hex inside a polygon of the combat's map
 #[tauri::command]
-fn update_grid_yoffset(chapter_id: String, battlemap: String, yoffset: i32) -> Result<(), String> {
-    // Get the path of the chapter JSON file
-    let chapter_path = utils::format_chapter_id(&chapter_id);
-
-    // Read existing JSON content into a `serde_json::Value`
-    let mut file = File::open(&chapter_path).map_err(|e| e.to_string())?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).map_err(|e| e.to_string())?;
+fn reveal_fog(chapter_id: String, battlemap: String, area: FogArea, campaign: State<'_, CampaignState>) -> TableauResult<Fog> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let hexes = fog_area_hexes(&campaign, &chapter_id, &battlemap, &area)?;
+    update_fog(&campaign, &chapter_id, &battlemap, "Reveal fog", false, |fog| fog.reveal(hexes))
+}
 
-    // Parse the content into a `Value`
-    let mut json_value: Value = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+/// Covers a hex, every hex within a radius or every hex inside a polygon of the combat's map with fog again
+#[tauri::command]
+fn hide_fog(chapter_id: String, battlemap: String, area: FogArea, campaign: State<'_, CampaignState>) -> TableauResult<Fog> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let hexes = fog_area_hexes(&campaign, &chapter_id, &battlemap, &area)?;
+    update_fog(&campaign, &chapter_id, &battlemap, "Hide fog", false, |fog| fog.hide(hexes))
+}
 
-    // Find the matching battlemap object in the `combat` array and update the grid y offset
-    let combat_array = json_value.get_mut("combat").unwrap().as_array_mut().unwrap();
-    for combat in combat_array.iter_mut() {
-        if combat.get("battlemap").unwrap().as_str().unwrap() == battlemap {
-            // Update the `gridoffset.y` field
-            combat["gridoffset"]["y"] = Value::Number(serde_json::Number::from(yoffset));
-            break;
-        }
-    }
+/// Renders the combat's fog of war to an image the display lays over the map.
+/// Returns the image's path relative to the fog assets directory.
+#[tauri::command]
+fn render_fog_mask(chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let combat = read_combat(&campaign, &chapter_id, &battlemap)?;
+    let path = fog::mask_path(&root, &chapter_id, &battlemap).map_err(|e| {
+        TableauError::io("Failed to locate fog mask", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
 
-    // Write the updated JSON content back to the file
-    let updated_content = serde_json::to_string_pretty(&json_value).map_err(|e| e.to_string())?;
-    let mut file = File::create(&chapter_path).map_err(|e| e.to_string())?;
-    file.write_all(updated_content.as_bytes()).map_err(|e| e.to_string())?;
+    utils::generate_fog_mask_png(&Layout::of(&combat), &combat.fog.revealed, &path).map_err(|e| {
+        TableauError::other("Failed to render fog of war", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+            .with_path(&path)
+    })?;
 
-    Ok(())
+    let relative = path.strip_prefix(root.assets_dir(AssetKind::Fog)).unwrap_or(&path);
+    Ok(relative.to_string_lossy().replace('\\', "/"))
 }
 
 #[tauri::command]
-fn upload_icon_image() -> Result<String, String> {
+fn upload_icon_image(campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let root = campaign.root();
+
     // Open a file dialog to select an image file
     match utils::select_image_file() {
         Some(path) => {
-            let icon_images_directory = "../tableau/assets/iconimages";
-
-            // Save the image to the specified directory
-            if let Err(e) = utils::save_image_to_directory(&path, Path::new(icon_images_directory)) {
-                return Err(format!("Failed to save image: {}", e));
-            }
-
-            // Convert the path to a filename and return it
-            match utils::get_image_filename(&path) {
-                Some(filename) => Ok(filename),
-                None => Err("Failed to extract image filename.".to_string()),
-            }
+            // Store the image under its content hash and return the stored name
+            let image = assets::import(campaign.store(), &root, AssetKind::IconImages, &path)
+                .map_err(|e| TableauError::io("Failed to save image", e).with_path(&path))?;
+            campaign.begin_crop(image.clone());
+            Ok(image)
         }
-        None => Err("No image file was selected.".to_string()),
+        None => Err(TableauError::cancelled("No image file was selected.")),
     }
 }
 
+/// Lets go of an icon image uploaded with `upload_icon_image` once the icon editor is done with it,
+/// so garbage collection may delete it
+#[tauri::command]
+fn finish_icon_crop(image_filename: String, campaign: State<'_, CampaignState>) {
+    campaign.end_crop(&image_filename);
+}
+
 #[tauri::command]
 fn add_entity(
     chapter_id: String,
@@ -792,27 +848,40 @@ fn add_entity(
     allegiance: String,
     entity_size: String,
     transform_state: TransformStateObject,
-) -> Result<String, String> {
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let image_filename = AssetRef::parse(&image_filename)?;
+    let mut transaction = begin_change(&campaign, &chapter_id, "Add entity")?;
+
     // Step 1: Generate a unique icon ID
-    let icon_id = match utils::generate_icon_id() {
+    let icon_id = match utils::generate_icon_id(&root) {
         Ok(id) => id,
-        Err(err) => return Err(format!("Failed to generate icon ID: {}", err)),
+        Err(err) => return Err(TableauError::io("Failed to generate icon ID", err).with_path(root.entity_ids_file())),
     };
+    transaction.capture_entity_with_icon(&icon_id).map_err(history_error(&chapter_id))?;
 
     // Step 2: Generate the entity icon
-    if let Err(err) = utils::generate_entity_icon(&image_filename, transform_state, &icon_id, &allegiance) {
-        return Err(format!("Failed to generate entity icon: {}", err));
+    if let Err(err) = utils::generate_entity_icon(&root, &image_filename, transform_state, &icon_id.icon_path(&root), &allegiance) {
+        return Err(TableauError::io("Failed to generate entity icon", err)
+            .with_path(image_filename.path(&root, AssetKind::IconImages)));
     }
 
     // Step 3: Create the entity JSON file
-    if let Err(err) = utils::create_entity(&icon_id, &allegiance, &entity_size) {
-        return Err(format!("Failed to create entity: {}", err));
+    if let Err(err) = utils::create_entity(campaign.store(), &root, &icon_id, &allegiance, &entity_size) {
+        return Err(TableauError::io("Failed to create entity", err)
+            .with_path(icon_id.json_path(&root)));
     }
 
     // Step 4: Update the entities in the chapter JSON
-    if let Err(err) = utils::update_entities(&chapter_id, &battlemap_id, &icon_id) {
-        return Err(format!("Failed to update entities: {}", err));
+    if let Err(err) = utils::update_entities(campaign.store(), &root, &chapter_id, &battlemap_id, &icon_id) {
+        return Err(TableauError::io("Failed to update entities", err)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap_id));
     }
+    refresh_vision(&campaign, &chapter_id, &battlemap_id)?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
 
     // If all steps succeed, return success message
     Ok(format!("Entity '{}' added successfully.", icon_id))
@@ -822,179 +891,1465 @@ fn add_entity(
 fn get_entities(
     chapter_id: String,
     battlemap_id: String,
-) -> Result<Vec<models::Entity>, String> {
-    // Step 1: Locate the chapter file
-    let chapter_path = utils::format_chapter_id(&chapter_id);
-
-    if !chapter_path.exists() {
-        return Err(format!("Chapter file for '{}' not found.", chapter_id));
-    }
-
-    // Step 2: Read the chapter data
-    let mut file = File::open(&chapter_path).map_err(|e| format!("Failed to open chapter file: {}", e))?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).map_err(|e| format!("Failed to read chapter file: {}", e))?;
-
-    // Step 3: Parse the chapter JSON
-    let json_value: Value = serde_json::from_str(&content).map_err(|e| format!("Failed to parse JSON: {}", e))?;
-
-    // Step 4: Find the combat object matching the battlemap_id
-    let combat_array = json_value
-        .get("combat")
-        .and_then(|c| c.as_array())
-        .ok_or_else(|| "Combat array not found in the chapter file.".to_string())?;
-
-    let combat = combat_array
-        .iter()
-        .find(|c| c.get("battlemap").and_then(|b| b.as_str()) == Some(&battlemap_id))
-        .ok_or_else(|| format!("Battlemap '{}' not found in the chapter '{}'", battlemap_id, chapter_id))?;
-
-    // Step 5: Get the entities array from the combat object
-    let entities_array = combat
-        .get("entities")
-        .and_then(|e| e.as_array())
-        .ok_or_else(|| "Entities array not found in the combat object.".to_string())?;
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Vec<models::Entity>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+
+    // Step 1: Load the chapter
+    let chapter = campaign
+        .store()
+        .read_chapter(&root, &chapter_id)
+        .map_err(|e| TableauError::io("Failed to load chapter", e).with_chapter(&chapter_id))?;
+
+    // Step 2: Find the combat object matching the battlemap_id
+    let combat = chapter.find_combat(&battlemap_id).ok_or_else(|| {
+        TableauError::from(battlemap_not_found(&battlemap_id, &chapter_id))
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap_id)
+    })?;
 
-    // Step 6: Load each entity.json file using load_entity_from_file and collect the data
+    // Step 3: Load each entity.json file using load_entity_from_file and collect the data
     let mut entities_data = Vec::new();
-    for entity_file in entities_array {
-        if let Some(entity_filename) = entity_file.as_str() {
-            // Use the load_entity_from_file function to get the entity data
-            match utils::load_entity_from_file(entity_filename) {
-                Ok(entity) => entities_data.push(entity),
-                Err(err) => return Err(format!("Failed to load entity '{}': {}", entity_filename, err)),
+    for entity_filename in &combat.entities {
+        let entity_id = EntityId::from_filename(entity_filename)
+            .map_err(|e| TableauError::from(e).with_chapter(&chapter_id).with_battlemap(&battlemap_id))?;
+        match campaign.store().read_entity(&root, &entity_id) {
+            Ok(entity) => entities_data.push(entity),
+            Err(err) => {
+                return Err(TableauError::io(&format!("Failed to load entity '{}'", entity_filename), err)
+                    .with_chapter(&chapter_id)
+                    .with_battlemap(&battlemap_id)
+                    .with_path(entity_id.json_path(&root)))
             }
         }
     }
 
-    // Step 7: Return the array of entity data to the front end
+    // Step 4: Return the array of entity data to the front end
     Ok(entities_data)
 }
 
+/// Applies a partial update of an entity's properties. Moves, damage and conditions have their own commands so
+/// they end up in the combat log; a patch touches only the fields it names, so it never undoes them.
+/// The edit is recorded in the history of the chapter it was made from.
 #[tauri::command]
-fn update_entity(entity: models::Entity) -> Result<(), String> {
-    // Extract the ID from the entity's icon filename, assuming it ends with `.png`.
-    let icon_filename = entity.icon.trim();
-    if !icon_filename.ends_with(".png") {
-        return Err("Invalid icon format. Expected format: 'id.png'".to_string());
+fn update_entity(chapter_id: String, entity: String, patch: serde_json::Value, campaign: State<'_, CampaignState>) -> TableauResult<()> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let id = EntityId::from_filename(&entity)?;
+    let patch: EntityPatch = serde_json::from_value(patch)
+        .map_err(|e| TableauError::validation(format!("Invalid entity patch: {}", e)).with_path(id.json_path(&root)))?;
+
+    let mut transaction = begin_change(&campaign, &chapter_id, "Edit entity")?;
+    transaction.capture_entity(&id).map_err(history_error(&chapter_id))?;
+
+    // The patch is applied to the entity as it is under the store's lock, not to the editor's copy of it
+    let sight_changed = campaign
+        .store()
+        .update_entity(&root, &id, |entity| {
+            let sight_changed = patch.affects_sight(entity);
+            patch.apply(entity);
+            Ok(sight_changed)
+        })
+        .map_err(|e| {
+            TableauError::io(&format!("Failed to write to file '{}'", id.json_filename()), e).with_path(id.json_path(&root))
+        })?;
+
+    // The fog of every combat the entity is in may follow what it sees
+    if sight_changed {
+        let chapter = campaign
+            .store()
+            .read_chapter(&root, &chapter_id)
+            .map_err(|e| TableauError::io("Failed to load chapter", e).with_chapter(&chapter_id))?;
+        for combat in chapter.combat.iter().filter(|combat| combat.entities.contains(&id.json_filename())) {
+            refresh_vision(&campaign, &chapter_id, &combat.battlemap)?;
+        }
     }
-    let id = &icon_filename[..icon_filename.len() - 4]; // Remove the `.png` extension.
-
-    // Define the path to the entity file (`../tableau/entities/id.json`).
-    let entity_file_path = Path::new("../tableau/entities").join(format!("{}.json", id));
-
-    // Serialize the Entity struct to a JSON string.
-    let serialized_entity = to_string_pretty(&entity).map_err(|e| {
-        format!("Failed to serialize entity: {}", e)
-    })?;
-
-    // Open the file in write mode and write the serialized JSON.
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true) // Clear the file before writing the new content.
-        .open(&entity_file_path)
-        .map_err(|e| format!("Failed to open file '{}': {}", entity_file_path.display(), e))?;
 
-    file.write_all(serialized_entity.as_bytes()).map_err(|e| {
-        format!("Failed to write to file '{}': {}", entity_file_path.display(), e)
-    })?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
 
     Ok(())
 }
 
 #[tauri::command]
-fn remove_entity(chapter_id: String, battlemap_id: String, icon_id: String) -> Result<(), String> {
+fn remove_entity(
+    app: AppHandle,
+    chapter_id: String,
+    battlemap_id: String,
+    icon_id: String,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<()> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+
+    let icon_id = EntityId::from_filename(&icon_id)?;
+    let combat = read_combat(&campaign, &chapter_id, &battlemap_id)?;
+
+    // Conditions of other entities may expire if the turn passes on
+    let mut transaction = begin_change(&campaign, &chapter_id, "Remove entity")?;
+    let record = |transaction: &mut Transaction| -> std::io::Result<()> {
+        transaction.capture_entity_with_icon(&icon_id)?;
+        transaction.capture_combat_entities(&combat)
+    };
+    record(&mut transaction).map_err(history_error(&chapter_id))?;
+
     // Step 1: Remove the associated PNG file in the assets directory
-    let png_path = format!("../tableau/assets/entities/{}", icon_id);
+    let png_path = icon_id.icon_path(&root);
     if let Err(err) = std::fs::remove_file(&png_path) {
-        return Err(format!("Failed to delete PNG file '{}': {}", png_path, err));
+        return Err(TableauError::io("Failed to delete PNG file", err).with_path(&png_path));
     }
 
     // Step 2: Remove the associated JSON file in the entities directory
-    let json_filename = icon_id.replace(".png", ".json");
-    let json_path = format!("../tableau/entities/{}", json_filename);
-    if let Err(err) = std::fs::remove_file(&json_path) {
-        return Err(format!("Failed to delete JSON file '{}': {}", json_path, err));
+    let json_filename = icon_id.json_filename();
+    let json_path = icon_id.json_path(&root);
+    if let Err(err) = campaign.store().remove_entity_file(&json_path) {
+        return Err(TableauError::io("Failed to delete JSON file", err).with_path(&json_path));
     }
 
-    // Step 3: Remove the icon from the entities array in the specified combat object
-    let chapter_path = utils::format_chapter_id(&chapter_id);
-
-    // Read the existing chapter file
-    let mut file = std::fs::File::open(&chapter_path).map_err(|e| format!("Failed to open chapter file: {}", e))?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).map_err(|e| format!("Failed to read chapter file: {}", e))?;
-
-    // Parse the chapter JSON
-    let mut json_value: serde_json::Value = serde_json::from_str(&content).map_err(|e| format!("Failed to parse JSON: {}", e))?;
-
-    // Locate the specified battlemap's combat object
-    let combat_array = json_value
-        .get_mut("combat")
-        .and_then(|c| c.as_array_mut())
-        .ok_or_else(|| "Combat array not found in the chapter file.".to_string())?;
-
-    let combat = combat_array
-        .iter_mut()
-        .find(|c| c.get("battlemap").and_then(|b| b.as_str()) == Some(&battlemap_id))
-        .ok_or_else(|| format!("Battlemap '{}' not found in the chapter '{}'", battlemap_id, chapter_id))?;
-
-    // Find and remove the entity from the entities array
-    if let Some(entities_array) = combat.get_mut("entities").and_then(|e| e.as_array_mut()) {
-        entities_array.retain(|e| e.as_str() != Some(&json_filename));
+    // Step 3: Remove the icon from the entities array and the turn order of the specified combat object
+    let skip = combat_inactive_entities(&campaign, &combat, &chapter_id)?;
+    let (initiative, turn_change) = campaign
+        .store()
+        .update_chapter(&root, &chapter_id, |chapter| {
+            let combat = chapter
+                .find_combat_mut(&battlemap_id)
+                .ok_or_else(|| battlemap_not_found(&battlemap_id, &chapter_id))?;
+            combat.entities.retain(|entity| entity != &json_filename);
+            let turn_change = combat.initiative.remove(&json_filename, &skip);
+            Ok((combat.initiative.clone(), turn_change))
+        })
+        .map_err(|e| {
+            TableauError::io("Failed to update chapter file", e)
+                .with_chapter(&chapter_id)
+                .with_battlemap(&battlemap_id)
+        })?;
+
+    // Step 4: If it was the entity's turn, the turn has passed on
+    if let Some(turn_change) = turn_change {
+        announce_turn_change(&app, &campaign, &mut transaction, &combat, &chapter_id, &initiative, &turn_change)?;
     }
-
-    // Write the updated JSON content back to the file
-    let updated_content = serde_json::to_string_pretty(&json_value).map_err(|e| format!("Failed to serialize updated JSON: {}", e))?;
-    let mut file = std::fs::File::create(&chapter_path).map_err(|e| format!("Failed to open chapter file for writing: {}", e))?;
-    file.write_all(updated_content.as_bytes()).map_err(|e| format!("Failed to write updated chapter file: {}", e))?;
+    refresh_vision(&campaign, &chapter_id, &battlemap_id)?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
 
     Ok(())
 }
 
+/// Adds `count` copies of an entity to the combat, each with a new ID and its own copy of the icon.
+/// Copies get the next free numbers after the entity's name and stand on the nearest free hexes around it.
 #[tauri::command]
-fn get_entity(entity_filename: String) -> Result<Value, String> {
-    // Define the path to the entity file in the entities directory
-    let entity_path = Path::new("../tableau/entities").join(&entity_filename);
-
-    // Check if the file exists
-    if !entity_path.exists() {
-        return Err(format!("Entity file '{}' not found.", entity_filename));
+fn duplicate_entity(
+    chapter_id: String,
+    battlemap_id: String,
+    entity: String,
+    count: u32,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Vec<models::Entity>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    if count == 0 || count > duplicate::MAX_COPIES {
+        return Err(TableauError::validation(format!(
+            "Between 1 and {} copies can be made at once.",
+            duplicate::MAX_COPIES
+        )));
     }
 
-    // Open and read the file
-    let mut file = match File::open(&entity_path) {
-        Ok(f) => f,
-        Err(e) => return Err(format!("Failed to open entity file '{}': {}", entity_filename, e)),
-    };
-
-    let mut content = String::new();
-    if let Err(e) = file.read_to_string(&mut content) {
-        return Err(format!("Failed to read entity file '{}': {}", entity_filename, e));
+    // Step 1: Load the combat's entities for their names and the hexes they stand on
+    let source_id = EntityId::from_filename(&entity)?;
+    let combat = read_combat(&campaign, &chapter_id, &battlemap_id)?;
+    let (source, others) = read_combat_entities(&campaign, &chapter_id, &combat, &source_id)?;
+
+    let names = duplicate::numbered_names(&source.name, others.iter().map(|other| other.name.as_str()), count);
+    let locations = duplicate::free_hexes_around(
+        &Layout::of(&combat),
+        source.location,
+        others.iter().map(|other| other.location),
+        count,
+    );
+
+    // Step 2: Create the copies, each with its own ID, icon and entity file.
+    // A failure part way through drops the transaction, which takes back the copies made so far.
+    let mut transaction = begin_change(&campaign, &chapter_id, "Duplicate entity")?;
+    let mut copies = Vec::new();
+    for (index, name) in names.into_iter().enumerate() {
+        let icon_id = utils::generate_icon_id(&root)
+            .map_err(|e| TableauError::io("Failed to generate icon ID", e).with_path(root.entity_ids_file()))?;
+        transaction.capture_entity_with_icon(&icon_id).map_err(history_error(&chapter_id))?;
+
+        let icon_path = icon_id.icon_path(&root);
+        std::fs::copy(source_id.icon_path(&root), &icon_path)
+            .map_err(|e| TableauError::io("Failed to copy entity icon", e).with_path(&icon_path))?;
+
+        let mut copy = source.clone();
+        copy.icon = icon_id.icon_filename();
+        copy.name = name;
+        // Copies pile up on the source's hex once every hex around it is taken
+        copy.location = locations.get(index).copied().unwrap_or(source.location);
+        campaign.store().write_entity(&root, &icon_id, copy.clone()).map_err(|e| {
+            TableauError::io("Failed to create entity", e).with_path(icon_id.json_path(&root))
+        })?;
+        copies.push((icon_id, copy));
     }
 
-    // Parse the content into JSON
-    match serde_json::from_str(&content) {
-        Ok(json_value) => Ok(json_value),
-        Err(e) => Err(format!("Failed to parse entity JSON in file '{}': {}", entity_filename, e)),
-    }
+    // Step 3: Add the copies to the combat
+    campaign
+        .store()
+        .update_chapter(&root, &chapter_id, |chapter| {
+            let combat = chapter
+                .find_combat_mut(&battlemap_id)
+                .ok_or_else(|| battlemap_not_found(&battlemap_id, &chapter_id))?;
+            combat.entities.extend(copies.iter().map(|(icon_id, _)| icon_id.json_filename()));
+            Ok(())
+        })
+        .map_err(|e| {
+            TableauError::io("Failed to update entities", e)
+                .with_chapter(&chapter_id)
+                .with_battlemap(&battlemap_id)
+        })?;
+    refresh_vision(&campaign, &chapter_id, &battlemap_id)?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
+    Ok(copies.into_iter().map(|(_, copy)| copy).collect())
 }
 
+/// Lists the campaign's bestiary
+#[tauri::command]
+fn get_templates(campaign: State<'_, CampaignState>) -> TableauResult<Vec<Template>> {
+    Ok(load_bestiary(&campaign.root())?.templates)
+}
+
+/// Creates a template from scratch, cropping its icon the way `add_entity` does.
+/// The bestiary belongs to the whole campaign; changes to it are recorded in the history of the chapter being edited.
+#[tauri::command]
+fn create_template(
+    chapter_id: String,
+    image_filename: String,
+    transform_state: TransformStateObject,
+    name: String,
+    allegiance: String,
+    entity_size: String,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Template> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let image_filename = AssetRef::parse(&image_filename)?;
+    let mut bestiary = load_bestiary(&root)?;
+    let id = bestiary.generate_id();
+    let icon = format!("{}.png", id);
+    let icon_path = bestiary::icons_dir(&root).join(&icon);
+
+    let mut transaction = begin_change(&campaign, &chapter_id, "Create template")?;
+    let record = |transaction: &mut Transaction| -> std::io::Result<()> {
+        transaction.capture_file(&bestiary::templates_path(&root))?;
+        transaction.capture_file(&icon_path)
+    };
+    record(&mut transaction).map_err(history_error(&chapter_id))?;
+
+    // The icon is generated in the trash, so a failure part way through leaves no half-written icon in the bestiary
+    let scratch_path = root.trash_dir().join(format!("template_{}", icon));
+    let generated = utils::generate_entity_icon(&root, &image_filename, transform_state, &scratch_path, &allegiance)
+        .map_err(|e| {
+            TableauError::io("Failed to generate entity icon", e).with_path(image_filename.path(&root, AssetKind::IconImages))
+        })
+        .and_then(|_| {
+            std::fs::create_dir_all(bestiary::icons_dir(&root))
+                .and_then(|_| std::fs::rename(&scratch_path, &icon_path))
+                .map_err(|e| TableauError::io("Failed to save template icon", e).with_path(&icon_path))
+        });
+    if let Err(e) = generated {
+        if scratch_path.exists() {
+            if let Err(err) = std::fs::remove_file(&scratch_path) {
+                log::warn!("Failed to delete template icon '{}': {}", scratch_path.display(), err);
+            }
+        }
+        return Err(e);
+    }
+
+    let stats = TemplateStats::of(&models::Entity::new(icon.clone(), String::new(), allegiance.clone(), entity_size.clone()));
+    let template = Template {
+        id,
+        icon,
+        name: name.trim().to_string(),
+        allegiance,
+        size: entity_size,
+        stats,
+    };
+    bestiary.templates.push(template.clone());
+    save_bestiary(&bestiary, &root)?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
+    Ok(template)
+}
+
+/// Saves an entity's stats and icon as a new template. The entity becomes an instance of it.
+#[tauri::command]
+fn create_template_from_entity(chapter_id: String, entity: String, campaign: State<'_, CampaignState>) -> TableauResult<Template> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let entity_id = EntityId::from_filename(&entity)?;
+    let source = campaign
+        .store()
+        .read_entity(&root, &entity_id)
+        .map_err(|e| TableauError::io("Failed to load entity file", e).with_path(entity_id.json_path(&root)))?;
+
+    let mut bestiary = load_bestiary(&root)?;
+    let id = bestiary.generate_id();
+    let icon = format!("{}.png", id);
+    let icon_path = bestiary::icons_dir(&root).join(&icon);
+
+    // The entity's link to the template is part of the step, so undo never leaves it pointing at a missing template
+    let mut transaction = begin_change(&campaign, &chapter_id, "Save entity as template")?;
+    let record = |transaction: &mut Transaction| -> std::io::Result<()> {
+        transaction.capture_file(&bestiary::templates_path(&root))?;
+        transaction.capture_file(&icon_path)?;
+        transaction.capture_entity(&entity_id)
+    };
+    record(&mut transaction).map_err(history_error(&chapter_id))?;
+    std::fs::create_dir_all(bestiary::icons_dir(&root))
+        .and_then(|_| std::fs::copy(entity_id.icon_path(&root), &icon_path))
+        .map_err(|e| TableauError::io("Failed to save template icon", e).with_path(&icon_path))?;
+
+    let template = Template {
+        id: id.clone(),
+        icon,
+        // "Goblin 3" makes a "Goblin" template
+        name: duplicate::strip_number(source.name.trim()).to_string(),
+        allegiance: source.allegiance.clone(),
+        size: source.size.clone(),
+        stats: TemplateStats::of(&source),
+    };
+    bestiary.templates.push(template.clone());
+    save_bestiary(&bestiary, &root)?;
+
+    campaign
+        .store()
+        .update_entity(&root, &entity_id, |entity| {
+            entity.template = Some(id);
+            Ok(())
+        })
+        .map_err(|e| TableauError::io("Failed to update entity", e).with_path(entity_id.json_path(&root)))?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
+    Ok(template)
+}
+
+/// Saves changes to a template's name, defaults and stats; its icon stays as it is. With `propagate`, instances
+/// whose stats still match the template's old stats get the new ones. Returns the instances that were updated.
+#[tauri::command]
+fn update_template(
+    chapter_id: String,
+    template: Template,
+    propagate: bool,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Vec<String>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let mut bestiary = load_bestiary(&root)?;
+    let existing = bestiary
+        .find_mut(&template.id)
+        .ok_or_else(|| TableauError::not_found(format!("Template '{}' not found.", template.id)))?;
+
+    let before = existing.stats.clone();
+    let propagate = propagate && before != template.stats;
+    let instances = if propagate {
+        bestiary::instances(campaign.store(), &root, &template.id)
+            .map_err(|e| TableauError::io("Failed to find the template's instances", e))?
+    } else {
+        Vec::new()
+    };
+
+    let mut transaction = begin_change(&campaign, &chapter_id, "Edit template")?;
+    let record = |transaction: &mut Transaction| -> std::io::Result<()> {
+        transaction.capture_file(&bestiary::templates_path(&root))?;
+        instances.iter().try_for_each(|id| transaction.capture_entity(id))
+    };
+    record(&mut transaction).map_err(history_error(&chapter_id))?;
+
+    existing.name = template.name.trim().to_string();
+    existing.allegiance = template.allegiance;
+    existing.size = template.size;
+    existing.stats = template.stats.clone();
+    save_bestiary(&bestiary, &root)?;
+
+    // An instance whose stats differ from the old template was changed by hand and keeps its own stats
+    let updated = bestiary::update_instances(campaign.store(), &root, &instances, |entity| {
+        if TemplateStats::of(entity) != before {
+            return false;
+        }
+        template.stats.apply(entity);
+        true
+    })
+    .map_err(|e| TableauError::io("Template saved, but failed to update its instances", e))?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
+    Ok(updated)
+}
+
+/// Deletes a template. Its instances stay as they are, no longer linked to it.
+#[tauri::command]
+fn remove_template(chapter_id: String, template_id: String, campaign: State<'_, CampaignState>) -> TableauResult<()> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let mut bestiary = load_bestiary(&root)?;
+    let index = bestiary
+        .templates
+        .iter()
+        .position(|template| template.id == template_id)
+        .ok_or_else(|| TableauError::not_found(format!("Template '{}' not found.", template_id)))?;
+    let template = bestiary.templates.remove(index);
+    let icon_path = bestiary::icons_dir(&root).join(&template.icon);
+    let instances = bestiary::instances(campaign.store(), &root, &template.id)
+        .map_err(|e| TableauError::io("Failed to find the template's instances", e))?;
+
+    let mut transaction = begin_change(&campaign, &chapter_id, "Remove template")?;
+    let record = |transaction: &mut Transaction| -> std::io::Result<()> {
+        transaction.capture_file(&bestiary::templates_path(&root))?;
+        transaction.capture_file(&icon_path)?;
+        instances.iter().try_for_each(|id| transaction.capture_entity(id))
+    };
+    record(&mut transaction).map_err(history_error(&chapter_id))?;
+
+    save_bestiary(&bestiary, &root)?;
+    if let Err(e) = std::fs::remove_file(&icon_path) {
+        log::warn!("Failed to delete template icon '{}': {}", icon_path.display(), e);
+    }
+
+    bestiary::update_instances(campaign.store(), &root, &instances, |entity| {
+        entity.template = None;
+        true
+    })
+    .map_err(|e| TableauError::io("Template deleted, but failed to unlink its instances", e))?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
+    Ok(())
+}
+
+/// Adds a new instance of a template to a combat. It takes the template's name, numbered if the combat
+/// already has entities of that name.
+#[tauri::command]
+fn add_entity_from_template(
+    chapter_id: String,
+    battlemap_id: String,
+    template_id: String,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<models::Entity> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let bestiary = load_bestiary(&root)?;
+    let template = bestiary
+        .find(&template_id)
+        .ok_or_else(|| TableauError::not_found(format!("Template '{}' not found.", template_id)))?;
+
+    let combat = read_combat(&campaign, &chapter_id, &battlemap_id)?;
+    let mut names = Vec::new();
+    for entity_filename in &combat.entities {
+        let entity_id = EntityId::from_filename(entity_filename)?;
+        if let Ok(entity) = campaign.store().read_entity(&root, &entity_id) {
+            names.push(entity.name);
+        }
+    }
+    let name = if names.iter().any(|name| duplicate::strip_number(name.trim()) == template.name) {
+        duplicate::numbered_names(&template.name, names.iter().map(String::as_str), 1).remove(0)
+    } else {
+        template.name.clone()
+    };
+
+    let mut transaction = begin_change(&campaign, &chapter_id, "Add entity from template")?;
+
+    let icon_id = utils::generate_icon_id(&root)
+        .map_err(|e| TableauError::io("Failed to generate icon ID", e).with_path(root.entity_ids_file()))?;
+    transaction.capture_entity_with_icon(&icon_id).map_err(history_error(&chapter_id))?;
+
+    let icon_path = icon_id.icon_path(&root);
+    std::fs::copy(bestiary::icons_dir(&root).join(&template.icon), &icon_path)
+        .map_err(|e| TableauError::io("Failed to copy template icon", e).with_path(&icon_path))?;
+
+    let entity = template.instantiate(icon_id.icon_filename(), name);
+    campaign
+        .store()
+        .write_entity(&root, &icon_id, entity.clone())
+        .map_err(|e| TableauError::io("Failed to create entity", e).with_path(icon_id.json_path(&root)))?;
+
+    utils::update_entities(campaign.store(), &root, &chapter_id, &battlemap_id, &icon_id).map_err(|e| {
+        TableauError::io("Failed to update entities", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap_id)
+    })?;
+    refresh_vision(&campaign, &chapter_id, &battlemap_id)?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
+    Ok(entity)
+}
+
+fn load_bestiary(root: &CampaignRoot) -> TableauResult<Bestiary> {
+    Bestiary::load(root).map_err(|e| TableauError::io("Failed to load bestiary", e).with_path(root.bestiary_dir()))
+}
+
+fn save_bestiary(bestiary: &Bestiary, root: &CampaignRoot) -> TableauResult<()> {
+    bestiary
+        .save(root)
+        .map_err(|e| TableauError::io("Failed to save bestiary", e).with_path(root.bestiary_dir()))
+}
+
+#[tauri::command]
+fn get_entity(entity_filename: String, campaign: State<'_, CampaignState>) -> TableauResult<models::Entity> {
+    let root = campaign.root();
+
+    // Load (and migrate if needed) the entity file from the entities directory
+    let entity_id = EntityId::from_filename(&entity_filename)?;
+    campaign.store().read_entity(&root, &entity_id).map_err(|e| {
+        TableauError::io("Failed to load entity file", e).with_path(entity_id.json_path(&root))
+    })
+}
+
+
+/// Starts recording a command in the chapter's undo history
+fn begin_change<'a>(campaign: &'a CampaignState, chapter_id: &ChapterId, label: &str) -> TableauResult<Transaction<'a>> {
+    campaign
+        .journal()
+        .begin(campaign.store(), &campaign.root(), chapter_id, label)
+        .map_err(|e| TableauError::io("Failed to load chapter", e).with_chapter(chapter_id))
+}
+
+fn history_error(chapter_id: &ChapterId) -> impl Fn(std::io::Error) -> TableauError + '_ {
+    move |e| TableauError::io("Failed to record the change in the undo history", e).with_chapter(chapter_id)
+}
+
+/// Reverts the chapter's most recent change. Returns what was undone, or nothing if there was nothing to undo.
+#[tauri::command]
+fn undo(chapter_id: String, campaign: State<'_, CampaignState>) -> TableauResult<Option<String>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let stepped = campaign
+        .journal()
+        .undo(campaign.store(), &campaign.root(), &chapter_id)
+        .map_err(|e| TableauError::io("Failed to undo", e).with_chapter(&chapter_id))?;
+    record_stepped_over(&campaign, &chapter_id, stepped, |step| CombatEventKind::StepUndone { step })
+}
+
+/// Reapplies the chapter's most recently undone change. Returns what was redone, or nothing if there was nothing to redo.
+#[tauri::command]
+fn redo(chapter_id: String, campaign: State<'_, CampaignState>) -> TableauResult<Option<String>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let stepped = campaign
+        .journal()
+        .redo(campaign.store(), &campaign.root(), &chapter_id)
+        .map_err(|e| TableauError::io("Failed to redo", e).with_chapter(&chapter_id))?;
+    record_stepped_over(&campaign, &chapter_id, stepped, |step| CombatEventKind::StepRedone { step })
+}
+
+/// Marks an undone or redone step in the logs of the combats it recorded events in, so the summary
+/// stops or starts counting them again. Returns the step's label.
+fn record_stepped_over(
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    stepped: Option<SteppedOver>,
+    marker: impl Fn(u64) -> CombatEventKind,
+) -> TableauResult<Option<String>> {
+    let Some(stepped) = stepped else {
+        return Ok(None);
+    };
+    for (step, battlemap) in stepped.combat_logs {
+        // The combat may be gone along with its log by now
+        if !combat_log::log_path(&campaign.root(), chapter_id, &battlemap)?.exists() {
+            continue;
+        }
+        record_combat_events(campaign, chapter_id, &battlemap, 0, None, vec![marker(step)])?;
+    }
+    Ok(Some(stepped.label))
+}
+
+#[tauri::command]
+fn get_history(chapter_id: String, campaign: State<'_, CampaignState>) -> TableauResult<HistoryInfo> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    Ok(campaign.journal().info(&chapter_id))
+}
+
+fn read_combat(campaign: &CampaignState, chapter_id: &ChapterId, battlemap: &str) -> TableauResult<Combat> {
+    let chapter = campaign
+        .store()
+        .read_chapter(&campaign.root(), chapter_id)
+        .map_err(|e| TableauError::io("Failed to load chapter", e).with_chapter(chapter_id))?;
+    chapter.find_combat(battlemap).cloned().ok_or_else(|| {
+        TableauError::from(battlemap_not_found(battlemap, chapter_id))
+            .with_chapter(chapter_id)
+            .with_battlemap(battlemap)
+    })
+}
+
+/// Loads one entity of the combat along with the combat's other entities. Missing entity files are skipped.
+fn read_combat_entities(
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    combat: &Combat,
+    entity_id: &EntityId,
+) -> TableauResult<(models::Entity, Vec<models::Entity>)> {
+    if !combat.entities.contains(&entity_id.json_filename()) {
+        return Err(TableauError::not_found(format!("Entity '{}' is not part of this combat.", entity_id))
+            .with_chapter(chapter_id)
+            .with_battlemap(&combat.battlemap));
+    }
+
+    let mut entity = None;
+    let mut others = Vec::new();
+    for (other_id, other) in load_combat_entities(campaign, chapter_id, combat)? {
+        if &other_id == entity_id {
+            entity = Some(other);
+        } else {
+            others.push(other);
+        }
+    }
+    let entity = entity.ok_or_else(|| {
+        TableauError::not_found(format!("Entity file '{}' not found.", entity_id.json_filename()))
+            .with_path(entity_id.json_path(&campaign.root()))
+    })?;
+    Ok((entity, others))
+}
+
+/// Loads every entity of the combat. Missing entity files are skipped.
+fn load_combat_entities(
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    combat: &Combat,
+) -> TableauResult<Vec<(EntityId, models::Entity)>> {
+    let root = campaign.root();
+    let mut entities = Vec::new();
+    for entity_filename in &combat.entities {
+        let entity_id = EntityId::from_filename(entity_filename)?;
+        match campaign.store().read_entity(&root, &entity_id) {
+            Ok(entity) => entities.push((entity_id, entity)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(TableauError::io(&format!("Failed to load entity '{}'", entity_filename), e)
+                    .with_chapter(chapter_id)
+                    .with_battlemap(&combat.battlemap)
+                    .with_path(entity_id.json_path(&root)))
+            }
+        }
+    }
+    Ok(entities)
+}
+
+/// Entities of a combat that cannot take a turn, read before the chapter is locked for an update
+fn combat_inactive_entities(campaign: &CampaignState, combat: &Combat, chapter_id: &ChapterId) -> TableauResult<HashSet<String>> {
+    initiative::inactive_entities(campaign.store(), &campaign.root(), &combat.entities).map_err(|e| {
+        TableauError::io("Failed to load entities", e)
+            .with_chapter(chapter_id)
+            .with_battlemap(&combat.battlemap)
+    })
+}
+
+/// Tells every window about the new active turn, then counts down the conditions of the entities whose
+/// turn ended or started and lets the constructor window know about the ones that expired.
+/// The turn change and the expired conditions are recorded in the combat's log.
+fn announce_turn_change(
+    app: &AppHandle,
+    campaign: &CampaignState,
+    transaction: &mut Transaction,
+    combat: &Combat,
+    chapter_id: &ChapterId,
+    initiative: &Initiative,
+    change: &TurnChange,
+) -> TableauResult<()> {
+    let payload = TurnChanged {
+        chapter_id: chapter_id.as_str(),
+        battlemap: &combat.battlemap,
+        change,
+    };
+    if let Err(err) = app.emit(TURN_CHANGED_EVENT, payload) {
+        log::warn!("Failed to emit {}: {}", TURN_CHANGED_EVENT, err);
+    }
+
+    let expired = conditions::tick_for_turn_change(campaign.store(), &campaign.root(), &combat.entities, initiative, change)
+        .map_err(|e| {
+            TableauError::io("Failed to update conditions", e)
+                .with_chapter(chapter_id)
+                .with_battlemap(&combat.battlemap)
+        })?;
+
+    let mut events = vec![CombatEventKind::TurnChange {
+        previous: change.previous.clone(),
+        active: change.active.clone(),
+    }];
+    for condition in expired {
+        events.push(CombatEventKind::ConditionRemoved {
+            entity: condition.entity.clone(),
+            name: condition.name.clone(),
+            condition: condition.condition.clone(),
+            expired: true,
+        });
+        if let Err(err) = app.emit_to(CONSTRUCTOR_WINDOW, CONDITION_EXPIRED_EVENT, condition) {
+            log::warn!("Failed to emit {}: {}", CONDITION_EXPIRED_EVENT, err);
+        }
+    }
+
+    let step = transaction.log_events(&combat.battlemap);
+    record_combat_events(campaign, chapter_id, &combat.battlemap, change.round, Some(step), events)
+}
+
+/// Runs `change` on the combat's turn order, saves it and announces the new active turn.
+/// Dead entities and entities whose file is missing are passed in as the ones to skip.
+fn change_turn(
+    app: &AppHandle,
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    battlemap: &str,
+    label: &str,
+    change: impl FnOnce(&mut Initiative, &HashSet<String>) -> Result<TurnChange, String>,
+) -> TableauResult<Initiative> {
+    let combat = read_combat(campaign, chapter_id, battlemap)?;
+    let skip = combat_inactive_entities(campaign, &combat, chapter_id)?;
+
+    // Conditions count down with the turn, so their entities are part of the change
+    let mut transaction = begin_change(campaign, chapter_id, label)?;
+    transaction.capture_combat_entities(&combat).map_err(history_error(chapter_id))?;
+
+    let (initiative, turn_change) = campaign
+        .store()
+        .update_chapter(&campaign.root(), chapter_id, |chapter| {
+            let combat = chapter
+                .find_combat_mut(battlemap)
+                .ok_or_else(|| battlemap_not_found(battlemap, chapter_id))?;
+            let turn_change = change(&mut combat.initiative, &skip)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+            Ok((combat.initiative.clone(), turn_change))
+        })
+        .map_err(|e| {
+            TableauError::io("Failed to update initiative", e)
+                .with_chapter(chapter_id)
+                .with_battlemap(battlemap)
+        })?;
+
+    announce_turn_change(app, campaign, &mut transaction, &combat, chapter_id, &initiative, &turn_change)?;
+    transaction.commit(campaign.journal()).map_err(history_error(chapter_id))?;
+    Ok(initiative)
+}
+
+/// Rolls a d20 plus the initiative bonus for every entity in the combat and starts round 1
+#[tauri::command]
+fn roll_initiative(
+    app: AppHandle,
+    chapter_id: String,
+    battlemap: String,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Initiative> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let combat = read_combat(&campaign, &chapter_id, &battlemap)?;
+
+    let mut bonuses = Vec::new();
+    for entity_filename in &combat.entities {
+        let entity_id = EntityId::from_filename(entity_filename)?;
+        let entity = campaign.store().read_entity(&root, &entity_id).map_err(|e| {
+            TableauError::io(&format!("Failed to load entity '{}'", entity_filename), e)
+                .with_chapter(&chapter_id)
+                .with_battlemap(&battlemap)
+                .with_path(entity_id.json_path(&root))
+        })?;
+        bonuses.push((entity_filename.clone(), entity.initiative_bonus));
+    }
+
+    change_turn(&app, &campaign, &chapter_id, &battlemap, "Roll initiative", |initiative, skip| {
+        initiative.order.clear();
+        for (entity, bonus) in &bonuses {
+            initiative.set(entity, initiative::roll(*bonus), *bonus);
+        }
+        Ok(initiative.start(skip))
+    })
+}
+
+/// Sets an entity's initiative by hand, or rolls it when `initiative` is omitted (e.g. for a latecomer).
+/// The current turn and round are kept.
+#[tauri::command]
+fn set_initiative(
+    app: AppHandle,
+    chapter_id: String,
+    battlemap: String,
+    entity: String,
+    initiative: Option<i32>,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Initiative> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let entity_id = EntityId::from_filename(&entity)?;
+    let json_filename = entity_id.json_filename();
+
+    let combat = read_combat(&campaign, &chapter_id, &battlemap)?;
+    if !combat.entities.contains(&json_filename) {
+        return Err(TableauError::validation(format!("Entity '{}' is not part of this combat.", entity_id))
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap));
+    }
+
+    let bonus = campaign
+        .store()
+        .read_entity(&root, &entity_id)
+        .map_err(|e| TableauError::io("Failed to load entity file", e).with_path(entity_id.json_path(&root)))?
+        .initiative_bonus;
+    let value = initiative.unwrap_or_else(|| initiative::roll(bonus));
+
+    change_turn(&app, &campaign, &chapter_id, &battlemap, "Set initiative", |initiative, _| {
+        initiative.set(&json_filename, value, bonus);
+        Ok(initiative.change_from(initiative.active.clone()))
+    })
+}
+
+/// Passes the turn to the next entity, skipping dead and delayed ones. Starts round 1 if the combat has not begun.
+#[tauri::command]
+fn advance_turn(app: AppHandle, chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<Initiative> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    change_turn(&app, &campaign, &chapter_id, &battlemap, "Next turn", |initiative, skip| initiative.advance(skip))
+}
+
+#[tauri::command]
+fn rewind_turn(app: AppHandle, chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<Initiative> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    change_turn(&app, &campaign, &chapter_id, &battlemap, "Previous turn", |initiative, skip| initiative.rewind(skip))
+}
+
+#[tauri::command]
+fn advance_round(app: AppHandle, chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<Initiative> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    change_turn(&app, &campaign, &chapter_id, &battlemap, "Next round", |initiative, skip| initiative.advance_round(skip))
+}
+
+#[tauri::command]
+fn rewind_round(app: AppHandle, chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<Initiative> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    change_turn(&app, &campaign, &chapter_id, &battlemap, "Previous round", |initiative, skip| initiative.rewind_round(skip))
+}
+
+/// The active entity delays its turn; it is skipped until `resume_turn` lets it act
+#[tauri::command]
+fn delay_turn(app: AppHandle, chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<Initiative> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    change_turn(&app, &campaign, &chapter_id, &battlemap, "Delay turn", |initiative, skip| initiative.delay(skip))
+}
+
+/// A delayed entity acts now, moving into the turn order right before the entity whose turn it was
+#[tauri::command]
+fn resume_turn(
+    app: AppHandle,
+    chapter_id: String,
+    battlemap: String,
+    entity: String,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Initiative> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let json_filename = EntityId::from_filename(&entity)?.json_filename();
+    change_turn(&app, &campaign, &chapter_id, &battlemap, "Resume turn", |initiative, _| initiative.resume(&json_filename))
+}
+
+/// Ends the combat's turn tracking: clears the order and resets the round counter
+#[tauri::command]
+fn clear_initiative(app: AppHandle, chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<Initiative> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    change_turn(&app, &campaign, &chapter_id, &battlemap, "Clear initiative", |initiative, _| Ok(initiative.reset()))
+}
+
+/// Rolls a dice expression such as `2d6+3`, `4d6kh3`, `1d20 adv` or `8d6 fire` and records it in the chapter's
+/// roll log. Public rolls are also sent to the display window. Passing the `seed` of an earlier roll replays it.
+#[tauri::command]
+fn roll_dice(
+    app: AppHandle,
+    chapter_id: String,
+    expression: String,
+    public: Option<bool>,
+    seed: Option<u64>,
+    session: State<'_, RollSession>,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<RollRecord> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let expression = DiceExpression::parse(&expression).map_err(|e| TableauError::validation(e).with_chapter(&chapter_id))?;
+    if seed.is_some_and(|seed| seed >= dice::SEED_LIMIT) {
+        return Err(TableauError::validation(format!("Seeds must be below {}.", dice::SEED_LIMIT)).with_chapter(&chapter_id));
+    }
+
+    // Rolls are logged next to the chapter, so the chapter has to exist
+    campaign
+        .store()
+        .read_chapter(&root, &chapter_id)
+        .map_err(|e| TableauError::io("Failed to load chapter", e).with_chapter(&chapter_id))?;
+
+    let record = session
+        .roll(&root, &chapter_id, &expression, public.unwrap_or(false), seed)
+        .map_err(|e| TableauError::io("Failed to record roll", e).with_chapter(&chapter_id))?;
+
+    if record.public {
+        if let Err(err) = app.emit(PUBLIC_ROLL_EVENT, &record) {
+            log::warn!("Failed to emit {}: {}", PUBLIC_ROLL_EVENT, err);
+        }
+    }
+
+    Ok(record)
+}
+
+/// Returns the chapter's rolls from the current session, oldest first, or from every session if `all_sessions` is set
+#[tauri::command]
+fn get_roll_log(
+    chapter_id: String,
+    all_sessions: Option<bool>,
+    session: State<'_, RollSession>,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Vec<RollRecord>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let log = RollLog::load(&campaign.root(), &chapter_id)
+        .map_err(|e| TableauError::io("Failed to load roll log", e).with_chapter(&chapter_id))?;
+
+    Ok(match all_sessions {
+        Some(true) => log.rolls,
+        _ => log.rolls.into_iter().filter(|roll| roll.session == session.id()).collect(),
+    })
+}
+
+/// Deals damage to an entity of the combat, applying its resistances, vulnerabilities and immunities to
+/// `damage_type`. Temporary hitpoints are drained first; entities at 0 hitpoints die, or start dying if they are
+/// player characters. The change is recorded in the combat's log, crediting `source` with the damage and any kill.
+#[tauri::command]
+fn apply_damage(
+    chapter_id: String,
+    battlemap: String,
+    entity: String,
+    amount: u32,
+    damage_type: Option<String>,
+    source: Option<String>,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<HitpointChange> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let damage_type = damage_type
+        .as_deref()
+        .filter(|name| !name.trim().is_empty())
+        .map(DamageType::parse)
+        .transpose()
+        .map_err(TableauError::validation)?;
+    let source = source.as_deref().map(combat_entity_filename).transpose()?;
+
+    // The dead see nothing, so a kill may shrink the party's vision
+    let died = std::cell::Cell::new(false);
+    let update = |entity: &mut models::Entity, filename: &str| {
+        let change = entity.apply_damage(amount, damage_type);
+        died.set(change.died);
+        let mut events = vec![CombatEventKind::Damage {
+            entity: filename.to_string(),
+            name: entity.name.clone(),
+            source: source.clone(),
+            change: change.clone(),
+        }];
+        if change.died {
+            events.push(CombatEventKind::Death {
+                entity: filename.to_string(),
+                name: entity.name.clone(),
+                source,
+            });
+        }
+        Ok((change, events))
+    };
+    update_combat_entity_then(&campaign, &chapter_id, &battlemap, &entity, "Apply damage", update, || {
+        if died.get() {
+            refresh_vision(&campaign, &chapter_id, &battlemap)
+        } else {
+            Ok(())
+        }
+    })
+}
+
+/// Heals an entity of the combat up to its maximum hitpoints and records the change in the combat's log.
+/// Dead entities cannot be healed; dying ones are stabilized once they are above 0 hitpoints.
+#[tauri::command]
+fn apply_healing(
+    chapter_id: String,
+    battlemap: String,
+    entity: String,
+    amount: u32,
+    source: Option<String>,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<HitpointChange> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let source = source.as_deref().map(combat_entity_filename).transpose()?;
+
+    update_combat_entity(&campaign, &chapter_id, &battlemap, &entity, "Apply healing", |entity, filename| {
+        let change = entity.apply_healing(amount)?;
+        let event = CombatEventKind::Healing {
+            entity: filename.to_string(),
+            name: entity.name.clone(),
+            source,
+            change: change.clone(),
+        };
+        Ok((change, vec![event]))
+    })
+}
+
+/// The hex of a combat's grid under a point of the display, given in pixels of the reference container
+#[tauri::command]
+fn hex_at(
+    chapter_id: String,
+    battlemap: String,
+    point: models::Coordinates,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Hex> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let combat = read_combat(&campaign, &chapter_id, &battlemap)?;
+    Ok(Layout::of(&combat).hex_at(point))
+}
+
+/// The hexes a straight line between two hexes passes through, both ends included, e.g. to measure a distance.
+/// Lines longer than `hex::MAX_LINE_LENGTH` steps are refused.
+#[tauri::command]
+fn hex_line(from: Hex, to: Hex) -> TableauResult<Vec<Hex>> {
+    if from.distance(to) > hex::MAX_LINE_LENGTH {
+        return Err(TableauError::validation(format!(
+            "A line can be at most {} hexes long.",
+            hex::MAX_LINE_LENGTH
+        )));
+    }
+    Ok(from.line_to(to))
+}
+
+/// The movement map of an entity of the combat, along with where it stands and how far it can move
+fn entity_movement(
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    battlemap: &str,
+    entity: &str,
+    obstacles: Option<Obstacles>,
+) -> TableauResult<(MovementMap, models::Entity)> {
+    let entity_id = EntityId::from_filename(entity)?;
+    let combat = read_combat(campaign, chapter_id, battlemap)?;
+    let (entity, others) = read_combat_entities(campaign, chapter_id, &combat, &entity_id)?;
+    let map = MovementMap::new(Layout::of(&combat), &combat.terrain, &entity, &others, obstacles.unwrap_or_default());
+    Ok((map, entity))
+}
+
+/// Every hex an entity of the combat can end its move on with its speed, cheapest first.
+/// Hostile entities and `obstacles` block hexes; `obstacles.difficult` hexes cost double.
+#[tauri::command]
+fn get_reachable_hexes(
+    chapter_id: String,
+    battlemap: String,
+    entity: String,
+    obstacles: Option<Obstacles>,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Vec<ReachableHex>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let (map, entity) = entity_movement(&campaign, &chapter_id, &battlemap, &entity, obstacles)?;
+    Ok(movement::reachable(&map, entity.location, entity.speed / movement::FEET_PER_HEX))
+}
+
+/// The cheapest path for an entity of the combat from where it stands to `to`, None if it can't get there
+#[tauri::command]
+fn find_path(
+    chapter_id: String,
+    battlemap: String,
+    entity: String,
+    to: Hex,
+    obstacles: Option<Obstacles>,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<Option<MovePath>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let (map, entity) = entity_movement(&campaign, &chapter_id, &battlemap, &entity, obstacles)?;
+    Ok(movement::find_path(&map, entity.location, to))
+}
+
+/// Moves an entity of the combat, e.g. after it was dragged on the display, and records the move
+#[tauri::command]
+fn move_entity(
+    chapter_id: String,
+    battlemap: String,
+    entity: String,
+    location: Hex,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<()> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let update = |entity: &mut models::Entity, filename: &str| {
+        let event = CombatEventKind::Movement {
+            entity: filename.to_string(),
+            name: entity.name.clone(),
+            from: entity.location,
+            to: location,
+        };
+        entity.location = location;
+        Ok(((), vec![event]))
+    };
+    // The fog follows the party in the same undo step as the move
+    update_combat_entity_then(&campaign, &chapter_id, &battlemap, &entity, "Move entity", update, || {
+        refresh_vision(&campaign, &chapter_id, &battlemap)
+    })
+}
+
+/// Puts a condition on an entity of the combat and records it in the combat's log
+#[tauri::command]
+fn add_condition(
+    chapter_id: String,
+    battlemap: String,
+    entity: String,
+    condition: models::Condition,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<()> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    condition.validate().map_err(TableauError::validation)?;
+
+    update_combat_entity(&campaign, &chapter_id, &battlemap, &entity, "Add condition", |entity, filename| {
+        entity.conditions.push(condition.clone());
+        let event = CombatEventKind::ConditionApplied {
+            entity: filename.to_string(),
+            name: entity.name.clone(),
+            condition,
+        };
+        Ok(((), vec![event]))
+    })
+}
+
+/// Removes the condition at `index` from an entity of the combat and records it in the combat's log
+#[tauri::command]
+fn remove_condition(
+    chapter_id: String,
+    battlemap: String,
+    entity: String,
+    index: usize,
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<()> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    update_combat_entity(&campaign, &chapter_id, &battlemap, &entity, "Remove condition", |entity, filename| {
+        if index >= entity.conditions.len() {
+            return Err(format!("The entity has no condition at position {}.", index));
+        }
+        let event = CombatEventKind::ConditionRemoved {
+            entity: filename.to_string(),
+            name: entity.name.clone(),
+            condition: entity.conditions.remove(index),
+            expired: false,
+        };
+        Ok(((), vec![event]))
+    })
+}
+
+/// The entity file (`<id>.json`) for an entity given by either of its files or its ID
+fn combat_entity_filename(entity: &str) -> TableauResult<String> {
+    Ok(EntityId::from_filename(entity)?.json_filename())
+}
+
+/// Applies `update` to an entity of the combat, then appends the events it returns to the combat's log.
+/// `update` also gets the entity's file name (`<id>.json`) to put in the events.
+fn update_combat_entity<T>(
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    battlemap: &str,
+    entity: &str,
+    label: &str,
+    update: impl FnOnce(&mut models::Entity, &str) -> Result<(T, Vec<CombatEventKind>), String>,
+) -> TableauResult<T> {
+    update_combat_entity_then(campaign, chapter_id, battlemap, entity, label, update, || Ok(()))
+}
+
+/// Like `update_combat_entity`, running `then` after the entity is updated so what it changes is part of the same undo step
+fn update_combat_entity_then<T>(
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    battlemap: &str,
+    entity: &str,
+    label: &str,
+    update: impl FnOnce(&mut models::Entity, &str) -> Result<(T, Vec<CombatEventKind>), String>,
+    then: impl FnOnce() -> TableauResult<()>,
+) -> TableauResult<T> {
+    let root = campaign.root();
+    let entity_id = EntityId::from_filename(entity)?;
+    let filename = entity_id.json_filename();
+
+    let combat = read_combat(campaign, chapter_id, battlemap)?;
+    if !combat.entities.contains(&filename) {
+        return Err(TableauError::not_found(format!("Entity '{}' is not part of this combat.", filename))
+            .with_chapter(chapter_id)
+            .with_battlemap(battlemap));
+    }
+
+    let mut transaction = begin_change(campaign, chapter_id, label)?;
+    transaction.capture_entity(&entity_id).map_err(history_error(chapter_id))?;
+
+    let (result, events) = campaign
+        .store()
+        .update_entity(&root, &entity_id, |entity| {
+            update(entity, &filename).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+        })
+        .map_err(|e| TableauError::io("Failed to update entity", e).with_path(entity_id.json_path(&root)))?;
+    then()?;
+    let step = transaction.log_events(battlemap);
+    transaction.commit(campaign.journal()).map_err(history_error(chapter_id))?;
+
+    record_combat_events(campaign, chapter_id, battlemap, combat.initiative.round, Some(step), events)?;
+    Ok(result)
+}
+
+/// Appends events to the combat's log, stamped with the current time, `round` and the undo step they belong to
+fn record_combat_events(
+    campaign: &CampaignState,
+    chapter_id: &ChapterId,
+    battlemap: &str,
+    round: u32,
+    step: Option<u64>,
+    events: Vec<CombatEventKind>,
+) -> TableauResult<()> {
+    let root = campaign.root();
+    for kind in events {
+        combat_log::append(&root, chapter_id, battlemap, &CombatEvent::now(round, step, kind)).map_err(|e| {
+            TableauError::io("Failed to record the change in the combat log", e)
+                .with_chapter(chapter_id)
+                .with_battlemap(battlemap)
+        })?;
+    }
+    Ok(())
+}
 
+/// Returns the combat's log, oldest event first
+#[tauri::command]
+fn get_combat_log(chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<Vec<CombatEvent>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    load_combat_log(&campaign, &chapter_id, &battlemap)
+}
+
+fn load_combat_log(campaign: &CampaignState, chapter_id: &ChapterId, battlemap: &str) -> TableauResult<Vec<CombatEvent>> {
+    combat_log::load(&campaign.root(), chapter_id, battlemap).map_err(|e| {
+        TableauError::io("Failed to load combat log", e)
+            .with_chapter(chapter_id)
+            .with_battlemap(battlemap)
+    })
+}
+
+/// Totals the combat's log per entity: damage dealt and taken, healing, kills and deaths, plus the rounds elapsed
+#[tauri::command]
+fn get_combat_summary(chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<CombatSummary> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    summarize_combat(&campaign, &chapter_id, &battlemap)
+}
+
+fn summarize_combat(campaign: &CampaignState, chapter_id: &ChapterId, battlemap: &str) -> TableauResult<CombatSummary> {
+    let root = campaign.root();
+    let events = load_combat_log(campaign, chapter_id, battlemap)?;
+
+    // Entities that never took damage or moved are only named in their files
+    let mut summary = CombatSummary::from_events(battlemap, &events);
+    summary.fill_names(|entity| {
+        let entity_id = EntityId::from_filename(entity).ok()?;
+        let name = campaign.store().read_entity(&root, &entity_id).ok()?.name;
+        Some(name).filter(|name| !name.is_empty())
+    });
+    Ok(summary)
+}
+
+/// Writes the combat's summary as Markdown to a file the user picks. Returns the file's path.
+#[tauri::command]
+fn export_combat_summary(chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let summary = summarize_combat(&campaign, &chapter_id, &battlemap)?;
+
+    let default_name = format!("{} summary.md", Path::new(&battlemap).file_stem().and_then(|stem| stem.to_str()).unwrap_or("encounter"));
+    let path = utils::select_markdown_save_file(&default_name)
+        .ok_or_else(|| TableauError::cancelled("No file was selected."))?;
+
+    std::fs::write(&path, summary.to_markdown()).map_err(|e| {
+        TableauError::io("Failed to write the summary", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+            .with_path(&path)
+    })?;
+    Ok(path.display().to_string())
+}
+
+/// Saves the combat's current state under `name`: map and grid settings, turn order and every entity file
+#[tauri::command]
+fn create_snapshot(chapter_id: String, battlemap: String, name: String, campaign: State<'_, CampaignState>) -> TableauResult<SnapshotInfo> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(TableauError::validation("A snapshot needs a name."));
+    }
+
+    let combat = read_combat(&campaign, &chapter_id, &battlemap)?;
+    let mut snapshots = load_snapshots(&campaign, &chapter_id, &battlemap)?;
+    if snapshots.find(name).is_some() {
+        return Err(TableauError::from(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("A snapshot named '{}' already exists.", name),
+        ))
+        .with_chapter(&chapter_id)
+        .with_battlemap(&battlemap));
+    }
+
+    // Taking the snapshot copies the entities' icons, so undo has to delete those copies again
+    let mut transaction = begin_change(&campaign, &chapter_id, "Save snapshot")?;
+    capture_snapshots_file(&mut transaction, &root, &chapter_id, &battlemap)?;
+    let record = |transaction: &mut Transaction| -> std::io::Result<()> {
+        for filename in &combat.entities {
+            transaction.capture_file(&snapshots::icon_copy_path(&root, &chapter_id, &EntityId::from_filename(filename)?))?;
+        }
+        Ok(())
+    };
+    record(&mut transaction).map_err(history_error(&chapter_id))?;
+
+    let snapshot = snapshots::take(campaign.store(), &root, &chapter_id, name, &combat).map_err(|e| {
+        TableauError::io("Failed to take snapshot", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+    let info = SnapshotInfo::from(&snapshot);
+    snapshots.snapshots.push(snapshot);
+    snapshots.save(&root, &chapter_id, &battlemap).map_err(|e| {
+        TableauError::io("Failed to save snapshot", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
+    Ok(info)
+}
+
+/// Lists the combat's snapshots, oldest first
+#[tauri::command]
+fn list_snapshots(chapter_id: String, battlemap: String, campaign: State<'_, CampaignState>) -> TableauResult<Vec<SnapshotInfo>> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let snapshots = load_snapshots(&campaign, &chapter_id, &battlemap)?;
+    Ok(snapshots.snapshots.iter().map(SnapshotInfo::from).collect())
+}
+
+/// Puts the combat back the way it was when the snapshot was taken. Every entity file is rewritten from the
+/// snapshot, entities added since are taken out of the combat and deleted unless another combat uses them,
+/// and the map, grid and turn order are replaced. Returns the restored combat.
+#[tauri::command]
+fn restore_snapshot(chapter_id: String, battlemap: String, name: String, campaign: State<'_, CampaignState>) -> TableauResult<Combat> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let root = campaign.root();
+    let snapshots = load_snapshots(&campaign, &chapter_id, &battlemap)?;
+    let snapshot = snapshots.find(&name).ok_or_else(|| {
+        TableauError::not_found(format!("Snapshot '{}' not found.", name))
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+
+    // Both the snapshot's entities and the ones added since may be rewritten or deleted
+    let mut transaction = begin_change(&campaign, &chapter_id, "Restore snapshot")?;
+    let record = |transaction: &mut Transaction| -> std::io::Result<()> {
+        let chapter = campaign.store().read_chapter(&root, &chapter_id)?;
+        let current = chapter.find_combat(&battlemap).into_iter().flat_map(|combat| &combat.entities);
+        for filename in snapshot.combat.entities.iter().chain(current) {
+            transaction.capture_entity_with_icon(&EntityId::from_filename(filename)?)?;
+        }
+        Ok(())
+    };
+    record(&mut transaction).map_err(history_error(&chapter_id))?;
+
+    snapshots::restore_entities(campaign.store(), &root, &chapter_id, snapshot).map_err(|e| {
+        TableauError::io("Failed to restore entities", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+
+    let replaced = campaign
+        .store()
+        .update_chapter(&root, &chapter_id, |chapter| {
+            Ok(match chapter.find_combat_mut(&battlemap) {
+                Some(combat) => {
+                    // Terrain describes the map rather than the encounter, so it stays as it is now
+                    let mut restored = snapshot.combat.clone();
+                    restored.terrain = std::mem::take(&mut combat.terrain);
+                    std::mem::replace(combat, restored).entities
+                }
+                None => {
+                    chapter.combat.push(snapshot.combat.clone());
+                    Vec::new()
+                }
+            })
+        })
+        .map_err(|e| {
+            TableauError::io("Failed to update chapter", e)
+                .with_chapter(&chapter_id)
+                .with_battlemap(&battlemap)
+        })?;
+
+    let added: Vec<String> = replaced
+        .into_iter()
+        .filter(|entity| !snapshot.combat.entities.contains(entity))
+        .collect();
+    gc::remove_unreferenced_entities(campaign.store(), &root, &added).map_err(|e| {
+        TableauError::io("Snapshot restored, but failed to delete entities added since", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))?;
+
+    Ok(snapshot.combat.clone())
+}
+
+/// Deletes one of the combat's snapshots
+#[tauri::command]
+fn remove_snapshot(chapter_id: String, battlemap: String, name: String, campaign: State<'_, CampaignState>) -> TableauResult<()> {
+    let chapter_id = ChapterId::parse(&chapter_id)?;
+    let mut snapshots = load_snapshots(&campaign, &chapter_id, &battlemap)?;
+    let count = snapshots.snapshots.len();
+    snapshots.snapshots.retain(|snapshot| snapshot.name != name);
+    if snapshots.snapshots.len() == count {
+        return Err(TableauError::not_found(format!("Snapshot '{}' not found.", name))
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap));
+    }
+
+    let root = campaign.root();
+    let mut transaction = begin_change(&campaign, &chapter_id, "Remove snapshot")?;
+    capture_snapshots_file(&mut transaction, &root, &chapter_id, &battlemap)?;
+    snapshots.save(&root, &chapter_id, &battlemap).map_err(|e| {
+        TableauError::io("Failed to save snapshots", e)
+            .with_chapter(&chapter_id)
+            .with_battlemap(&battlemap)
+    })?;
+    remove_unused_snapshot_icons(&mut transaction, &root, &chapter_id)?;
+    transaction.commit(campaign.journal()).map_err(history_error(&chapter_id))
+}
+
+/// Deletes the icon copies that no snapshot left in the chapter uses
+fn remove_unused_snapshot_icons(transaction: &mut Transaction, root: &CampaignRoot, chapter_id: &ChapterId) -> TableauResult<()> {
+    let unused = snapshots::unreferenced_icons(root, chapter_id)
+        .map_err(|e| TableauError::io("Failed to read snapshots", e).with_chapter(chapter_id))?;
+    for icon in &unused {
+        transaction.capture_file(icon).map_err(history_error(chapter_id))?;
+    }
+    for icon in &unused {
+        std::fs::remove_file(icon)
+            .map_err(|e| TableauError::io("Failed to delete a snapshot icon", e).with_chapter(chapter_id).with_path(icon))?;
+    }
+    Ok(())
+}
+
+fn capture_snapshots_file(transaction: &mut Transaction, root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> TableauResult<()> {
+    snapshots::snapshots_path(root, chapter_id, battlemap)
+        .and_then(|path| transaction.capture_file(&path))
+        .map_err(history_error(chapter_id))
+}
+
+fn load_snapshots(campaign: &CampaignState, chapter_id: &ChapterId, battlemap: &str) -> TableauResult<CombatSnapshots> {
+    CombatSnapshots::load(&campaign.root(), chapter_id, battlemap).map_err(|e| {
+        TableauError::io("Failed to load snapshots", e)
+            .with_chapter(chapter_id)
+            .with_battlemap(battlemap)
+    })
+}
+
+/// Renders the hex grid overlay for a battlemap into `assets/hexgrids`, named after the battlemap.
+/// Returns the hex grid's file name.
 #[tauri::command]
 fn generate_hexgrid(
+    battlemap: String,
     container_width: u32,
     container_height: u32,
     hex_size: f32,
     overflow: usize,
-    output_path: String,
-) -> Result<String, String> {
+    campaign: State<'_, CampaignState>,
+) -> TableauResult<String> {
+    let battlemap = AssetRef::parse(&battlemap)?;
+    let output_path = battlemap.path(&campaign.root(), AssetKind::Hexgrids);
+
     // Create metadata filename alongside the PNG
-    let metadata_path = format!("{}.meta", output_path);
+    let metadata_path = output_path.with_file_name(format!("{}.meta", battlemap));
     
     // Check if both files exist and parameters match
-    if Path::new(&output_path).exists() && Path::new(&metadata_path).exists() {
+    if output_path.exists() && metadata_path.exists() {
         // Read existing metadata
         if let Ok(metadata_content) = std::fs::read_to_string(&metadata_path) {
             if let Ok(metadata) = serde_json::from_str::<serde_json::Value>(&metadata_content) {
@@ -1003,17 +2358,17 @@ fn generate_hexgrid(
                    metadata["container_height"] == container_height &&
                    metadata["hex_size"] == hex_size &&
                    metadata["overflow"] == overflow {
-                    return Ok(format!("Hex grid already exists with current parameters at '{}'.", output_path));
+                    return Ok(battlemap.to_string());
                 }
             }
         }
     }
 
-    // Generate grid centers
-    let grid_centers = utils::generate_grid_centers(container_width, container_height, hex_size, overflow);
+    // Generate grid hexes
+    let grid_hexes = utils::generate_grid_hexes(container_width, container_height, hex_size, overflow);
 
     // Generate the PNG
-    match utils::generate_hex_grid_png(&grid_centers, hex_size, container_width, container_height, &output_path) {
+    match utils::generate_hex_grid_png(&grid_hexes, hex_size, container_width, container_height, &output_path) {
         Ok(_) => {
             // Save metadata for future parameter comparison
             let metadata = serde_json::json!({
@@ -1023,12 +2378,135 @@ fn generate_hexgrid(
                 "overflow": overflow
             });
             
-            if let Err(e) = std::fs::write(&metadata_path, metadata.to_string()) {
+            if let Err(e) = storage::write_atomic(&metadata_path, metadata.to_string().as_bytes()) {
                 eprintln!("Warning: Failed to save hexgrid metadata: {}", e);
             }
             
-            Ok(format!("Hex grid PNG generated at '{}'.", output_path))
+            Ok(battlemap.to_string())
         },
-        Err(e) => Err(format!("Failed to generate hex grid PNG: {}", e)),
+        Err(e) => Err(TableauError::other("Failed to generate hex grid PNG", e)
+            .with_battlemap(battlemap.as_str())
+            .with_path(&output_path)),
     }
-}
\ No newline at end of file
+}
+
+#[tauri::command]
+fn get_corrupt_files(report: State<'_, CorruptFileReport>) -> TableauResult<Vec<CorruptFile>> {
+    Ok(report.lock().clone())
+}
+
+#[tauri::command]
+fn restore_last_good_copy(
+    path: String,
+    campaign: State<'_, CampaignState>,
+    report: State<'_, CorruptFileReport>,
+) -> TableauResult<String> {
+    let mut corrupt_files = report.lock();
+
+    // Only files reported by the startup scan can be restored
+    let index = corrupt_files
+        .iter()
+        .position(|corrupt_file| corrupt_file.path == path)
+        .ok_or_else(|| TableauError::validation(format!("'{}' is not a reported corrupt file.", path)).with_path(&path))?;
+
+    storage::restore_last_good(Path::new(&path))
+        .map_err(|e| TableauError::io(&format!("Failed to restore '{}'", path), e).with_path(&path))?;
+
+    campaign.store().evict(Path::new(&path));
+    corrupt_files.remove(index);
+    Ok(format!("Restored the last good copy of '{}'.", path))
+}
+
+#[tauri::command]
+fn get_current_campaign(campaign: State<'_, CampaignState>) -> TableauResult<String> {
+    Ok(campaign.root().path().display().to_string())
+}
+
+#[tauri::command]
+fn get_recent_campaigns(campaign: State<'_, CampaignState>) -> TableauResult<Vec<String>> {
+    Ok(campaign
+        .recent_campaigns()
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect())
+}
+
+#[tauri::command]
+fn create_campaign(
+    campaign: State<'_, CampaignState>,
+    report: State<'_, CorruptFileReport>,
+) -> TableauResult<String> {
+    // Open a folder dialog to choose where the new campaign lives
+    let path = utils::select_directory().ok_or_else(|| TableauError::cancelled("No folder was selected."))?;
+
+    let root = campaign
+        .create(&path)
+        .map_err(|e| TableauError::io("Failed to create campaign", e).with_path(&path))?;
+
+    replace_corrupt_file_report(&root, &report);
+    Ok(root.path().display().to_string())
+}
+
+#[tauri::command]
+fn open_campaign(
+    campaign: State<'_, CampaignState>,
+    report: State<'_, CorruptFileReport>,
+) -> TableauResult<String> {
+    // Open a folder dialog to choose an existing campaign
+    let path = utils::select_directory().ok_or_else(|| TableauError::cancelled("No folder was selected."))?;
+
+    let root = campaign
+        .open(&path)
+        .map_err(|e| TableauError::io("Failed to open campaign", e).with_path(&path))?;
+
+    replace_corrupt_file_report(&root, &report);
+    Ok(root.path().display().to_string())
+}
+
+#[tauri::command]
+fn switch_campaign(
+    path: String,
+    campaign: State<'_, CampaignState>,
+    report: State<'_, CorruptFileReport>,
+) -> TableauResult<String> {
+    // Only campaigns from the recent list can be switched to by path
+    let path = PathBuf::from(path);
+    if !campaign.recent_campaigns().contains(&path) {
+        return Err(TableauError::validation(format!("'{}' is not a recent campaign.", path.display())).with_path(&path));
+    }
+
+    let root = campaign
+        .open(&path)
+        .map_err(|e| TableauError::io("Failed to switch campaign", e).with_path(&path))?;
+
+    replace_corrupt_file_report(&root, &report);
+    Ok(root.path().display().to_string())
+}
+
+/// Rescans the newly opened campaign so the corrupt file report matches it
+fn replace_corrupt_file_report(root: &CampaignRoot, report: &CorruptFileReport) {
+    *report.lock() = scan_campaign_for_corrupt_files(root);
+}
+
+/// Reports asset and entity files no chapter, template, snapshot or undo step references, grouped by category with their sizes.
+/// Unless `dry_run` is set the reported files are deleted.
+#[tauri::command]
+fn collect_garbage(dry_run: bool, campaign: State<'_, CampaignState>) -> TableauResult<GcReport> {
+    let root = campaign.root();
+    gc::collect_garbage(campaign.store(), &root, campaign.journal(), campaign.pending_crops(), dry_run)
+        .map_err(|e| TableauError::io("Failed to collect unused files", e).with_path(root.path()))
+}
+
+/// Maps the stored (content-hashed) names of an asset directory to the names the files were imported with
+#[tauri::command]
+fn get_asset_names(kind: String, campaign: State<'_, CampaignState>) -> TableauResult<BTreeMap<String, String>> {
+    let kind = AssetKind::from_dir_name(&kind)
+        .ok_or_else(|| TableauError::validation(format!("'{}' is not an asset directory.", kind)))?;
+
+    let root = campaign.root();
+    let index = campaign
+        .store()
+        .read_asset_index(&root)
+        .map_err(|e| TableauError::io("Failed to read the asset index", e).with_path(root.path()))?;
+    Ok(index.original_names(kind))
+}
@@ -0,0 +1,391 @@
+// migrations.rs
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::fog::DEFAULT_VISION_RADIUS;
+use crate::hex::Hex;
+
+/// Current schema version written into every chapter file
+pub const CHAPTER_SCHEMA_VERSION: u32 = 5;
+
+/// Current schema version written into every entity file
+pub const ENTITY_SCHEMA_VERSION: u32 = 7;
+
+/// A single upgrade step that takes a file from `from` to `from + 1`
+struct Migration {
+    from: u32,
+    description: &'static str,
+    apply: fn(&mut Value) -> Result<(), String>,
+}
+
+/// Chapter migrations, in order. Append new steps here when the chapter shape changes.
+const CHAPTER_MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "add grid settings to combat objects",
+        apply: chapter_v0_to_v1,
+    },
+    Migration {
+        from: 1,
+        description: "add initiative to combat objects",
+        apply: chapter_v1_to_v2,
+    },
+    Migration {
+        from: 2,
+        description: "add terrain to combat objects",
+        apply: chapter_v2_to_v3,
+    },
+    Migration {
+        from: 3,
+        description: "add fog of war to combat objects",
+        apply: chapter_v3_to_v4,
+    },
+    Migration {
+        from: 4,
+        description: "add vision settings to fog of war",
+        apply: chapter_v4_to_v5,
+    },
+];
+
+/// Entity migrations, in order. Append new steps here when the entity shape changes.
+const ENTITY_MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "introduce schema_version",
+        apply: |_| Ok(()),
+    },
+    Migration {
+        from: 1,
+        description: "add name, combat stats, ability scores and DM notes",
+        apply: entity_v1_to_v2,
+    },
+    Migration {
+        from: 2,
+        description: "replace free-form modifiers with structured conditions",
+        apply: entity_v2_to_v3,
+    },
+    Migration {
+        from: 3,
+        description: "add player flag, dying state and damage resistances",
+        apply: entity_v3_to_v4,
+    },
+    Migration {
+        from: 4,
+        description: "link entities to bestiary templates",
+        apply: entity_v4_to_v5,
+    },
+    Migration {
+        from: 5,
+        description: "store locations as axial hex coordinates",
+        apply: entity_v5_to_v6,
+    },
+    Migration {
+        from: 6,
+        description: "add darkvision",
+        apply: entity_v6_to_v7,
+    },
+];
+
+/// Chapters written before `gridsize`/`gridoffset` existed get the defaults `create_combat` uses.
+fn chapter_v0_to_v1(chapter: &mut Value) -> Result<(), String> {
+    let Some(combat_array) = chapter.get_mut("combat").and_then(|c| c.as_array_mut()) else {
+        return Ok(());
+    };
+
+    for combat in combat_array.iter_mut().filter_map(|c| c.as_object_mut()) {
+        combat.entry("gridsize").or_insert(json!(100));
+        combat.entry("gridoffset").or_insert(json!({ "x": 0, "y": 0 }));
+    }
+
+    Ok(())
+}
+
+/// Combats created before the initiative tracker start without a turn order.
+fn chapter_v1_to_v2(chapter: &mut Value) -> Result<(), String> {
+    let Some(combat_array) = chapter.get_mut("combat").and_then(|c| c.as_array_mut()) else {
+        return Ok(());
+    };
+
+    for combat in combat_array.iter_mut().filter_map(|c| c.as_object_mut()) {
+        combat
+            .entry("initiative")
+            .or_insert(json!({ "order": [], "active": null, "round": 0 }));
+    }
+
+    Ok(())
+}
+
+/// Combats created before terrain existed start with a bare map.
+fn chapter_v2_to_v3(chapter: &mut Value) -> Result<(), String> {
+    let Some(combat_array) = chapter.get_mut("combat").and_then(|c| c.as_array_mut()) else {
+        return Ok(());
+    };
+
+    for combat in combat_array.iter_mut().filter_map(|c| c.as_object_mut()) {
+        combat.entry("terrain").or_insert(json!({ "tiles": [], "walls": [] }));
+    }
+
+    Ok(())
+}
+
+/// Combats created before fog of war existed start with it turned off.
+fn chapter_v3_to_v4(chapter: &mut Value) -> Result<(), String> {
+    let Some(combat_array) = chapter.get_mut("combat").and_then(|c| c.as_array_mut()) else {
+        return Ok(());
+    };
+
+    for combat in combat_array.iter_mut().filter_map(|c| c.as_object_mut()) {
+        combat.entry("fog").or_insert(json!({ "enabled": false, "revealed": [] }));
+    }
+
+    Ok(())
+}
+
+/// Fog of war from before vision is only revealed by hand, as it was.
+fn chapter_v4_to_v5(chapter: &mut Value) -> Result<(), String> {
+    let Some(combat_array) = chapter.get_mut("combat").and_then(|c| c.as_array_mut()) else {
+        return Ok(());
+    };
+
+    for fog in combat_array.iter_mut().filter_map(|c| c.get_mut("fog")).filter_map(|f| f.as_object_mut()) {
+        fog.entry("mode").or_insert(json!("manual"));
+        fog.entry("vision_radius").or_insert(json!(DEFAULT_VISION_RADIUS));
+    }
+
+    Ok(())
+}
+
+/// Entities written before the richer stat block get the defaults `create_entity` uses.
+fn entity_v1_to_v2(entity: &mut Value) -> Result<(), String> {
+    let entity = entity.as_object_mut().ok_or("entity is not an object")?;
+
+    entity.entry("name").or_insert(json!(""));
+    entity.entry("armor_class").or_insert(json!(10));
+    entity.entry("speed").or_insert(json!(30));
+    entity.entry("initiative_bonus").or_insert(json!(0));
+    entity.entry("abilities").or_insert(json!({
+        "strength": 10,
+        "dexterity": 10,
+        "constitution": 10,
+        "intelligence": 10,
+        "wisdom": 10,
+        "charisma": 10
+    }));
+    entity.entry("dm_notes").or_insert(json!(""));
+
+    if let Some(hitpoints) = entity.get_mut("hitpoints").and_then(|h| h.as_object_mut()) {
+        hitpoints.entry("temporary").or_insert(json!(0));
+    }
+
+    Ok(())
+}
+
+/// Condition names recognised when converting free-form modifiers
+const STANDARD_CONDITIONS: [&str; 15] = [
+    "blinded",
+    "charmed",
+    "deafened",
+    "exhaustion",
+    "frightened",
+    "grappled",
+    "incapacitated",
+    "invisible",
+    "paralyzed",
+    "petrified",
+    "poisoned",
+    "prone",
+    "restrained",
+    "stunned",
+    "unconscious",
+];
+
+/// Each comma or line separated entry of the old `modifiers` text becomes a condition without a duration.
+/// Entries naming a standard condition get that kind; anything else is kept as a custom condition.
+fn entity_v2_to_v3(entity: &mut Value) -> Result<(), String> {
+    let entity = entity.as_object_mut().ok_or("entity is not an object")?;
+
+    let modifiers = match entity.remove("modifiers") {
+        Some(Value::String(modifiers)) => modifiers,
+        _ => String::new(),
+    };
+
+    let conditions: Vec<Value> = modifiers
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|modifier| !modifier.is_empty())
+        .map(|modifier| {
+            let lowercase = modifier.to_lowercase();
+            let (kind, label) = match STANDARD_CONDITIONS.iter().find(|name| **name == lowercase) {
+                Some(name) => (*name, ""),
+                None => ("custom", modifier),
+            };
+            json!({
+                "kind": kind,
+                "label": label,
+                "duration": null,
+                "source": "",
+                "expires": "end_of_turn"
+            })
+        })
+        .collect();
+    entity.entry("conditions").or_insert(Value::Array(conditions));
+
+    Ok(())
+}
+
+/// Existing entities are non-player characters without resistances, vulnerabilities or immunities.
+fn entity_v3_to_v4(entity: &mut Value) -> Result<(), String> {
+    let entity = entity.as_object_mut().ok_or("entity is not an object")?;
+
+    entity.entry("player").or_insert(json!(false));
+    entity.entry("dying").or_insert(json!(false));
+    entity.entry("resistances").or_insert(json!([]));
+    entity.entry("vulnerabilities").or_insert(json!([]));
+    entity.entry("immunities").or_insert(json!([]));
+
+    Ok(())
+}
+
+/// Entities made before the bestiary are not instances of any template.
+fn entity_v4_to_v5(entity: &mut Value) -> Result<(), String> {
+    let entity = entity.as_object_mut().ok_or("entity is not an object")?;
+
+    entity.entry("template").or_insert(json!(null));
+
+    Ok(())
+}
+
+/// Locations used to be the column and row of the display's grid cells, with odd rows shifted half a hex right.
+fn entity_v5_to_v6(entity: &mut Value) -> Result<(), String> {
+    let entity = entity.as_object_mut().ok_or("entity is not an object")?;
+    let Some(location) = entity.get_mut("location") else {
+        return Ok(());
+    };
+
+    let coordinate = |name: &str| location.get(name).and_then(Value::as_f64).map(|value| value.round() as i32);
+    let (Some(column), Some(row)) = (coordinate("x"), coordinate("y")) else {
+        return Err(format!("location {} has no x and y", location));
+    };
+    let hex = Hex::from_offset(column, row);
+    *location = json!({ "q": hex.q, "r": hex.r });
+
+    Ok(())
+}
+
+/// Entities made before vision see no farther in the dark than in the light.
+fn entity_v6_to_v7(entity: &mut Value) -> Result<(), String> {
+    let entity = entity.as_object_mut().ok_or("entity is not an object")?;
+
+    entity.entry("darkvision").or_insert(json!(0));
+
+    Ok(())
+}
+
+pub fn current_chapter_version() -> u32 {
+    CHAPTER_SCHEMA_VERSION
+}
+
+pub fn current_entity_version() -> u32 {
+    ENTITY_SCHEMA_VERSION
+}
+
+/// Upgrades a parsed chapter file in place. Returns true if anything was migrated.
+pub fn migrate_chapter(value: &mut Value, path: &Path) -> io::Result<bool> {
+    migrate(value, path, CHAPTER_MIGRATIONS, CHAPTER_SCHEMA_VERSION, true)
+}
+
+/// Upgrades a parsed entity file in place. Returns true if anything was migrated.
+pub fn migrate_entity(value: &mut Value, path: &Path) -> io::Result<bool> {
+    migrate(value, path, ENTITY_MIGRATIONS, ENTITY_SCHEMA_VERSION, true)
+}
+
+/// Upgrades an entity stored inside another file, e.g. a snapshot. `path` is the containing file, which is
+/// not backed up. Returns true if anything was migrated.
+pub fn migrate_embedded_entity(value: &mut Value, path: &Path) -> io::Result<bool> {
+    migrate(value, path, ENTITY_MIGRATIONS, ENTITY_SCHEMA_VERSION, false)
+}
+
+fn migrate(value: &mut Value, path: &Path, migrations: &[Migration], target: u32, backup: bool) -> io::Result<bool> {
+    // Files written before versioning have no `schema_version` and count as version 0
+    let mut version = match value.get("schema_version") {
+        None => 0,
+        Some(v) => v.as_u64().map(|v| v as u32).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("'{}' has an invalid schema_version: {}", path.display(), v),
+            )
+        })?,
+    };
+
+    if version > target {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "'{}' uses schema version {} but this version of Tableau only supports up to {}.",
+                path.display(),
+                version,
+                target
+            ),
+        ));
+    }
+
+    if version == target {
+        return Ok(false);
+    }
+
+    if !value.is_object() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("'{}' does not contain a JSON object.", path.display()),
+        ));
+    }
+
+    // Keep the original around before touching anything
+    if backup {
+        backup_before_migration(path, version)?;
+    }
+
+    while version < target {
+        let migration = migrations.iter().find(|m| m.from == version).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("No migration registered from schema version {} for '{}'.", version, path.display()),
+            )
+        })?;
+
+        (migration.apply)(value).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Migration from version {} ({}) failed for '{}': {}",
+                    version,
+                    migration.description,
+                    path.display(),
+                    e
+                ),
+            )
+        })?;
+
+        version += 1;
+        value["schema_version"] = json!(version);
+        log::info!("Migrated '{}' to schema version {} ({})", path.display(), version, migration.description);
+    }
+
+    Ok(true)
+}
+
+/// Copies `path` to `<path>.v<version>.bak`, leaving an existing backup of that version untouched.
+fn backup_before_migration(path: &Path, version: u32) -> io::Result<PathBuf> {
+    let mut backup_name = path.as_os_str().to_owned();
+    backup_name.push(format!(".v{}.bak", version));
+    let backup_path = PathBuf::from(backup_name);
+
+    if !backup_path.exists() {
+        fs::copy(path, &backup_path)?;
+    }
+
+    Ok(backup_path)
+}
@@ -1,5 +1,13 @@
 // models.rs
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::fog::Fog;
+use crate::hex::Hex;
+use crate::migrations::{current_chapter_version, current_entity_version};
+use crate::terrain::Terrain;
 
 #[derive(Deserialize)]
 pub struct TransformStateObject {
@@ -21,20 +29,470 @@ pub struct Coordinates {
     pub y: f32,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Hitpoints {
     pub current: i32,
     pub max: i32,
+    pub temporary: i32,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Armor class of a newly created entity
+pub const DEFAULT_ARMOR_CLASS: i32 = 10;
+
+/// Walking speed of a newly created entity, in feet
+pub const DEFAULT_SPEED: u32 = 30;
+
+/// The six ability scores. Every score starts at the average of 10.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct AbilityScores {
+    pub strength: i32,
+    pub dexterity: i32,
+    pub constitution: i32,
+    pub intelligence: i32,
+    pub wisdom: i32,
+    pub charisma: i32,
+}
+
+impl Default for AbilityScores {
+    fn default() -> Self {
+        AbilityScores {
+            strength: 10,
+            dexterity: 10,
+            constitution: 10,
+            intelligence: 10,
+            wisdom: 10,
+            charisma: 10,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Entity {
+    #[serde(default = "current_entity_version")]
+    pub schema_version: u32,
+    /// Label shown on the token, e.g. "Goblin Archer". Empty for unnamed entities.
+    pub name: String,
     pub icon: String,
     pub allegiance: String,
     pub size: String,
-    pub location: Coordinates,
+    /// The hex the token stands on, so it stays put when the grid is resized or the map rescaled
+    pub location: Hex,
     pub hitpoints: Hitpoints,
+    pub armor_class: i32,
+    pub speed: u32,
+    /// Feet the entity sees in the dark, 0 without darkvision
+    pub darkvision: u32,
+    pub initiative_bonus: i32,
+    pub abilities: AbilityScores,
     pub visible: bool,
     pub dead: bool,
-    pub modifiers: String,
+    /// Controlled by a player. Player characters at 0 hitpoints are dying rather than dead.
+    pub player: bool,
+    /// A player character at 0 hitpoints who is not dead yet
+    pub dying: bool,
+    pub resistances: Vec<DamageType>,
+    pub vulnerabilities: Vec<DamageType>,
+    pub immunities: Vec<DamageType>,
+    pub conditions: Vec<Condition>,
+    /// Notes for the DM only; never shown on the player display
+    pub dm_notes: String,
+    /// Bestiary template the entity was made from, if any
+    pub template: Option<String>,
+}
+
+impl Entity {
+    /// A fresh entity at the origin with no hitpoints and average stats
+    pub fn new(icon: String, name: String, allegiance: String, size: String) -> Self {
+        Entity {
+            schema_version: current_entity_version(),
+            name,
+            icon,
+            allegiance,
+            size,
+            location: Hex::default(),
+            hitpoints: Hitpoints { current: 0, max: 0, temporary: 0 },
+            armor_class: DEFAULT_ARMOR_CLASS,
+            speed: DEFAULT_SPEED,
+            darkvision: 0,
+            initiative_bonus: 0,
+            abilities: AbilityScores::default(),
+            visible: true,
+            dead: false,
+            player: false,
+            dying: false,
+            resistances: Vec::new(),
+            vulnerabilities: Vec::new(),
+            immunities: Vec::new(),
+            conditions: Vec::new(),
+            dm_notes: String::new(),
+            template: None,
+        }
+    }
+}
+
+/// The fields of an entity the properties editor changes. Fields missing from the patch are left as they are,
+/// so an edit never overwrites hitpoints, conditions or a location changed since the editor loaded the entity.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntityPatch {
+    pub name: Option<String>,
+    pub allegiance: Option<String>,
+    pub size: Option<String>,
+    pub hitpoints: Option<HitpointsPatch>,
+    pub armor_class: Option<i32>,
+    pub speed: Option<u32>,
+    pub darkvision: Option<u32>,
+    pub initiative_bonus: Option<i32>,
+    pub abilities: Option<AbilityScores>,
+    pub visible: Option<bool>,
+    pub dead: Option<bool>,
+    pub player: Option<bool>,
+    pub resistances: Option<Vec<DamageType>>,
+    pub vulnerabilities: Option<Vec<DamageType>>,
+    pub immunities: Option<Vec<DamageType>>,
+    pub dm_notes: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HitpointsPatch {
+    pub current: Option<i32>,
+    pub max: Option<i32>,
+    pub temporary: Option<i32>,
+}
+
+impl EntityPatch {
+    /// Whether the patch changes what the entity sees or whether it counts as part of the party
+    pub fn affects_sight(&self, entity: &Entity) -> bool {
+        self.allegiance.as_ref().is_some_and(|allegiance| *allegiance != entity.allegiance)
+            || self.visible.is_some_and(|visible| visible != entity.visible)
+            || self.dead.is_some_and(|dead| dead != entity.dead)
+            || self.darkvision.is_some_and(|darkvision| darkvision != entity.darkvision)
+    }
+
+    pub fn apply(self, entity: &mut Entity) {
+        fn set<T>(field: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+
+        set(&mut entity.name, self.name);
+        set(&mut entity.allegiance, self.allegiance);
+        set(&mut entity.size, self.size);
+        if let Some(hitpoints) = self.hitpoints {
+            set(&mut entity.hitpoints.current, hitpoints.current);
+            set(&mut entity.hitpoints.max, hitpoints.max);
+            set(&mut entity.hitpoints.temporary, hitpoints.temporary);
+        }
+        set(&mut entity.armor_class, self.armor_class);
+        set(&mut entity.speed, self.speed);
+        set(&mut entity.darkvision, self.darkvision);
+        set(&mut entity.initiative_bonus, self.initiative_bonus);
+        set(&mut entity.abilities, self.abilities);
+        set(&mut entity.visible, self.visible);
+        set(&mut entity.dead, self.dead);
+        set(&mut entity.player, self.player);
+        set(&mut entity.resistances, self.resistances);
+        set(&mut entity.vulnerabilities, self.vulnerabilities);
+        set(&mut entity.immunities, self.immunities);
+        set(&mut entity.dm_notes, self.dm_notes);
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DamageType {
+    Acid,
+    Bludgeoning,
+    Cold,
+    Fire,
+    Force,
+    Lightning,
+    Necrotic,
+    Piercing,
+    Poison,
+    Psychic,
+    Radiant,
+    Slashing,
+    Thunder,
+}
+
+impl DamageType {
+    /// Parses a damage type by name, ignoring case
+    pub fn parse(name: &str) -> Result<DamageType, String> {
+        serde_json::from_value(Value::String(name.trim().to_lowercase()))
+            .map_err(|_| format!("'{}' is not a damage type.", name))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DamageType::Acid => "acid",
+            DamageType::Bludgeoning => "bludgeoning",
+            DamageType::Cold => "cold",
+            DamageType::Fire => "fire",
+            DamageType::Force => "force",
+            DamageType::Lightning => "lightning",
+            DamageType::Necrotic => "necrotic",
+            DamageType::Piercing => "piercing",
+            DamageType::Poison => "poison",
+            DamageType::Psychic => "psychic",
+            DamageType::Radiant => "radiant",
+            DamageType::Slashing => "slashing",
+            DamageType::Thunder => "thunder",
+        }
+    }
+}
+
+impl fmt::Display for DamageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The standard conditions, plus `Custom` for anything else
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionKind {
+    Blinded,
+    Charmed,
+    Deafened,
+    Exhaustion,
+    Frightened,
+    Grappled,
+    Incapacitated,
+    Invisible,
+    Paralyzed,
+    Petrified,
+    Poisoned,
+    Prone,
+    Restrained,
+    Stunned,
+    Unconscious,
+    Custom,
+}
+
+/// Whether a condition's duration counts down at the start or the end of the affected entity's turn
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryTrigger {
+    StartOfTurn,
+    EndOfTurn,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Condition {
+    pub kind: ConditionKind,
+    /// Name of a custom condition; empty for the standard ones
+    pub label: String,
+    /// Rounds left, or `None` for a condition that lasts until it is removed by hand
+    pub duration: Option<u32>,
+    /// What caused the condition, e.g. a spell or the entity that applied it
+    pub source: String,
+    pub expires: ExpiryTrigger,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct Offset {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Splash {
+    pub image: String,
+    #[serde(default = "default_splash_allegiance")]
+    pub allegiance: String,
+}
+
+fn default_splash_allegiance() -> String {
+    "neutral".to_string()
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Combat {
+    pub battlemap: String,
+    pub mapsize: u32,
+    pub mapoffset: Offset,
+    pub gridsize: u32,
+    pub gridoffset: Offset,
+    pub entities: Vec<String>,
+    pub initiative: Initiative,
+    /// Missing from snapshots taken before terrain existed
+    #[serde(default)]
+    pub terrain: Terrain,
+    /// Missing from snapshots taken before fog of war existed
+    #[serde(default)]
+    pub fog: Fog,
+}
+
+/// Turn order of a combat. `round` is 0 until initiative has been rolled or set.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Initiative {
+    /// Sorted from first to last to act
+    pub order: Vec<InitiativeEntry>,
+    /// Entity file (`<id>.json`) whose turn it is
+    pub active: Option<String>,
+    pub round: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct InitiativeEntry {
+    pub entity: String,
+    pub initiative: i32,
+    /// The entity's initiative bonus, used to break ties
+    pub bonus: i32,
+    /// Set while the entity has delayed its turn; its turns are skipped until it resumes
+    pub delayed: bool,
+    /// Breaks ties on `initiative` ahead of `bonus`, higher first. Resuming from a delay sets it so the entity
+    /// stays right before the one it interrupted. Missing from chapters saved before resuming kept its place.
+    #[serde(default)]
+    pub tiebreak: i32,
+}
+
+impl Combat {
+    /// A freshly created combat with the default map and grid settings
+    pub fn new(battlemap: String) -> Self {
+        Combat {
+            battlemap,
+            mapsize: 100,
+            mapoffset: Offset::default(),
+            gridsize: 100,
+            gridoffset: Offset::default(),
+            entities: Vec::new(),
+            initiative: Initiative::default(),
+            terrain: Terrain::default(),
+            fog: Fog::default(),
+        }
+    }
+}
+
+/// Largest map or grid size a combat accepts
+const MAX_COMBAT_SIZE: u32 = 1000;
+
+/// A partial update of a combat's map and grid settings, read from a JSON merge patch.
+/// Fields missing from the patch are left as they are, and an offset may set just `x` or `y`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CombatPatch {
+    pub mapsize: Option<u32>,
+    pub mapoffset: OffsetPatch,
+    pub gridsize: Option<u32>,
+    pub gridoffset: OffsetPatch,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OffsetPatch {
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+}
+
+impl CombatPatch {
+    /// Parses and validates a merge patch. Settings cannot be removed, so `null` is rejected
+    /// along with unknown fields and out of range values.
+    pub fn from_json(patch: &Value) -> Result<Self, String> {
+        let fields = patch
+            .as_object()
+            .ok_or_else(|| "A combat patch must be a JSON object.".to_string())?;
+
+        let mut parsed = CombatPatch::default();
+        for (field, value) in fields {
+            match field.as_str() {
+                "mapsize" => parsed.mapsize = Some(parse_size(field, value, 0)?),
+                "gridsize" => parsed.gridsize = Some(parse_size(field, value, 1)?),
+                "mapoffset" => parsed.mapoffset = OffsetPatch::from_json(field, value)?,
+                "gridoffset" => parsed.gridoffset = OffsetPatch::from_json(field, value)?,
+                _ => return Err(format!("'{}' is not a combat setting that can be patched.", field)),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn apply(&self, combat: &mut Combat) {
+        if let Some(mapsize) = self.mapsize {
+            combat.mapsize = mapsize;
+        }
+        self.mapoffset.apply(&mut combat.mapoffset);
+        if let Some(gridsize) = self.gridsize {
+            combat.gridsize = gridsize;
+        }
+        self.gridoffset.apply(&mut combat.gridoffset);
+    }
+}
+
+impl OffsetPatch {
+    fn from_json(field: &str, patch: &Value) -> Result<Self, String> {
+        let fields = patch
+            .as_object()
+            .ok_or_else(|| format!("'{}' must be an object with 'x' and/or 'y'.", field))?;
+
+        let mut parsed = OffsetPatch::default();
+        for (axis, value) in fields {
+            let name = format!("{}.{}", field, axis);
+            let offset = value
+                .as_i64()
+                .and_then(|v| i32::try_from(v).ok())
+                .ok_or_else(|| format!("'{}' must be a whole number, got {}.", name, value))?;
+
+            match axis.as_str() {
+                "x" => parsed.x = Some(offset),
+                "y" => parsed.y = Some(offset),
+                _ => return Err(format!("'{}' is not a combat setting that can be patched.", name)),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn apply(&self, offset: &mut Offset) {
+        if let Some(x) = self.x {
+            offset.x = x;
+        }
+        if let Some(y) = self.y {
+            offset.y = y;
+        }
+    }
+}
+
+fn parse_size(field: &str, value: &Value, min: u32) -> Result<u32, String> {
+    value
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|v| (min..=MAX_COMBAT_SIZE).contains(v))
+        .ok_or_else(|| {
+            format!(
+                "'{}' must be a whole number between {} and {}, got {}.",
+                field, min, MAX_COMBAT_SIZE, value
+            )
+        })
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Chapter {
+    #[serde(default = "current_chapter_version")]
+    pub schema_version: u32,
+    pub combat: Vec<Combat>,
+    pub landscapes: Vec<String>,
+    pub splashes: Vec<Splash>,
+}
+
+impl Default for Chapter {
+    fn default() -> Self {
+        Chapter {
+            schema_version: current_chapter_version(),
+            combat: Vec::new(),
+            landscapes: Vec::new(),
+            splashes: Vec::new(),
+        }
+    }
+}
+
+impl Chapter {
+    pub fn find_combat(&self, battlemap: &str) -> Option<&Combat> {
+        self.combat.iter().find(|combat| combat.battlemap == battlemap)
+    }
+
+    pub fn find_combat_mut(&mut self, battlemap: &str) -> Option<&mut Combat> {
+        self.combat.iter_mut().find(|combat| combat.battlemap == battlemap)
+    }
 }
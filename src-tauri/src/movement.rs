@@ -0,0 +1,300 @@
+// movement.rs
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hex::{Hex, Layout};
+use crate::models::Entity;
+use crate::terrain::{Terrain, Wall};
+
+/// Feet of movement one hex takes
+pub const FEET_PER_HEX: u32 = 5;
+
+/// Hexes marked by hand, on top of the combat's terrain and what its entities block
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Obstacles {
+    #[serde(default)]
+    pub blocked: Vec<Hex>,
+    /// Hexes that take twice the movement to enter
+    #[serde(default)]
+    pub difficult: Vec<Hex>,
+}
+
+/// Everything that decides where an entity can move
+pub struct MovementMap {
+    layout: Layout,
+    blocked: HashSet<Hex>,
+    difficult: HashSet<Hex>,
+    walls: HashSet<Wall>,
+    /// Hexes of allies: they can be moved through, but not stopped on
+    occupied: HashSet<Hex>,
+}
+
+impl MovementMap {
+    /// The map for `mover`: hexes of hostile entities block it, those of its allies can only be passed through
+    pub fn new<'a>(
+        layout: Layout,
+        terrain: &Terrain,
+        mover: &Entity,
+        others: impl IntoIterator<Item = &'a Entity>,
+        obstacles: Obstacles,
+    ) -> MovementMap {
+        let mut map = MovementMap {
+            layout,
+            blocked: obstacles.blocked.into_iter().collect(),
+            difficult: obstacles.difficult.into_iter().collect(),
+            walls: terrain.walls.iter().copied().collect(),
+            occupied: HashSet::new(),
+        };
+        for tile in &terrain.tiles {
+            match tile.kind.movement_cost() {
+                None => {
+                    map.blocked.insert(tile.hex);
+                }
+                Some(cost) if cost > 1 => {
+                    map.difficult.insert(tile.hex);
+                }
+                Some(_) => {}
+            }
+        }
+        for other in others {
+            if hostile(mover, other) {
+                map.blocked.insert(other.location);
+            } else {
+                map.occupied.insert(other.location);
+            }
+        }
+        map
+    }
+
+    fn passable(&self, hex: Hex) -> bool {
+        !self.blocked.contains(&hex) && self.layout.on_display(hex)
+    }
+
+    /// Movement it takes to step from one hex into a neighboring one, None if it can't be entered
+    fn step_cost(&self, from: Hex, to: Hex) -> Option<u32> {
+        if !self.passable(to) || Wall::between(from, to).is_ok_and(|wall| self.walls.contains(&wall)) {
+            return None;
+        }
+        Some(if self.difficult.contains(&to) { 2 } else { 1 })
+    }
+
+    fn can_stop(&self, hex: Hex) -> bool {
+        !self.occupied.contains(&hex)
+    }
+}
+
+/// Entities of opposite sides get in each other's way
+fn hostile(mover: &Entity, other: &Entity) -> bool {
+    (mover.allegiance == "evil") != (other.allegiance == "evil")
+}
+
+/// A hex an entity can end its move on, with the hexes of movement it takes to get there
+#[derive(Debug, Clone, Serialize)]
+pub struct ReachableHex {
+    pub hex: Hex,
+    pub cost: u32,
+}
+
+/// The cheapest way between two hexes. `cost` is in hexes of movement; `hexes` includes both ends.
+#[derive(Debug, Clone, Serialize)]
+pub struct MovePath {
+    pub hexes: Vec<Hex>,
+    pub cost: u32,
+    pub feet: u32,
+}
+
+/// Every hex `start` can be left for with at most `budget` hexes of movement, cheapest first.
+/// The start itself is not included.
+pub fn reachable(map: &MovementMap, start: Hex, budget: u32) -> Vec<ReachableHex> {
+    let mut costs = HashMap::from([(start, 0)]);
+    let mut queue = BinaryHeap::from([Reverse((0, start))]);
+    while let Some(Reverse((cost, hex))) = queue.pop() {
+        if costs.get(&hex).is_some_and(|&best| best < cost) {
+            continue;
+        }
+        for neighbor in hex.neighbors() {
+            let Some(step) = map.step_cost(hex, neighbor) else {
+                continue;
+            };
+            let next = cost + step;
+            if next <= budget && costs.get(&neighbor).map_or(true, |&best| next < best) {
+                costs.insert(neighbor, next);
+                queue.push(Reverse((next, neighbor)));
+            }
+        }
+    }
+
+    let mut reachable: Vec<ReachableHex> = costs
+        .into_iter()
+        .filter(|&(hex, _)| hex != start && map.can_stop(hex))
+        .map(|(hex, cost)| ReachableHex { hex, cost })
+        .collect();
+    reachable.sort_by_key(|reachable| (reachable.cost, reachable.hex));
+    reachable
+}
+
+/// The cheapest path from `start` to `goal` (A*), None if the goal can't be reached or stopped on
+pub fn find_path(map: &MovementMap, start: Hex, goal: Hex) -> Option<MovePath> {
+    if start == goal {
+        return Some(MovePath { hexes: vec![start], cost: 0, feet: 0 });
+    }
+    if !map.can_stop(goal) || !map.passable(goal) {
+        return None;
+    }
+
+    // Every step costs at least 1, so the distance never overestimates
+    let mut costs = HashMap::from([(start, 0)]);
+    let mut came_from: HashMap<Hex, Hex> = HashMap::new();
+    let mut queue = BinaryHeap::from([Reverse((start.distance(goal), 0, start))]);
+    while let Some(Reverse((_, cost, hex))) = queue.pop() {
+        if hex == goal {
+            let mut hexes = vec![goal];
+            let mut current = goal;
+            while let Some(&previous) = came_from.get(&current) {
+                hexes.push(previous);
+                current = previous;
+            }
+            hexes.reverse();
+            return Some(MovePath { hexes, cost, feet: cost * FEET_PER_HEX });
+        }
+        if costs.get(&hex).is_some_and(|&best| best < cost) {
+            continue;
+        }
+        for neighbor in hex.neighbors() {
+            let Some(step) = map.step_cost(hex, neighbor) else {
+                continue;
+            };
+            let next = cost + step;
+            if costs.get(&neighbor).map_or(true, |&best| next < best) {
+                costs.insert(neighbor, next);
+                came_from.insert(neighbor, hex);
+                queue.push(Reverse((next + neighbor.distance(goal), next, neighbor)));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Coordinates;
+    use crate::terrain::{TerrainKind, TerrainTile};
+
+    /// A layout with plenty of room on the display around hex (0, 0)
+    fn layout() -> Layout {
+        Layout::new(20.0, Coordinates { x: 800.0, y: 450.0 })
+    }
+
+    fn entity(allegiance: &str, location: Hex) -> Entity {
+        let mut entity = Entity::new("icon".to_string(), String::new(), allegiance.to_string(), "medium".to_string());
+        entity.location = location;
+        entity
+    }
+
+    fn map(terrain: &Terrain, others: &[Entity], obstacles: Obstacles) -> MovementMap {
+        MovementMap::new(layout(), terrain, &entity("ally", Hex::new(0, 0)), others, obstacles)
+    }
+
+    fn open_map() -> MovementMap {
+        map(&Terrain::default(), &[], Obstacles::default())
+    }
+
+    /// Every step of the path goes to a neighbor
+    fn assert_connected(path: &MovePath) {
+        assert!(path.hexes.windows(2).all(|step| step[0].distance(step[1]) == 1));
+    }
+
+    #[test]
+    fn straight_path_on_open_ground() {
+        let path = find_path(&open_map(), Hex::new(0, 0), Hex::new(3, 0)).unwrap();
+        assert_eq!(path.hexes, vec![Hex::new(0, 0), Hex::new(1, 0), Hex::new(2, 0), Hex::new(3, 0)]);
+        assert_eq!((path.cost, path.feet), (3, 15));
+
+        let path = find_path(&open_map(), Hex::new(2, -1), Hex::new(2, -1)).unwrap();
+        assert_eq!((path.hexes, path.cost), (vec![Hex::new(2, -1)], 0));
+    }
+
+    #[test]
+    fn goes_around_blocked_hexes() {
+        let obstacles = Obstacles {
+            blocked: vec![Hex::new(1, -1), Hex::new(1, 0), Hex::new(0, 1)],
+            difficult: Vec::new(),
+        };
+        let path = find_path(&map(&Terrain::default(), &[], obstacles), Hex::new(0, 0), Hex::new(2, 0)).unwrap();
+        assert_connected(&path);
+        assert_eq!(path.cost, 5);
+        assert!(!path.hexes.contains(&Hex::new(1, 0)));
+    }
+
+    #[test]
+    fn prefers_a_longer_way_around_difficult_terrain() {
+        let terrain = Terrain {
+            tiles: [Hex::new(1, 0), Hex::new(2, 0)]
+                .map(|hex| TerrainTile { hex, kind: TerrainKind::Water })
+                .to_vec(),
+            walls: Vec::new(),
+        };
+        let path = find_path(&map(&terrain, &[], Obstacles::default()), Hex::new(0, 0), Hex::new(3, 0)).unwrap();
+        assert_connected(&path);
+        assert_eq!(path.cost, 4);
+        assert!(!path.hexes.contains(&Hex::new(1, 0)) && !path.hexes.contains(&Hex::new(2, 0)));
+
+        // Entering a difficult hex takes two hexes of movement
+        let path = find_path(&map(&terrain, &[], Obstacles::default()), Hex::new(0, 0), Hex::new(1, 0)).unwrap();
+        assert_eq!((path.cost, path.feet), (2, 10));
+    }
+
+    #[test]
+    fn walls_block_movement_between_hexes() {
+        let terrain = Terrain {
+            tiles: Vec::new(),
+            walls: vec![Wall::between(Hex::new(0, 0), Hex::new(1, 0)).unwrap()],
+        };
+        let path = find_path(&map(&terrain, &[], Obstacles::default()), Hex::new(0, 0), Hex::new(1, 0)).unwrap();
+        assert_connected(&path);
+        assert_eq!(path.cost, 2);
+    }
+
+    #[test]
+    fn hostile_entities_block_and_allies_can_only_be_passed() {
+        let hostile = [entity("evil", Hex::new(1, 0))];
+        let movement = map(&Terrain::default(), &hostile, Obstacles::default());
+        let path = find_path(&movement, Hex::new(0, 0), Hex::new(2, 0)).unwrap();
+        assert_eq!(path.cost, 3);
+        assert!(!path.hexes.contains(&Hex::new(1, 0)));
+
+        let ally = [entity("ally", Hex::new(1, 0))];
+        let movement = map(&Terrain::default(), &ally, Obstacles::default());
+        let path = find_path(&movement, Hex::new(0, 0), Hex::new(2, 0)).unwrap();
+        assert_eq!(path.hexes, vec![Hex::new(0, 0), Hex::new(1, 0), Hex::new(2, 0)]);
+        assert!(find_path(&movement, Hex::new(0, 0), Hex::new(1, 0)).is_none());
+        assert!(!reachable(&movement, Hex::new(0, 0), 1).iter().any(|reachable| reachable.hex == Hex::new(1, 0)));
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_path() {
+        let goal = Hex::new(3, 0);
+        let obstacles = Obstacles { blocked: goal.neighbors().to_vec(), difficult: Vec::new() };
+        assert!(find_path(&map(&Terrain::default(), &[], obstacles), Hex::new(0, 0), goal).is_none());
+
+        let blocked = Obstacles { blocked: vec![goal], difficult: Vec::new() };
+        assert!(find_path(&map(&Terrain::default(), &[], blocked), Hex::new(0, 0), goal).is_none());
+
+        assert!(find_path(&open_map(), Hex::new(0, 0), Hex::new(100, 0)).is_none());
+    }
+
+    #[test]
+    fn reachable_stays_within_the_budget() {
+        let reachable_hexes = reachable(&open_map(), Hex::new(0, 0), 2);
+        assert_eq!(reachable_hexes.len(), 18);
+        assert!(reachable_hexes.iter().all(|reachable| reachable.cost == reachable.hex.distance(Hex::new(0, 0))));
+        assert!(reachable_hexes.windows(2).all(|pair| pair[0].cost <= pair[1].cost));
+
+        let obstacles = Obstacles { blocked: Vec::new(), difficult: Hex::new(0, 0).neighbors().to_vec() };
+        let reachable_hexes = reachable(&map(&Terrain::default(), &[], obstacles), Hex::new(0, 0), 1);
+        assert!(reachable_hexes.is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+// refs.rs
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::campaign::{AssetKind, CampaignRoot};
+
+/// A file name inside one of the campaign's asset directories, as received from the frontend.
+/// Only a single plain file name is accepted, so joining it onto a directory can't leave that directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssetRef(String);
+
+impl AssetRef {
+    pub fn parse(name: &str) -> io::Result<AssetRef> {
+        validate_file_name(name)?;
+        Ok(AssetRef(name.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn path(&self, root: &CampaignRoot, kind: AssetKind) -> PathBuf {
+        root.assets_dir(kind).join(&self.0)
+    }
+}
+
+impl fmt::Display for AssetRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A chapter's ID, as in `chapters/chapter_<id>.json`. IDs are lowercased, so `Dungeon` and `dungeon` name the same chapter.
+/// Per-chapter directories (logs, snapshots, fog masks) are named after the ID, so it must be a plain file name too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChapterId(String);
+
+impl ChapterId {
+    pub fn parse(id: &str) -> io::Result<ChapterId> {
+        validate_file_name(id).map_err(|_| invalid_name(id, "is not a valid chapter ID"))?;
+        Ok(ChapterId(id.to_lowercase()))
+    }
+
+    /// The ID of a chapter given by the name `get_chapters` lists it under, e.g. `Chapter 1`
+    pub fn from_display_name(name: &str) -> io::Result<ChapterId> {
+        let formatted = name.to_lowercase().replace(' ', "_");
+        ChapterId::parse(formatted.strip_prefix("chapter_").unwrap_or(&formatted))
+    }
+
+    /// The chapter a file in the chapters directory holds, None if it is not a chapter file
+    pub fn from_file_name(file_name: &str) -> Option<ChapterId> {
+        let id = file_name.strip_prefix("chapter_")?.strip_suffix(".json")?;
+        ChapterId::parse(id).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn file_path(&self, root: &CampaignRoot) -> PathBuf {
+        root.chapters_dir().join(format!("chapter_{}.json", self.0))
+    }
+}
+
+impl AsRef<str> for ChapterId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ChapterId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The ID an entity is stored under, as `entities/<id>.json` and `assets/entities/<id>.png`.
+/// IDs are generated by `utils::generate_icon_id` and only ever contain letters, digits, `-` and `_`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntityId(String);
+
+impl EntityId {
+    pub fn parse(id: &str) -> io::Result<EntityId> {
+        let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(invalid_name(id, "is not a valid entity ID"));
+        }
+        Ok(EntityId(id.to_string()))
+    }
+
+    /// Accepts either file an entity is stored as (`<id>.json` or `<id>.png`) as well as the bare ID
+    pub fn from_filename(filename: &str) -> io::Result<EntityId> {
+        let id = filename
+            .strip_suffix(".json")
+            .or_else(|| filename.strip_suffix(".png"))
+            .unwrap_or(filename);
+        EntityId::parse(id)
+    }
+
+    pub fn json_filename(&self) -> String {
+        format!("{}.json", self.0)
+    }
+
+    pub fn icon_filename(&self) -> String {
+        format!("{}.png", self.0)
+    }
+
+    pub fn json_path(&self, root: &CampaignRoot) -> PathBuf {
+        root.entities_dir().join(self.json_filename())
+    }
+
+    pub fn icon_path(&self, root: &CampaignRoot) -> PathBuf {
+        root.assets_dir(AssetKind::Entities).join(self.icon_filename())
+    }
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Rejects anything but one normal path component: separators, `.`, `..`, drive prefixes and absolute paths
+fn validate_file_name(name: &str) -> io::Result<()> {
+    if name.contains(['/', '\\', ':', '\0']) {
+        return Err(invalid_name(name, "must be a plain file name"));
+    }
+
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(invalid_name(name, "must be a plain file name")),
+    }
+}
+
+fn invalid_name(name: &str, reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' {}.", name, reason))
+}
@@ -0,0 +1,251 @@
+// snapshots.rs
+use std::fs;
+use std::io;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::campaign::CampaignRoot;
+use crate::migrations;
+use crate::models::{Combat, Entity};
+use crate::refs::{AssetRef, ChapterId, EntityId};
+use crate::storage;
+use crate::store::CampaignStore;
+use crate::utils::now_millis;
+
+/// A combat's full state at one point in time: map and grid settings, turn order and every entity file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    /// Milliseconds since the Unix epoch
+    pub created: u64,
+    pub combat: Combat,
+    pub entities: Vec<Entity>,
+}
+
+/// What `list_snapshots` shows of a snapshot
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub created: u64,
+    pub entity_count: usize,
+    pub round: u32,
+}
+
+impl From<&Snapshot> for SnapshotInfo {
+    fn from(snapshot: &Snapshot) -> Self {
+        SnapshotInfo {
+            name: snapshot.name.clone(),
+            created: snapshot.created,
+            entity_count: snapshot.entities.len(),
+            round: snapshot.combat.initiative.round,
+        }
+    }
+}
+
+/// Every snapshot of one combat, oldest first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CombatSnapshots {
+    pub snapshots: Vec<Snapshot>,
+}
+
+/// Snapshots live at `snapshots/<chapter id>/<battlemap>.json`. Entity icons are copied to
+/// `snapshots/<chapter id>/icons`, so a snapshot can bring back entities whose files were deleted since.
+pub fn chapter_snapshots_dir(root: &CampaignRoot, chapter_id: &ChapterId) -> PathBuf {
+    root.snapshots_dir().join(chapter_id.as_str())
+}
+
+pub fn snapshots_path(root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> io::Result<PathBuf> {
+    let battlemap = AssetRef::parse(battlemap)?;
+    Ok(chapter_snapshots_dir(root, chapter_id).join(format!("{}.json", battlemap)))
+}
+
+pub fn icons_dir(root: &CampaignRoot, chapter_id: &ChapterId) -> PathBuf {
+    chapter_snapshots_dir(root, chapter_id).join("icons")
+}
+
+/// Where `take` copies an entity's icon to
+pub fn icon_copy_path(root: &CampaignRoot, chapter_id: &ChapterId, entity_id: &EntityId) -> PathBuf {
+    icons_dir(root, chapter_id).join(entity_id.icon_filename())
+}
+
+impl CombatSnapshots {
+    pub fn load(root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> io::Result<CombatSnapshots> {
+        Self::load_file(&snapshots_path(root, chapter_id, battlemap)?)
+    }
+
+    fn load_file(path: &Path) -> io::Result<CombatSnapshots> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(CombatSnapshots::default()),
+            Err(e) => return Err(e),
+        };
+
+        let invalid = |e: serde_json::Error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Snapshot file '{}' is invalid: {}", path.display(), e),
+            )
+        };
+        let mut value: Value = serde_json::from_str(&content).map_err(invalid)?;
+
+        // Snapshots keep entities as they were when taken, so bring them up to the current schema
+        let entities = value["snapshots"]
+            .as_array_mut()
+            .into_iter()
+            .flatten()
+            .filter_map(|snapshot| snapshot["entities"].as_array_mut())
+            .flatten();
+        for entity in entities {
+            migrations::migrate_embedded_entity(entity, path)?;
+        }
+
+        serde_json::from_value(value).map_err(invalid)
+    }
+
+    pub fn save(&self, root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> io::Result<()> {
+        let path = snapshots_path(root, chapter_id, battlemap)?;
+        fs::create_dir_all(chapter_snapshots_dir(root, chapter_id))?;
+        storage::write_json_atomic(&path, self)
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Snapshot> {
+        self.snapshots.iter().find(|snapshot| snapshot.name == name)
+    }
+
+    /// Deletes every snapshot of a combat, if it has any
+    pub fn remove(root: &CampaignRoot, chapter_id: &ChapterId, battlemap: &str) -> io::Result<()> {
+        match fs::remove_file(snapshots_path(root, chapter_id, battlemap)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Deletes the snapshots of every combat in a chapter, including their icons
+    pub fn remove_chapter(root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<()> {
+        match fs::remove_dir_all(chapter_snapshots_dir(root, chapter_id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Every chapter that has a snapshot directory
+pub fn chapters_with_snapshots(root: &CampaignRoot) -> io::Result<Vec<ChapterId>> {
+    let entries = match fs::read_dir(root.snapshots_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut chapters = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if let (true, Some(name)) = (entry.file_type()?.is_dir(), entry.file_name().to_str()) {
+            chapters.push(ChapterId::parse(name)?);
+        }
+    }
+    Ok(chapters)
+}
+
+/// The icon copies the chapter's snapshots use
+pub fn referenced_icons(root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<HashSet<PathBuf>> {
+    let entries = match fs::read_dir(chapter_snapshots_dir(root, chapter_id)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut referenced = HashSet::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue;
+        }
+        for snapshot in CombatSnapshots::load_file(&path)?.snapshots {
+            for entity in &snapshot.entities {
+                referenced.insert(icon_copy_path(root, chapter_id, &EntityId::from_filename(&entity.icon)?));
+            }
+        }
+    }
+    Ok(referenced)
+}
+
+/// The icon copies in the chapter's snapshot icons that none of the chapter's snapshots uses any more
+pub fn unreferenced_icons(root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<Vec<PathBuf>> {
+    let icons = match fs::read_dir(icons_dir(root, chapter_id)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let referenced = referenced_icons(root, chapter_id)?;
+    let mut unreferenced = Vec::new();
+    for entry in icons {
+        let path = entry?.path();
+        if !referenced.contains(&path) {
+            unreferenced.push(path);
+        }
+    }
+    Ok(unreferenced)
+}
+
+/// Reads the combat's entity files and copies their icons into the chapter's snapshot icons.
+/// Entities listed in the combat whose file is already gone are left out.
+pub fn take(
+    store: &CampaignStore,
+    root: &CampaignRoot,
+    chapter_id: &ChapterId,
+    name: &str,
+    combat: &Combat,
+) -> io::Result<Snapshot> {
+    let icons = icons_dir(root, chapter_id);
+    fs::create_dir_all(&icons)?;
+
+    let mut entities = Vec::new();
+    for filename in &combat.entities {
+        let entity_id = EntityId::from_filename(filename)?;
+        match store.read_entity(root, &entity_id) {
+            Ok(entity) => entities.push(entity),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::warn!("Entity '{}' is missing and was left out of snapshot '{}'", filename, name);
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+
+        // Icons never change once generated, so an existing copy is up to date
+        let icon = entity_id.icon_path(root);
+        let copy = icon_copy_path(root, chapter_id, &entity_id);
+        if icon.exists() && !copy.exists() {
+            fs::copy(&icon, &copy)?;
+        }
+    }
+
+    Ok(Snapshot {
+        name: name.to_string(),
+        created: now_millis(),
+        combat: combat.clone(),
+        entities,
+    })
+}
+
+/// Writes the snapshot's entity files back and brings back icons that were deleted since it was taken
+pub fn restore_entities(store: &CampaignStore, root: &CampaignRoot, chapter_id: &ChapterId, snapshot: &Snapshot) -> io::Result<()> {
+    for entity in &snapshot.entities {
+        let entity_id = EntityId::from_filename(&entity.icon)?;
+
+        let icon = entity_id.icon_path(root);
+        let copy = icon_copy_path(root, chapter_id, &entity_id);
+        if !icon.exists() && copy.exists() {
+            fs::copy(&copy, &icon)?;
+        }
+
+        store.write_entity(root, &entity_id, entity.clone())?;
+    }
+
+    Ok(())
+}
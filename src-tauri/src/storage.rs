@@ -0,0 +1,259 @@
+// storage.rs
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// Suffix of the copy of a JSON file as it was before its most recent write
+const LAST_GOOD_SUFFIX: &str = ".last-good";
+
+/// Suffix of the temporary file a write goes through before being renamed into place
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// Suffix a corrupt file is moved to when its last good copy is restored
+const CORRUPT_SUFFIX: &str = ".corrupt";
+
+/// A campaign file that failed to parse when the app started
+#[derive(Debug, Serialize, Clone)]
+pub struct CorruptFile {
+    pub path: String,
+    pub error: String,
+    pub fallback_available: bool,
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name: OsString = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub fn last_good_path(path: &Path) -> PathBuf {
+    with_suffix(path, LAST_GOOD_SUFFIX)
+}
+
+/// Writes `contents` to `path` so that the file is either fully old or fully new.
+/// The data goes to a temporary file in the same directory, is fsynced, then renamed over `path`.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(parent)?;
+
+    let temp_path = with_suffix(path, TEMP_SUFFIX);
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&temp_path, path)?;
+        sync_directory(parent)
+    })();
+
+    // Never leave a half-written temporary file behind
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// Serializes `value` as pretty JSON and writes it atomically.
+/// If the current file is valid JSON it is kept as the last good copy first.
+pub fn write_json_atomic<T: Serialize + ?Sized>(path: &Path, value: &T) -> io::Result<()> {
+    let content = serde_json::to_string_pretty(value)?;
+
+    if is_valid_json(path) {
+        write_atomic(&last_good_path(path), &fs::read(path)?)?;
+    }
+
+    write_atomic(path, content.as_bytes())
+}
+
+/// Appends `value` as one line of a JSON Lines file. Earlier lines are never rewritten, except for a torn
+/// last line left by an append that was cut short, which is cut off so it can't run into the new line.
+pub fn append_json_line<T: Serialize + ?Sized>(path: &Path, value: &T) -> io::Result<()> {
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+    if file.metadata()?.len() > 0 {
+        let mut last = [0u8];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            let mut content = Vec::new();
+            file.seek(SeekFrom::Start(0))?;
+            file.read_to_end(&mut content)?;
+            let end = content.iter().rposition(|&byte| byte == b'\n').map_or(0, |index| index + 1);
+            log::warn!("Cutting off the torn last line of '{}'", path.display());
+            file.set_len(end as u64)?;
+        }
+    }
+
+    // A single write per line, so concurrent appends don't interleave within a line
+    file.write_all(line.as_bytes())
+}
+
+/// Reads a JSON Lines file with `parse`, first line first. A missing file has no lines. A last line that fails
+/// to parse is the torn end of an append cut short by a crash; it is skipped with a warning instead of making
+/// the whole file unreadable.
+pub fn read_json_lines<T>(path: &Path, mut parse: impl FnMut(&str) -> serde_json::Result<T>) -> io::Result<Vec<T>> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    // A torn line may end in the middle of a character
+    let content = String::from_utf8_lossy(&content);
+    let lines: Vec<(usize, &str)> = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).collect();
+
+    let mut values = Vec::with_capacity(lines.len());
+    for (position, (index, line)) in lines.iter().enumerate() {
+        match parse(line) {
+            Ok(value) => values.push(value),
+            Err(e) if position + 1 == lines.len() => {
+                log::warn!("Skipping the torn last line of '{}': {}", path.display(), e);
+            }
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("'{}' is invalid at line {}: {}", path.display(), index + 1, e),
+                ))
+            }
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(unix)]
+fn sync_directory(dir: &Path) -> io::Result<()> {
+    // Persist the rename itself, not just the file contents
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_directory(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn is_valid_json(path: &Path) -> bool {
+    match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice::<serde::de::IgnoredAny>(&bytes).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Checks every JSON file in the given directories, removing temporary files left by an
+/// interrupted write and reporting any file that no longer parses.
+pub fn scan_for_corrupt_files(directories: &[PathBuf]) -> Vec<CorruptFile> {
+    let mut corrupt_files = Vec::new();
+
+    for directory in directories {
+        let Ok(entries) = fs::read_dir(directory) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if file_name.ends_with(TEMP_SUFFIX) {
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+
+            if !file_name.ends_with(".json") || !path.is_file() {
+                continue;
+            }
+
+            let error = match fs::read(&path) {
+                Ok(bytes) => match serde_json::from_slice::<serde::de::IgnoredAny>(&bytes) {
+                    Ok(_) => continue,
+                    Err(e) => e.to_string(),
+                },
+                Err(e) => e.to_string(),
+            };
+
+            corrupt_files.push(CorruptFile {
+                path: path.display().to_string(),
+                error,
+                fallback_available: is_valid_json(&last_good_path(&path)),
+            });
+        }
+    }
+
+    corrupt_files
+}
+
+/// Replaces a corrupt file with its last good copy, keeping the corrupt file alongside as `.corrupt`.
+pub fn restore_last_good(path: &Path) -> io::Result<()> {
+    let last_good = last_good_path(path);
+
+    if !is_valid_json(&last_good) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No usable last good copy of '{}' exists.", path.display()),
+        ));
+    }
+
+    if path.exists() {
+        fs::rename(path, with_suffix(path, CORRUPT_SUFFIX))?;
+    }
+
+    write_atomic(path, &fs::read(&last_good)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh scratch file path for a test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tableau-storage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn read_numbers(path: &Path) -> io::Result<Vec<u32>> {
+        read_json_lines(path, |line| serde_json::from_str(line))
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let path = scratch("round_trip.jsonl");
+        assert_eq!(read_numbers(&path).unwrap(), Vec::<u32>::new());
+        for number in [1, 2, 3] {
+            append_json_line(&path, &number).unwrap();
+        }
+        assert_eq!(read_numbers(&path).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn torn_last_line_is_skipped_and_cut_off_on_append() {
+        let path = scratch("torn.jsonl");
+        fs::write(&path, "1\n2\n{\"tor").unwrap();
+        assert_eq!(read_numbers(&path).unwrap(), vec![1, 2]);
+
+        append_json_line(&path, &3).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1\n2\n3\n");
+    }
+
+    #[test]
+    fn invalid_lines_before_the_last_are_errors() {
+        let path = scratch("invalid.jsonl");
+        fs::write(&path, "1\nnope\n3\n").unwrap();
+        let error = read_numbers(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 2"));
+    }
+}
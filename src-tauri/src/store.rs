@@ -0,0 +1,311 @@
+// store.rs
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::assets::AssetIndex;
+use crate::campaign::CampaignRoot;
+use crate::models::{Chapter, Entity};
+use crate::refs::{ChapterId, EntityId};
+use crate::storage;
+use crate::utils;
+
+/// How long a deferred chapter write waits for further changes before flushing
+const DEFERRED_WRITE_DELAY: Duration = Duration::from_millis(300);
+
+/// In-memory cache of chapter and entity files, keyed by file path.
+/// Every mutation holds the lock from read through flush, so edits from different
+/// windows are applied and written to disk one after another instead of clobbering each other.
+#[derive(Default)]
+pub struct CampaignStore {
+    cache: Arc<Mutex<Cache>>,
+}
+
+#[derive(Default)]
+struct Cache {
+    chapters: HashMap<PathBuf, Chapter>,
+    entities: HashMap<PathBuf, Entity>,
+    pending_writes: HashMap<PathBuf, PendingWrite>,
+    asset_index: Option<AssetIndex>,
+}
+
+/// A cached chapter with changes that have not been written to disk yet
+struct PendingWrite {
+    root: CampaignRoot,
+    chapter_id: ChapterId,
+    due: Instant,
+}
+
+impl Cache {
+    fn chapter(&mut self, root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<&mut Chapter> {
+        let path = chapter_id.file_path(root);
+        if !self.chapters.contains_key(&path) {
+            let chapter = utils::load_chapter(root, chapter_id)?;
+            self.chapters.insert(path.clone(), chapter);
+        }
+        Ok(self.chapters.get_mut(&path).expect("chapter was just cached"))
+    }
+
+    fn entity(&mut self, root: &CampaignRoot, entity_id: &EntityId) -> io::Result<&mut Entity> {
+        let path = entity_id.json_path(root);
+        if !self.entities.contains_key(&path) {
+            let entity = utils::load_entity_from_file(root, entity_id)?;
+            self.entities.insert(path.clone(), entity);
+        }
+        Ok(self.entities.get_mut(&path).expect("entity was just cached"))
+    }
+
+    fn asset_index(&mut self, root: &CampaignRoot) -> io::Result<&mut AssetIndex> {
+        if self.asset_index.is_none() {
+            self.asset_index = Some(AssetIndex::load(root)?);
+        }
+        Ok(self.asset_index.as_mut().expect("asset index was just cached"))
+    }
+
+    /// Writes a chapter with pending changes to disk. The changes stay cached if the write fails.
+    fn flush_chapter(&mut self, path: &Path) -> io::Result<()> {
+        let Some(pending) = self.pending_writes.remove(path) else {
+            return Ok(());
+        };
+        let Some(chapter) = self.chapters.get(path) else {
+            return Ok(());
+        };
+        utils::save_chapter(&pending.root, &pending.chapter_id, chapter)
+    }
+
+    fn flush_all(&mut self) {
+        let paths: Vec<PathBuf> = self.pending_writes.keys().cloned().collect();
+        for path in paths {
+            if let Err(e) = self.flush_chapter(&path) {
+                log::error!("Failed to write chapter '{}': {}", path.display(), e);
+            }
+        }
+    }
+}
+
+fn lock_cache(cache: &Mutex<Cache>) -> MutexGuard<'_, Cache> {
+    match cache.lock() {
+        Ok(cache) => cache,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Waits until a deferred chapter write has gone `DEFERRED_WRITE_DELAY` without further changes, then flushes it.
+/// Stops early if the chapter was written or removed in the meantime.
+fn flush_when_idle(cache: Arc<Mutex<Cache>>, path: PathBuf) {
+    let mut due = Instant::now() + DEFERRED_WRITE_DELAY;
+    loop {
+        thread::sleep(due.saturating_duration_since(Instant::now()));
+
+        let mut cache = lock_cache(&cache);
+        match cache.pending_writes.get(&path) {
+            None => return,
+            Some(pending) if pending.due > Instant::now() => due = pending.due,
+            Some(_) => {
+                if let Err(e) = cache.flush_chapter(&path) {
+                    log::error!("Failed to write chapter '{}': {}", path.display(), e);
+                }
+                return;
+            }
+        }
+    }
+}
+
+impl CampaignStore {
+    fn lock(&self) -> MutexGuard<'_, Cache> {
+        lock_cache(&self.cache)
+    }
+
+    pub fn read_chapter(&self, root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<Chapter> {
+        let mut cache = self.lock();
+        cache.chapter(root, chapter_id).cloned()
+    }
+
+    /// Applies `update` to the chapter and writes it to disk before releasing the lock.
+    /// If `update` or the write fails, the cached chapter is left untouched.
+    pub fn update_chapter<T>(
+        &self,
+        root: &CampaignRoot,
+        chapter_id: &ChapterId,
+        update: impl FnOnce(&mut Chapter) -> io::Result<T>,
+    ) -> io::Result<T> {
+        self.update_chapter_reading_entities(root, chapter_id, |chapter, _| update(chapter))
+    }
+
+    /// Like `update_chapter`, also letting `update` read entities while the lock is held, for updates
+    /// that depend on the chapter's entities as they are at the moment of writing
+    pub fn update_chapter_reading_entities<T>(
+        &self,
+        root: &CampaignRoot,
+        chapter_id: &ChapterId,
+        update: impl FnOnce(&mut Chapter, &mut dyn FnMut(&EntityId) -> io::Result<Entity>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut cache = self.lock();
+        let mut chapter = cache.chapter(root, chapter_id)?.clone();
+
+        let result = update(&mut chapter, &mut |entity_id| cache.entity(root, entity_id).cloned())?;
+        utils::save_chapter(root, chapter_id, &chapter)?;
+
+        *cache.chapter(root, chapter_id)? = chapter;
+        // The write above already includes any deferred changes
+        cache.pending_writes.remove(&chapter_id.file_path(root));
+        Ok(result)
+    }
+
+    /// Applies `update` to the cached chapter right away but delays writing it to disk until no
+    /// further changes arrive for a short while, so a burst of updates becomes a single write.
+    /// If `update` fails, the cached chapter is left untouched.
+    /// `update` can read entities while the lock is held.
+    pub fn update_chapter_deferred_reading_entities<T>(
+        &self,
+        root: &CampaignRoot,
+        chapter_id: &ChapterId,
+        update: impl FnOnce(&mut Chapter, &mut dyn FnMut(&EntityId) -> io::Result<Entity>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut cache = self.lock();
+        let mut chapter = cache.chapter(root, chapter_id)?.clone();
+
+        let result = update(&mut chapter, &mut |entity_id| cache.entity(root, entity_id).cloned())?;
+        *cache.chapter(root, chapter_id)? = chapter;
+
+        let path = chapter_id.file_path(root);
+        let pending = PendingWrite {
+            root: root.clone(),
+            chapter_id: chapter_id.clone(),
+            due: Instant::now() + DEFERRED_WRITE_DELAY,
+        };
+        if cache.pending_writes.insert(path.clone(), pending).is_none() {
+            let cache = Arc::clone(&self.cache);
+            thread::spawn(move || flush_when_idle(cache, path));
+        }
+
+        Ok(result)
+    }
+
+    /// Writes a new chapter, failing if the chapter file already exists
+    pub fn create_chapter(&self, root: &CampaignRoot, chapter_id: &ChapterId, chapter: Chapter) -> io::Result<()> {
+        let mut cache = self.lock();
+        let path = chapter_id.file_path(root);
+
+        if path.exists() || cache.chapters.contains_key(&path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Chapter '{}' already exists.", chapter_id),
+            ));
+        }
+
+        utils::save_chapter(root, chapter_id, &chapter)?;
+        cache.chapters.insert(path, chapter);
+        Ok(())
+    }
+
+    /// Writes a chapter whether or not its file exists, e.g. to bring back a deleted chapter
+    pub fn write_chapter(&self, root: &CampaignRoot, chapter_id: &ChapterId, chapter: Chapter) -> io::Result<()> {
+        let mut cache = self.lock();
+        let path = chapter_id.file_path(root);
+
+        utils::save_chapter(root, chapter_id, &chapter)?;
+        cache.pending_writes.remove(&path);
+        cache.chapters.insert(path, chapter);
+        Ok(())
+    }
+
+    /// Deletes a chapter file and drops it from the cache
+    pub fn remove_chapter_file(&self, path: &Path) -> io::Result<()> {
+        let mut cache = self.lock();
+        fs::remove_file(path)?;
+        cache.chapters.remove(path);
+        cache.pending_writes.remove(path);
+        Ok(())
+    }
+
+    pub fn read_entity(&self, root: &CampaignRoot, entity_id: &EntityId) -> io::Result<Entity> {
+        let mut cache = self.lock();
+        cache.entity(root, entity_id).cloned()
+    }
+
+    /// Replaces the entity file's contents and writes it to disk before releasing the lock
+    pub fn write_entity(&self, root: &CampaignRoot, entity_id: &EntityId, entity: Entity) -> io::Result<()> {
+        let mut cache = self.lock();
+        let path = entity_id.json_path(root);
+
+        storage::write_json_atomic(&path, &entity)?;
+        cache.entities.insert(path, entity);
+        Ok(())
+    }
+
+    /// Applies `update` to the entity and writes it to disk before releasing the lock.
+    /// If `update` or the write fails, the cached entity is left untouched.
+    pub fn update_entity<T>(
+        &self,
+        root: &CampaignRoot,
+        entity_id: &EntityId,
+        update: impl FnOnce(&mut Entity) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut cache = self.lock();
+        let cached = cache.entity(root, entity_id)?;
+
+        let mut entity = cached.clone();
+        let result = update(&mut entity)?;
+        storage::write_json_atomic(&entity_id.json_path(root), &entity)?;
+
+        *cached = entity;
+        Ok(result)
+    }
+
+    /// Deletes an entity file and drops it from the cache
+    pub fn remove_entity_file(&self, path: &Path) -> io::Result<()> {
+        let mut cache = self.lock();
+        fs::remove_file(path)?;
+        cache.entities.remove(path);
+        Ok(())
+    }
+
+    pub fn read_asset_index(&self, root: &CampaignRoot) -> io::Result<AssetIndex> {
+        let mut cache = self.lock();
+        cache.asset_index(root).cloned()
+    }
+
+    /// Applies `update` to the asset index and writes it to disk before releasing the lock
+    pub fn update_asset_index<T>(
+        &self,
+        root: &CampaignRoot,
+        update: impl FnOnce(&mut AssetIndex) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut cache = self.lock();
+        let cached = cache.asset_index(root)?;
+
+        let mut index = cached.clone();
+        let result = update(&mut index)?;
+        index.save(root)?;
+
+        *cached = index;
+        Ok(result)
+    }
+
+    /// Forgets a cached file, including changes not yet written, so the next access reloads it from disk
+    pub fn evict(&self, path: &Path) {
+        let mut cache = self.lock();
+        cache.chapters.remove(path);
+        cache.entities.remove(path);
+        cache.pending_writes.remove(path);
+    }
+
+    /// Writes every chapter with deferred changes to disk now, e.g. before the app exits
+    pub fn flush(&self) {
+        self.lock().flush_all();
+    }
+
+    /// Flushes deferred writes and drops every cached file, e.g. after switching campaigns
+    pub fn clear(&self) {
+        let mut cache = self.lock();
+        cache.flush_all();
+        cache.chapters.clear();
+        cache.entities.clear();
+        cache.asset_index = None;
+    }
+}
@@ -0,0 +1,93 @@
+// terrain.rs
+use serde::{Deserialize, Serialize};
+
+use crate::dice::DiceExpression;
+use crate::hex::Hex;
+
+/// What is painted on a hex
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TerrainKind {
+    /// Can't be entered
+    Blocked,
+    /// Takes twice the movement to enter
+    Difficult,
+    /// Takes twice the movement to enter, like swimming without a swim speed
+    Water,
+    /// Can be entered, but deals `damage` (a dice expression, e.g. `2d6 fire`) to whoever does
+    Hazard { damage: String },
+}
+
+impl TerrainKind {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            TerrainKind::Hazard { damage } => DiceExpression::parse(damage).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Movement it takes to enter a hex of this kind, None if it can't be entered
+    pub fn movement_cost(&self) -> Option<u32> {
+        match self {
+            TerrainKind::Blocked => None,
+            TerrainKind::Difficult | TerrainKind::Water => Some(2),
+            TerrainKind::Hazard { .. } => Some(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TerrainTile {
+    pub hex: Hex,
+    #[serde(flatten)]
+    pub kind: TerrainKind,
+}
+
+/// A wall along the edge between two neighboring hexes. It blocks movement and sight between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Wall(Hex, Hex);
+
+impl Wall {
+    /// The wall between two neighboring hexes, the same whichever side it is given from
+    pub fn between(a: Hex, b: Hex) -> Result<Wall, String> {
+        if a.distance(b) != 1 {
+            return Err(format!("Hexes ({}, {}) and ({}, {}) are not neighbors.", a.q, a.r, b.q, b.r));
+        }
+        Ok(Wall(a.min(b), a.max(b)))
+    }
+}
+
+/// What a combat's map holds: painted hexes, at most one kind per hex, and walls between hexes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Terrain {
+    pub tiles: Vec<TerrainTile>,
+    pub walls: Vec<Wall>,
+}
+
+impl Terrain {
+    /// Paints `kind` on every hex, replacing what was there
+    pub fn paint(&mut self, hexes: &[Hex], kind: &TerrainKind) {
+        self.erase(hexes);
+        for &hex in hexes {
+            if !self.tiles.iter().any(|tile| tile.hex == hex) {
+                self.tiles.push(TerrainTile { hex, kind: kind.clone() });
+            }
+        }
+    }
+
+    pub fn erase(&mut self, hexes: &[Hex]) {
+        self.tiles.retain(|tile| !hexes.contains(&tile.hex));
+    }
+
+    pub fn add_walls(&mut self, walls: &[Wall]) {
+        for wall in walls {
+            if !self.walls.contains(wall) {
+                self.walls.push(*wall);
+            }
+        }
+    }
+
+    pub fn remove_walls(&mut self, walls: &[Wall]) {
+        self.walls.retain(|wall| !walls.contains(wall));
+    }
+}
@@ -1,32 +1,37 @@
 // Standard library imports
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::fs::{self, File, OpenOptions};
-use std::io::{self, BufRead, BufReader, Read, Write, Result};
+use std::io::{self, BufRead, BufReader, Write, Result};
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 // External crate imports
 use rand::{distributions::Alphanumeric, Rng};
-use serde_json::{json, Value};
+use serde_json::Value;
 
 // Image processing
 use image::{DynamicImage, GenericImage, GenericImageView, Rgba, imageops::crop};
-use imageproc::drawing::{draw_polygon_mut, draw_line_segment_mut};
+use imageproc::drawing::draw_polygon_mut;
 use imageproc::point::Point;
 
 // Project-specific imports
-use crate::models::{BoundingBox, TransformStateObject, Entity, Coordinates};
+use crate::campaign::{AssetKind, CampaignRoot};
+use crate::migrations;
+use crate::storage;
+use crate::hex::{self, Hex, Layout};
+use crate::models::{BoundingBox, TransformStateObject, Entity, Coordinates, Chapter, Combat, Splash};
+use crate::refs::{AssetRef, ChapterId, EntityId};
+use crate::store::CampaignStore;
 
 // File dialog for user interaction
 use native_dialog::FileDialog;
 
 
-pub fn list_files_in_directory(dir: &str) -> io::Result<Vec<String>> {
-    let path = Path::new(dir);
-    
+pub fn list_files_in_directory(dir: &Path) -> io::Result<Vec<String>> {
     // Read the directory contents
     let mut file_names = Vec::new();
     
-    for entry in fs::read_dir(path)? {
+    for entry in fs::read_dir(dir)? {
         let entry = entry?;
         let path = entry.path();
         
@@ -70,6 +75,13 @@ match chars.next() {
 }
 }
 
+// Milliseconds since the Unix epoch, used to timestamp logs and name sessions
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
 // Function to open file explorer and return the selected image paths
 pub fn select_image_files() -> Option<Vec<PathBuf>> {
     // Opens a file dialog for selecting multiple images
@@ -88,260 +100,99 @@ pub fn select_image_file() -> Option<PathBuf> {
         .ok()?
 }
 
-/// Function that takes an image path and an output directory and saves the image into the output directory
-/// This version does not return anything, just propagates errors if any occur.
-pub fn save_image_to_directory(image_path: &Path, output_directory: &Path) -> Result<()> {
-    // Ensure the output directory exists
-    if !output_directory.exists() {
-        fs::create_dir_all(output_directory)?;
-    }
-
-    // Get the file name of the image from the input path
-    if let Some(file_name) = image_path.file_name() {
-        // Define the destination path as output_directory/image_file_name
-        let destination_path = output_directory.join(file_name);
-
-        // Copy the image to the destination
-        fs::copy(image_path, &destination_path)?;
-
-        // No need to return anything, operation successful
-        Ok(())
-    } else {
-        // Return an error if the image file doesn't have a valid file name
-        Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "Invalid image file name",
-        ))
-    }
+// Function to open a folder dialog and return the selected directory
+pub fn select_directory() -> Option<PathBuf> {
+    FileDialog::new()
+        .show_open_single_dir()
+        .ok()?
 }
 
-/// Function that takes an image path and returns the image filename as a String
-pub fn get_image_filename(image_path: &Path) -> Option<String> {
-    image_path.file_name()
-        .and_then(|name| name.to_str())  // Convert OsStr to &str
-        .map(|name_str| name_str.to_string())  // Convert &str to String
+// Function to open a save dialog for a Markdown file, suggesting `default_name`
+pub fn select_markdown_save_file(default_name: &str) -> Option<PathBuf> {
+    FileDialog::new()
+        .set_filename(default_name)
+        .add_filter("Markdown files", &["md"])
+        .show_save_single_file()
+        .ok()?
 }
 
-// Function to format chapter ID into the JSON filename in the desired path
-pub fn format_chapter_id(input: &str) -> PathBuf {
-    let lowercase_input = input.to_lowercase();
-    // Construct the path as `../tableau/chapters/chapter_<id>.json`
-    PathBuf::from(format!("../tableau/chapters/chapter_{}.json", lowercase_input))
-}
+/// Loads and validates the chapter file for the given chapter ID.
+/// Older files are migrated to the current schema (with a backup) before validation.
+/// Missing or mistyped fields are reported as `InvalidData` rather than being defaulted.
+pub fn load_chapter(root: &CampaignRoot, chapter_id: &ChapterId) -> io::Result<Chapter> {
+    let path = chapter_id.file_path(root);
 
-// Function to update only the 'landscapes' field of the JSON file
-pub fn update_landscapes(chapter_id: String, new_landscapes: Vec<String>) -> io::Result<()> {
-    // Convert chapter ID to the JSON filename
-    let path = format_chapter_id(&chapter_id);
-
-    // Read existing JSON content into a `serde_json::Value`
-    let mut json_value: Value = if path.exists() {
-        let mut file = File::open(&path)?;
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-
-        // Parse the content into a `Value`
-        serde_json::from_str(&content).unwrap_or_else(|_| {
-            // Create default structure if parsing fails
-            json!({
-                "landscapes": [],
-                "splashes": [],
-                "combat": []
-            })
-        })
-    } else {
-        // If the file doesn't exist, create a default structure
-        json!({
-            "landscapes": [],
-            "splashes": [],
-            "combat": []
-        })
-    };
+    // Check if the file exists
+    if !path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Chapter file for ID '{}' not found.", chapter_id),
+        ));
+    }
 
-    // Get the existing landscapes
-    let existing_landscapes: HashSet<String> = if let Some(landscapes) = json_value.get("landscapes") {
-        if let Some(array) = landscapes.as_array() {
-            // Convert existing landscapes array into a HashSet to avoid duplicates
-            array.iter()
-                .filter_map(|v| v.as_str().map(String::from))  // Convert `Value` to `String`
-                .collect()
-        } else {
-            HashSet::new()
-        }
-    } else {
-        HashSet::new()  // If no landscapes field exists, start with an empty set
+    let content = fs::read_to_string(&path)?;
+    let invalid = |e: serde_json::Error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Chapter '{}' is invalid: {}", chapter_id, e),
+        )
     };
 
-    // Convert new landscapes into a HashSet to avoid duplicates
-    let new_landscapes_set: HashSet<String> = new_landscapes.into_iter().collect();
+    // Upgrade older files before parsing them into the Chapter struct
+    let mut json_value: Value = serde_json::from_str(&content).map_err(invalid)?;
+    let migrated = migrations::migrate_chapter(&mut json_value, &path)?;
 
-    // Combine the two sets, retaining only unique values
-    let combined_landscapes: HashSet<String> = existing_landscapes.union(&new_landscapes_set).cloned().collect();
+    let chapter: Chapter = serde_json::from_value(json_value).map_err(invalid)?;
 
-    // Update the 'landscapes' field in the JSON object with the combined set (convert to Vec for JSON)
-    let landscape_values: Vec<Value> = combined_landscapes.into_iter().map(Value::String).collect();
-    json_value["landscapes"] = Value::Array(landscape_values);
+    if migrated {
+        save_chapter(root, chapter_id, &chapter)?;
+    }
 
-    // Open the file for writing and write the updated JSON data
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)  // Clear the file before writing new content
-        .open(&path)?;
+    Ok(chapter)
+}
 
-    let updated_content = serde_json::to_string_pretty(&json_value)?;
-    file.write_all(updated_content.as_bytes())?;
+/// Serializes the chapter and atomically writes it to the chapter file for the given chapter ID.
+pub fn save_chapter(root: &CampaignRoot, chapter_id: &ChapterId, chapter: &Chapter) -> io::Result<()> {
+    let path = chapter_id.file_path(root);
+    storage::write_json_atomic(&path, chapter)
+}
 
-    Ok(())
+// Function to update only the 'landscapes' field of the JSON file
+pub fn update_landscapes(store: &CampaignStore, root: &CampaignRoot, chapter_id: &ChapterId, new_landscapes: Vec<String>) -> io::Result<()> {
+    store.update_chapter(root, chapter_id, |chapter| {
+        // Append the new landscapes, skipping any that are already present
+        for landscape in new_landscapes {
+            if !chapter.landscapes.contains(&landscape) {
+                chapter.landscapes.push(landscape);
+            }
+        }
+        Ok(())
+    })
 }
 
 // Function to update only the 'splashes' field of the JSON file
-pub fn update_splashes(chapter_id: String, new_splashes: Vec<String>) -> io::Result<()> {
-    // Convert chapter ID to the JSON filename
-    let path = format_chapter_id(&chapter_id);
-
-    // Read existing JSON content into a `serde_json::Value`
-    let mut json_value: Value = if path.exists() {
-        let mut file = File::open(&path)?;
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-
-        // Parse the content into a `Value`
-        serde_json::from_str(&content).unwrap_or_else(|_| {
-            // Create default structure if parsing fails
-            json!({
-                "landscapes": [],
-                "splashes": [],
-                "combat": []
-            })
-        })
-    } else {
-        // If the file doesn't exist, create a default structure
-        json!({
-            "landscapes": [],
-            "splashes": [],
-            "combat": []
-        })
-    };
-
-    // Get the existing splashes as a set of (image, allegiance) tuples
-    let existing_splashes: HashSet<(String, String)> = if let Some(splashes) = json_value.get("splashes") {
-        if let Some(array) = splashes.as_array() {
-            array.iter()
-                .filter_map(|v| {
-                    if let Some(image) = v.get("image").and_then(|img| img.as_str()) {
-                        let allegiance = v.get("allegiance").and_then(|a| a.as_str()).unwrap_or("neutral").to_string();
-                        Some((image.to_string(), allegiance))
-                    } else {
-                        None
-                    }
-                })
-                .collect()
-        } else {
-            HashSet::new()
+pub fn update_splashes(store: &CampaignStore, root: &CampaignRoot, chapter_id: &ChapterId, new_splashes: Vec<String>) -> io::Result<()> {
+    store.update_chapter(root, chapter_id, |chapter| {
+        // Append the new splashes with a default "neutral" allegiance, unique by image name
+        for image in new_splashes {
+            if !chapter.splashes.iter().any(|splash| splash.image == image) {
+                chapter.splashes.push(Splash {
+                    image,
+                    allegiance: "neutral".to_string(),
+                });
+            }
         }
-    } else {
-        HashSet::new()  // If no splashes field exists, start with an empty set
-    };
-
-    // Create new splashes as (image, allegiance) tuples with default "neutral" allegiance
-    let new_splashes_set: HashSet<(String, String)> = new_splashes.into_iter()
-        .map(|image| (image, "neutral".to_string()))  // New splashes default to "neutral"
-        .collect();
-
-    // Combine the two sets, retaining only unique values (by image name)
-    let combined_splashes: HashSet<(String, String)> = existing_splashes.union(&new_splashes_set).cloned().collect();
-
-    // Convert the combined splashes into the correct JSON format (array of objects)
-    let splash_values: Vec<Value> = combined_splashes.into_iter()
-        .map(|(image, allegiance)| {
-            json!({
-                "image": image,
-                "allegiance": allegiance
-            })
-        })
-        .collect();
-
-    // Update the 'splashes' field in the JSON object with the new structure
-    json_value["splashes"] = Value::Array(splash_values);
-
-    // Open the file for writing and write the updated JSON data
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)  // Clear the file before writing new content
-        .open(&path)?;
-
-    let updated_content = serde_json::to_string_pretty(&json_value)?;
-    file.write_all(updated_content.as_bytes())?;
-
-    Ok(())
+        Ok(())
+    })
 }
 
 // updates the combat field of the JSON file
-pub fn update_combat(chapter_id: String, battlemap: String) -> io::Result<()> {
-    // Get the path of the chapter JSON file
-    let chapter_path = format_chapter_id(&chapter_id);
-
-    // Read existing JSON content into a `serde_json::Value`
-    let mut json_value: Value = if chapter_path.exists() {
-        let mut file = File::open(&chapter_path)?;
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-
-        // Parse the content into a `Value`
-        serde_json::from_str(&content).unwrap_or_else(|_| {
-            // Create default structure if parsing fails
-            json!({
-                "landscapes": [],
-                "splashes": [],
-                "combat": []
-            })
-        })
-    } else {
-        // If the file doesn't exist, create a default structure
-        json!({
-            "landscapes": [],
-            "splashes": [],
-            "combat": []
-        })
-    };
-
-    // Create a new combat object
-    let new_combat = json!({
-        "battlemap": battlemap,
-        "mapsize": 100,
-        "mapoffset": {
-            "x": 0,
-            "y": 0
-        },
-        "gridsize": 100,
-        "gridoffset": {
-            "x": 0,
-            "y": 0
-        },
-        "entities": []
-    });
-
-    // Append the new combat object to the `combat` field
-    if let Some(combat_array) = json_value["combat"].as_array_mut() {
-        combat_array.push(new_combat);
-    } else {
-        // If combat is not an array, initialize it as an empty array and add the new object
-        json_value["combat"] = json!([new_combat]);
-    }
-
-    // Open the file for writing and write the updated JSON data
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)  // Clear the file before writing new content
-        .open(&chapter_path)?;
-
-    let updated_content = serde_json::to_string_pretty(&json_value)?;
-    file.write_all(updated_content.as_bytes())?;
-
-    Ok(())
+pub fn update_combat(store: &CampaignStore, root: &CampaignRoot, chapter_id: &ChapterId, battlemap: String) -> io::Result<()> {
+    store.update_chapter(root, chapter_id, |chapter| {
+        // Append a new combat object with the default map and grid settings
+        chapter.combat.push(Combat::new(battlemap));
+        Ok(())
+    })
 }
 
 // Updated function to create a hexagonal mask with boundary vertices
@@ -472,19 +323,18 @@ pub fn crop_to_content(image: &mut DynamicImage) -> DynamicImage {
 }
 
 
-pub fn generate_icon_id() -> Result<String> {
-    // Hard-coded file path
-    let file_path = "../tableau/entities/entity_ids.txt";
+pub fn generate_icon_id(root: &CampaignRoot) -> Result<EntityId> {
+    let file_path = root.entity_ids_file();
 
     // Ensure the directory exists
-    if let Some(parent) = Path::new(file_path).parent() {
+    if let Some(parent) = file_path.parent() {
         fs::create_dir_all(parent)?;
     }
 
     // Load existing IDs into a HashSet to check for duplicates
     let mut existing_ids = HashSet::new();
-    if Path::new(file_path).exists() {
-        let file = File::open(file_path)?;
+    if file_path.exists() {
+        let file = File::open(&file_path)?;
         let reader = BufReader::new(file);
         for line in reader.lines() {
             existing_ids.insert(line?);
@@ -507,18 +357,19 @@ pub fn generate_icon_id() -> Result<String> {
     }
 
     // Open the file in append mode and write the new ID
-    let mut file = OpenOptions::new().append(true).create(true).open(file_path)?;
+    let mut file = OpenOptions::new().append(true).create(true).open(&file_path)?;
     writeln!(file, "{}", icon_id)?;
 
-    Ok(icon_id)
+    EntityId::parse(&icon_id)
 }
 
 /// Function to generate an entity icon, crop the image based on the given bounding box
-/// and transformation state, apply a hex mask, and save it using the given icon ID.
+/// and transformation state, apply a hex mask, and save it to `output_path`.
 pub fn generate_entity_icon(
-    filename: &str,
+    root: &CampaignRoot,
+    filename: &AssetRef,
     transform_state: TransformStateObject,
-    icon_id: &str,
+    output_path: &Path,
     allegiance: &str,
 ) -> std::result::Result<String, io::Error> {
     let bounding_box = BoundingBox {
@@ -531,8 +382,7 @@ pub fn generate_entity_icon(
     let container_width = 450.0;
     let container_height = 450.0;
 
-    let base_directory = Path::new("../tableau/assets/iconimages");
-    let image_path = base_directory.join(filename);
+    let image_path = filename.path(root, AssetKind::IconImages);
 
     if !image_path.exists() {
         return Err(io::Error::new(
@@ -574,152 +424,51 @@ pub fn generate_entity_icon(
     masked_image = add_hex_stroke(&masked_image, allegiance);
     let final_image = crop_to_content(&mut masked_image);
 
-    let output_directory = Path::new("../tableau/assets/entities");
-
-    if !output_directory.exists() {
+    if let Some(output_directory) = output_path.parent().filter(|directory| !directory.exists()) {
         if let Err(err) = std::fs::create_dir_all(output_directory) {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
+            return Err(io::Error::other(
                 format!("Failed to create output directory: {}", err),
             ));
         }
     }
 
-    let output_path = output_directory.join(format!("{}.png", icon_id));
-
-    match final_image.save(&output_path) {
+    match final_image.save(output_path) {
         Ok(_) => Ok(format!("Hexagonally masked image saved as '{}'.", output_path.display())),
-        Err(err) => Err(io::Error::new(
-            io::ErrorKind::Other,
+        Err(err) => Err(io::Error::other(
             format!("Failed to save hexagonally masked image: {}", err),
         )),
     }
 }
 
-/// Function to create a new entity JSON file with the given icon ID, allegiance, and size.
-/// The entity is saved as `iconid.json` in the directory `../tableau/entities`.
-pub fn create_entity(icon_id: &str, allegiance: &str, entity_size: &str) -> io::Result<()> {
-    // Define the directory where the entity file will be stored
-    let output_directory = Path::new("../tableau/entities");
-
-    // Ensure the directory exists
-    if !output_directory.exists() {
-        std::fs::create_dir_all(output_directory)?;
-    }
-
-    // Construct the file path as `../tableau/entities/iconid.json`
-    let file_path = output_directory.join(format!("{}.json", icon_id));
-
-    // Create the entity JSON object
-    let entity_data = json!({
-        "icon": format!("{}.png", icon_id),
-        "allegiance": allegiance,
-        "size": entity_size,
-        "location": {
-            "x": 0,
-            "y": 0
-        },
-        "hitpoints": {
-            "current": 0,
-            "max": 0
-        },
-        "visible": true,
-        "dead": false,
-        "modifiers": ""
-    });
-
-    // Open the file for writing (create or truncate)
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&file_path)?;
-
-    // Write the JSON data to the file
-    let content = serde_json::to_string_pretty(&entity_data)?;
-    file.write_all(content.as_bytes())?;
+/// Function to create a new, unnamed entity JSON file with the given icon ID, allegiance, and size.
+/// The entity is saved as `iconid.json` in the campaign's entities directory.
+pub fn create_entity(store: &CampaignStore, root: &CampaignRoot, icon_id: &EntityId, allegiance: &str, entity_size: &str) -> io::Result<()> {
+    let entity = Entity::new(icon_id.icon_filename(), String::new(), allegiance.to_string(), entity_size.to_string());
 
-    Ok(())
+    // Write the entity to `<campaign>/entities/iconid.json`
+    store.write_entity(root, icon_id, entity)
 }
 
 /// Function to update the entities array in a specified combat object within the chapter JSON file.
 /// Appends the given `iconid.json` to the `entities` array in the combat object that matches the `battlemapid`.
-pub fn update_entities(chapter_id: &str, battlemap_id: &str, icon_id: &str) -> io::Result<()> {
-    // Get the path of the chapter JSON file
-    let chapter_path = format_chapter_id(chapter_id);
-
-    // Read existing JSON content into a `serde_json::Value`
-    let mut json_value: Value = if chapter_path.exists() {
-        let mut file = std::fs::File::open(&chapter_path)?;
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-
-        // Parse the content into a `Value`
-        serde_json::from_str(&content).unwrap_or_else(|_| {
-            // Create default structure if parsing fails
-            json!({
-                "landscapes": [],
-                "splashes": [],
-                "combat": []
-            })
-        })
-    } else {
-        // If the file doesn't exist, create a default structure
-        json!({
-            "landscapes": [],
-            "splashes": [],
-            "combat": []
-        })
-    };
-
-    // Find the matching battlemap object in the `combat` array
-    let combat_array = json_value
-        .get_mut("combat")
-        .and_then(|c| c.as_array_mut())
-        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Combat array not found"))?;
-
-    for combat in combat_array.iter_mut() {
-        if let Some(battlemap) = combat.get("battlemap").and_then(|b| b.as_str()) {
-            if battlemap == battlemap_id {
-                // Check if the "entities" field exists, and create an empty array if it does not
-                if !combat.get("entities").is_some() {
-                    combat["entities"] = json!([]);
-                }
-                // Now, safely get the mutable reference to the "entities" array
-                let entities = combat
-                    .get_mut("entities")
-                    .and_then(|e| e.as_array_mut())
-                    .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Failed to get entities array"))?;
-
-                // Append the `iconid.json` to the entities array
-                entities.push(Value::String(format!("{}.json", icon_id)));
-
-                // Write the updated JSON content back to the file
-                let updated_content = serde_json::to_string_pretty(&json_value)?;
-                let mut file = OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&chapter_path)?;
-
-                file.write_all(updated_content.as_bytes())?;
-
-                return Ok(());
-            }
-        }
-    }
-
-    // If no matching battlemap is found, return an error
-    Err(io::Error::new(
-        io::ErrorKind::NotFound,
-        format!("Battlemap '{}' not found in the chapter '{}'", battlemap_id, chapter_id),
-    ))
+pub fn update_entities(store: &CampaignStore, root: &CampaignRoot, chapter_id: &ChapterId, battlemap_id: &str, icon_id: &EntityId) -> io::Result<()> {
+    store.update_chapter(root, chapter_id, |chapter| {
+        // Find the matching battlemap object in the `combat` array
+        let combat = chapter.find_combat_mut(battlemap_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Battlemap '{}' not found in the chapter '{}'", battlemap_id, chapter_id),
+            )
+        })?;
+
+        // Append the `iconid.json` to the entities array
+        combat.entities.push(icon_id.json_filename());
+        Ok(())
+    })
 }
 
-pub fn load_entity_from_file(entity_filename: &str) -> io::Result<Entity> {
-    // Hardcoded base directory path
-    let base_directory = Path::new("../tableau/entities");
-    let entity_path = base_directory.join(entity_filename);
+pub fn load_entity_from_file(root: &CampaignRoot, entity_id: &EntityId) -> io::Result<Entity> {
+    let entity_path = entity_id.json_path(root);
 
     // Check if the file exists
     if !entity_path.exists() {
@@ -729,29 +478,34 @@ pub fn load_entity_from_file(entity_filename: &str) -> io::Result<Entity> {
         ));
     }
 
-    // Open the entity file
-    let mut file = File::open(&entity_path)?;
-    let mut content = String::new();
-    file.read_to_string(&mut content)?;
-
-    // Parse the JSON content into the Entity struct
-    let entity: Entity = serde_json::from_str(&content).map_err(|e| {
+    let content = fs::read_to_string(&entity_path)?;
+    let invalid = |e: serde_json::Error| {
         io::Error::new(
             io::ErrorKind::InvalidData,
             format!("Failed to parse entity JSON: {}", e),
         )
-    })?;
+    };
+
+    // Upgrade older files before parsing them into the Entity struct
+    let mut json_value: Value = serde_json::from_str(&content).map_err(invalid)?;
+    let migrated = migrations::migrate_entity(&mut json_value, &entity_path)?;
+
+    let entity: Entity = serde_json::from_value(json_value).map_err(invalid)?;
+
+    if migrated {
+        storage::write_json_atomic(&entity_path, &entity)?;
+    }
 
     Ok(entity)
 }
 
-/// Function to generate the center coordinates of a pointy-topped hexagonal grid
-pub fn generate_grid_centers(
+/// Function to generate the hexes of a pointy-topped hexagonal grid covering the container
+pub fn generate_grid_hexes(
     container_width: u32,
     container_height: u32,
     size: f32,
     overflow: usize,
-) -> Vec<Coordinates> {
+) -> Vec<Hex> {
     let hex_height = size * 2.0;
     let hex_width = (3.0_f32).sqrt() * size;
     let vertical_spacing = hex_height * 3.0 / 4.0;
@@ -760,68 +514,78 @@ pub fn generate_grid_centers(
     let columns = ((container_width as f32 / horizontal_spacing).ceil() as usize) + overflow;
     let rows = ((container_height as f32 / vertical_spacing).ceil() as usize) + overflow;
 
-    let mut centers = Vec::new();
+    let mut hexes = Vec::new();
 
     for row in -(overflow as i32)..rows as i32 {
-        let cy = row as f32 * vertical_spacing;
         for col in -(overflow as i32)..columns as i32 {
-            let mut cx = col as f32 * horizontal_spacing;
-            if row % 2 != 0 {
-                cx += horizontal_spacing / 2.0;
-            }
-            centers.push(Coordinates { x: cx, y: cy });
+            hexes.push(Hex::from_offset(col, row));
         }
     }
-    centers
-}
-
-/// Calculate the hexagon vertices based on the center and size
-pub fn calculate_hexagon_vertices(cx: f32, cy: f32, radius: f32) -> Vec<imageproc::point::Point<i32>> {
-    let mut vertices = Vec::new();
-    for i in 0..6 {
-        let angle_deg = 60.0 * i as f32 + 30.0;
-        let angle_rad = std::f32::consts::PI / 180.0 * angle_deg;
-        let x = cx + radius * angle_rad.cos();
-        let y = cy + radius * angle_rad.sin();
-        vertices.push(imageproc::point::Point::new(x as i32, y as i32));
-    }
-    vertices
+    hexes
 }
 
+/// Draws the outlines of `hexes`. The image is drawn unshifted, with hex (0, 0) in the top left corner;
+/// the display moves it by the combat's grid offset.
 pub fn generate_hex_grid_png(
-    grid_centers: &[Coordinates],
+    hexes: &[Hex],
     size: f32,
     original_width: u32,
     original_height: u32,
-    output_path: &str,
+    output_path: &Path,
 ) -> std::result::Result<(), Box<dyn std::error::Error>> {
     let mut image = image::RgbaImage::new(original_width, original_height);
     let line_color = image::Rgba([122, 122, 122, 255]); // Gray color for hexagon lines
 
-    // Loop through each center and draw hexagon outlines without scaling
-    for &center in grid_centers {
-        // No scaling applied, use the original center coordinates
-        let center_x = center.x;
-        let center_y = center.y;
-
-        // Keep the hexagon size the same as provided
-        let hex_size = size;
+    let layout = Layout::new(size, Coordinates { x: 0.0, y: 0.0 });
 
-        // Calculate hexagon vertices based on the original size and center
-        let vertices = calculate_hexagon_vertices(center_x, center_y, hex_size);
+    // Loop through each hex and draw its outline without scaling
+    for &hex in hexes {
+        let vertices = layout.corners(hex);
 
         // Draw thicker lines by drawing small circles along the lines
         for i in 0..6 {
-            let start = (vertices[i].x, vertices[i].y);  // Convert Point<i32> to tuple (i32, i32)
-            let end = (vertices[(i + 1) % 6].x, vertices[(i + 1) % 6].y);  // Convert Point<i32> to tuple (i32, i32)
+            let start = (vertices[i].x as i32, vertices[i].y as i32);
+            let end = (vertices[(i + 1) % 6].x as i32, vertices[(i + 1) % 6].y as i32);
 
             // Draw a thick line (5px wide) by drawing small circles along the line
             draw_thick_line(&mut image, start, end, 2, line_color);
         }
     }
 
-    // Save the image as PNG and map the error
-    image.save(output_path).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    // Encode the image as PNG and write it atomically
+    let mut png_bytes = Vec::new();
+    image.write_to(&mut io::Cursor::new(&mut png_bytes), image::ImageOutputFormat::Png)?;
+    storage::write_atomic(output_path, &png_bytes)?;
+
+    Ok(())
+}
+
+/// Draws a combat's fog of war at the size of the display's reference container: opaque black, with every
+/// revealed hex cut out
+pub fn generate_fog_mask_png(
+    layout: &Layout,
+    revealed: &BTreeSet<Hex>,
+    output_path: &Path,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let (width, height) = (hex::DISPLAY_WIDTH as u32, hex::DISPLAY_HEIGHT as u32);
+    let mut image = image::RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+
+    for &hex in revealed {
+        let points: Vec<Point<i32>> = layout
+            .corners(hex)
+            .iter()
+            .map(|corner| Point::new(corner.x.round() as i32, corner.y.round() as i32))
+            .collect();
+
+        // Tiny grids can round a hex's corners together, which the polygon fill rejects
+        if points[0] != points[5] {
+            draw_polygon_mut(&mut image, &points, Rgba([0, 0, 0, 0]));
+        }
+    }
+
+    let mut png_bytes = Vec::new();
+    image.write_to(&mut io::Cursor::new(&mut png_bytes), image::ImageOutputFormat::Png)?;
+    storage::write_atomic(output_path, &png_bytes)?;
 
     Ok(())
 }
@@ -0,0 +1,47 @@
+// vision.rs
+use std::collections::{BTreeSet, HashSet};
+
+use crate::fog::MAX_RADIUS;
+use crate::hex::{Hex, Layout};
+use crate::models::Entity;
+use crate::movement::FEET_PER_HEX;
+use crate::terrain::{Terrain, Wall};
+
+/// Farthest anyone sees, in feet
+pub const MAX_VISION: u32 = MAX_RADIUS * FEET_PER_HEX;
+
+/// Whether a straight line between two hexes crosses no wall
+pub fn line_of_sight(walls: &HashSet<Wall>, from: Hex, to: Hex) -> bool {
+    from.line_to(to)
+        .windows(2)
+        .all(|step| Wall::between(step[0], step[1]).map_or(true, |wall| !walls.contains(&wall)))
+}
+
+/// Every hex on the display within `range` feet of `origin` that a line from `origin` reaches without crossing a wall
+pub fn visible_hexes(layout: &Layout, walls: &HashSet<Wall>, origin: Hex, range: u32) -> Vec<Hex> {
+    origin
+        .within(range.min(MAX_VISION) / FEET_PER_HEX)
+        .into_iter()
+        .filter(|&hex| layout.on_display(hex) && line_of_sight(walls, origin, hex))
+        .collect()
+}
+
+/// How far an entity sees, in feet: as far as the light reaches, or as far as its darkvision does
+pub fn sight_range(entity: &Entity, vision_radius: u32) -> u32 {
+    vision_radius.max(entity.darkvision)
+}
+
+/// Every hex the party sees: those visible to a friendly (non-evil) entity that is alive and shown on the display
+pub fn party_vision<'a>(
+    layout: &Layout,
+    terrain: &Terrain,
+    entities: impl IntoIterator<Item = &'a Entity>,
+    vision_radius: u32,
+) -> BTreeSet<Hex> {
+    let walls: HashSet<Wall> = terrain.walls.iter().copied().collect();
+    entities
+        .into_iter()
+        .filter(|entity| entity.allegiance != "evil" && entity.visible && !entity.dead)
+        .flat_map(|entity| visible_hexes(layout, &walls, entity.location, sight_range(entity, vision_radius)))
+        .collect()
+}
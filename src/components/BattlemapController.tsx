@@ -21,7 +21,7 @@ function BattlemapController({ combatData }: props) {
     };
 
     const sendSizeChange = (size: number) => {
-        invoke('update_battlemap_size', { chapterId, battlemap: combatData.battlemap, size: size })
+        invoke('patch_combat', { chapterId, battlemap: combatData.battlemap, patch: { mapsize: size } })
             .then(()=> {
                 reloadChapterData();
             });
@@ -38,7 +38,7 @@ function BattlemapController({ combatData }: props) {
     };
 
     const sendXOffsetChange = (xoffset: number) => {
-        invoke('update_battlemap_xoffset', { chapterId, battlemap: combatData.battlemap, xoffset: xoffset })
+        invoke('patch_combat', { chapterId, battlemap: combatData.battlemap, patch: { mapoffset: { x: xoffset } } })
             .then(()=> {
                 reloadChapterData();
             });
@@ -55,7 +55,7 @@ function BattlemapController({ combatData }: props) {
     };
 
     const sendYOffsetChange = (yoffset: number) => {
-        invoke('update_battlemap_yoffset', { chapterId, battlemap: combatData.battlemap, yoffset: yoffset })
+        invoke('patch_combat', { chapterId, battlemap: combatData.battlemap, patch: { mapoffset: { y: yoffset } } })
             .then(()=> {
                 reloadChapterData();
             });
@@ -0,0 +1,167 @@
+import { invoke } from '@tauri-apps/api/core';
+import { useEffect, useState } from 'react';
+import {
+    Entity,
+    TableauError,
+    Template,
+    TemplateStats,
+    useGlobalState,
+    useReloadChapterData,
+    useReloadEntityData,
+} from './GlobalStateContext';
+import '../styles/components/Bestiary.css';
+
+interface props {
+    battlemap: string;
+}
+
+type NumericStat = 'max_hitpoints' | 'armor_class' | 'speed' | 'darkvision' | 'initiative_bonus';
+
+const NUMERIC_STATS: { key: NumericStat; label: string }[] = [
+    { key: 'max_hitpoints', label: 'HP' },
+    { key: 'armor_class', label: 'AC' },
+    { key: 'speed', label: 'Speed' },
+    { key: 'darkvision', label: 'Darkvision' },
+    { key: 'initiative_bonus', label: 'Init' },
+];
+
+function Bestiary({ battlemap }: props) {
+    const { chapterId } = useGlobalState();
+    const reloadEntityData = useReloadEntityData();
+    const reloadChapterData = useReloadChapterData();
+    const [templates, setTemplates] = useState<Template[]>([]);
+    const [draft, setDraft] = useState<Template | null>(null);
+    const [propagate, setPropagate] = useState(true);
+    const [message, setMessage] = useState('');
+
+    const reload = () => {
+        invoke<Template[]>('get_templates').then(setTemplates);
+    };
+
+    useEffect(reload, []);
+
+    const handleSelect = (template: Template) => {
+        setDraft({ ...template, stats: { ...template.stats } });
+        setMessage('');
+    };
+
+    const handleStatChange = (key: NumericStat, value: number) => {
+        if (draft === null) {
+            return;
+        }
+        const stats: TemplateStats = { ...draft.stats, [key]: value };
+        setDraft({ ...draft, stats });
+    };
+
+    const handleSave = () => {
+        if (draft === null) {
+            return;
+        }
+        invoke<string[]>('update_template', { chapterId, template: draft, propagate })
+            .then((updated) => {
+                setMessage(updated.length > 0 ? `Updated ${updated.length} instances` : 'Saved');
+                reload();
+                reloadEntityData(battlemap);
+            })
+            .catch((err: TableauError) => setMessage(err.message));
+    };
+
+    const handleAdd = (template: Template) => {
+        invoke<Entity>('add_entity_from_template', {
+            chapterId,
+            battlemapId: battlemap,
+            templateId: template.id,
+        })
+            .then(() => {
+                reloadEntityData(battlemap);
+                reloadChapterData(); // the fog of war may follow the party's vision
+            })
+            .catch((err: TableauError) => setMessage(err.message));
+    };
+
+    const handleRemove = (template: Template) => {
+        invoke('remove_template', { chapterId, templateId: template.id }).then(() => {
+            if (draft?.id === template.id) {
+                setDraft(null);
+            }
+            reload();
+        });
+    };
+
+    return (
+        <div className='bestiary'>
+            <div className='bestiary-list'>
+                {templates.map((template) => (
+                    <div
+                        key={template.id}
+                        className={`bestiary-entry ${draft?.id === template.id ? 'bestiary-entry-selected' : ''}`}
+                    >
+                        <img
+                            src={`../tableau/bestiary/icons/${template.icon}`}
+                            alt={template.name}
+                            className='bestiary-icon'
+                            onClick={() => handleSelect(template)}
+                        />
+                        <span className='bestiary-entry-name' onClick={() => handleSelect(template)}>
+                            {template.name || 'Unnamed'}
+                        </span>
+                        <span className='bestiary-action' onClick={() => handleAdd(template)}>
+                            Add
+                        </span>
+                        <span className='bestiary-action' onClick={() => handleRemove(template)}>
+                            x
+                        </span>
+                    </div>
+                ))}
+            </div>
+            {draft !== null && (
+                <div className='bestiary-editor'>
+                    <input
+                        type='text'
+                        className='bestiary-name'
+                        value={draft.name}
+                        onChange={(event) => setDraft({ ...draft, name: event.target.value })}
+                        placeholder='Name, e.g. Goblin'
+                    />
+                    <div className='bestiary-stats'>
+                        {NUMERIC_STATS.map(({ key, label }) => (
+                            <label key={key}>
+                                {label}
+                                <input
+                                    type='number'
+                                    className='properties-number-input'
+                                    value={draft.stats[key]}
+                                    onChange={(event) => handleStatChange(key, Number(event.target.value) || 0)}
+                                />
+                            </label>
+                        ))}
+                        <span
+                            className='bestiary-action'
+                            onClick={() =>
+                                setDraft({ ...draft, allegiance: draft.allegiance === 'evil' ? 'neutral' : 'evil' })
+                            }
+                        >
+                            {draft.allegiance}
+                        </span>
+                    </div>
+                    <div className='bestiary-save'>
+                        <label>
+                            <input
+                                type='checkbox'
+                                checked={propagate}
+                                onChange={(event) => setPropagate(event.target.checked)}
+                            />
+                            Update unchanged instances
+                        </label>
+                        <div className='show-display' onClick={handleSave}>
+                            Save
+                        </div>
+                    </div>
+                    {message !== '' && <div className='bestiary-message'>{message}</div>}
+                </div>
+            )}
+        </div>
+    );
+}
+
+export default Bestiary;
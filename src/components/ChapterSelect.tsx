@@ -1,7 +1,7 @@
 import { useNavigate } from 'react-router-dom';
 import { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { useGlobalState } from './GlobalStateContext';
+import { TableauError, useGlobalState } from './GlobalStateContext';
 import '../styles/components/ChapterSelect.css';
 
 function ChapterSelect() {
@@ -13,13 +13,14 @@ function ChapterSelect() {
   const [contextChapter, setContextChapter] = useState("");
   const [menuVisible, setMenuVisible] = useState(false);
   const [menuPosition, setMenuPosition] = useState({ x: 0, y: 0 });
+  const [removedChapter, setRemovedChapter] = useState("");
 
   const getChapters = () => {
     invoke<string[]>('get_chapters')
       .then((response: string[]) => {
         setChapters(response);
       })
-      .catch((error: string) => {
+      .catch((error: TableauError) => {
         console.error('Failed to fetch chapters:', error);
       });
   }
@@ -106,13 +107,26 @@ function ChapterSelect() {
   };
 
   const handleRemove  = () => {
-    invoke('remove_chapter', {chapterId: contextChapter})
+    invoke('remove_chapter', {chapterId: contextChapter, deleteEntities: true})
       .then(() => {
         getChapters();
         setMenuVisible(false);
+        setRemovedChapter(contextChapter);
       })
   }
 
+  // The removal is the most recent step in the removed chapter's undo history
+  const handleRestore = () => {
+    invoke('undo', {chapterId: removedChapter.split(' ')[1]})
+      .then(() => {
+        getChapters();
+        setRemovedChapter("");
+      })
+      .catch((error: TableauError) => {
+        console.error('Failed to restore chapter:', error);
+      });
+  }
+
   return (
     <div className="chapter-select">
       <h1>Chapter Select</h1>
@@ -133,6 +147,12 @@ function ChapterSelect() {
           <p>Add Chapter</p>
         </div>
       </div>
+      {removedChapter && (
+        <div className="chapter-restore">
+          <p>{removedChapter} removed.</p>
+          <p className="chapter-restore-undo" onClick={handleRestore}>Restore</p>
+        </div>
+      )}
       {newChapterVisible && (
         <div className="new-chapter-menu">
           <input type="text" className='new-chapter-input' placeholder='Chapter Name' onChange={(event: React.ChangeEvent<HTMLInputElement>) => setNewChapterName(event.target.value)}/>
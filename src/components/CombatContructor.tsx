@@ -2,6 +2,8 @@ import { useNavigate, useParams } from 'react-router-dom';
 import BattlemapController from './BattlemapController';
 import {
     Combat,
+    ConditionExpiredPayload,
+    HistoryInfo,
     useGlobalState,
     useOpenDisplayWindow,
     useReloadChapterData,
@@ -13,6 +15,12 @@ import { useEffect, useState } from 'react';
 import IconEditor from './IconEditor';
 import ConstructorEntity from './ConstructorEntity';
 import PropertiesEditor from './PropertiesEditor';
+import DiceRoller from './DiceRoller';
+import CombatLog from './CombatLog';
+import Snapshots from './Snapshots';
+import TerrainEditor from './TerrainEditor';
+import FogEditor from './FogEditor';
+import Bestiary from './Bestiary';
 import { invoke } from '@tauri-apps/api/core';
 import { emit, listen } from '@tauri-apps/api/event';
 import '../styles/components/CombatConstructor.css';
@@ -29,6 +37,7 @@ function CombatConstructor() {
     const reloadEntityData = useReloadEntityData();
     const updateBattlemapId = useUpdateBattlemapId();
     const [selectedEntity, setSelectedEntity] = useState('');
+    const [history, setHistory] = useState<HistoryInfo>({ undo: [], redo: [] });
 
     const combatData: Combat = chapterData.combat.find(
         (combat: Combat) => combat.battlemap === battlemap
@@ -77,6 +86,56 @@ function CombatConstructor() {
         };
     }, []);
 
+    // Conditions count down as turns pass; refresh the entities when one runs out
+    useEffect(() => {
+        const unlistenConditionExpired = listen<ConditionExpiredPayload>('conditionExpired', () => {
+            reloadEntityData(battlemap);
+        });
+
+        return () => {
+            unlistenConditionExpired.then((unsub) => unsub());
+        };
+    }, [battlemap, reloadEntityData]);
+
+    // Any change to the chapter or its entities may have added a step to the undo history
+    useEffect(() => {
+        invoke<HistoryInfo>('get_history', { chapterId: chapterId })
+            .then(setHistory)
+            .catch((error) => console.error('Failed to load undo history:', error));
+    }, [chapterId, chapterData, entityData]);
+
+    const handleHistoryStep = (command: 'undo' | 'redo') => {
+        invoke<string | null>(command, { chapterId: chapterId })
+            .then((label) => {
+                if (label !== null) {
+                    reloadChapterData();
+                    reloadEntityData(battlemap);
+                }
+            })
+            .catch((error) => console.error(`Failed to ${command}:`, error));
+    };
+
+    // Ctrl+Z undoes, Ctrl+Y and Ctrl+Shift+Z redo, unless a text field has focus
+    useEffect(() => {
+        const handleKeyDown = (event: KeyboardEvent) => {
+            const target = event.target as HTMLElement;
+            if (!(event.ctrlKey || event.metaKey) || target.closest('input, textarea, select')) {
+                return;
+            }
+            const key = event.key.toLowerCase();
+            if (key === 'z' && !event.shiftKey) {
+                event.preventDefault();
+                handleHistoryStep('undo');
+            } else if (key === 'y' || (key === 'z' && event.shiftKey)) {
+                event.preventDefault();
+                handleHistoryStep('redo');
+            }
+        };
+
+        window.addEventListener('keydown', handleKeyDown);
+        return () => window.removeEventListener('keydown', handleKeyDown);
+    });
+
     const handleBackClick = () => {
         updateBattlemapId('');
         emit('combatUnselected');
@@ -92,6 +151,30 @@ function CombatConstructor() {
         setEditorProperties('properties');
     };
 
+    const handleDiceButton = () => {
+        setEditorProperties('dice');
+    };
+
+    const handleLogButton = () => {
+        setEditorProperties('log');
+    };
+
+    const handleSnapshotsButton = () => {
+        setEditorProperties('snapshots');
+    };
+
+    const handleBestiaryButton = () => {
+        setEditorProperties('bestiary');
+    };
+
+    const handleTerrainButton = () => {
+        setEditorProperties('terrain');
+    };
+
+    const handleFogButton = () => {
+        setEditorProperties('fog');
+    };
+
     const handleEntityClick = (icon: string) => {
         setSelectedEntity(icon);
         setEditorProperties('properties');
@@ -99,11 +182,11 @@ function CombatConstructor() {
 
     const handleShowDisplay = () => {
         invoke('generate_hexgrid', {
+            battlemap: combatData.battlemap,
             containerWidth: 1667,
             containerHeight: 953,
             hexSize: combatData.gridsize,
             overflow: 3,
-            outputPath: `../tableau/assets/hexgrids/${combatData.battlemap}`,
         }).then(() => {
             reloadChapterData();
             openDisplayWindow(`combat-display/${chapterId}/${battlemapId}`);
@@ -144,6 +227,20 @@ function CombatConstructor() {
                     Combat Constructor: Chapter {chapterId}
                 </div>}
                 <div className='show-display-container'>
+                    <div
+                        className={`show-display history-button ${history.undo.length === 0 ? 'history-empty' : ''}`}
+                        onClick={() => handleHistoryStep('undo')}
+                        title={history.undo.length > 0 ? `Undo ${history.undo[0]}` : 'Nothing to undo'}
+                    >
+                        Undo
+                    </div>
+                    <div
+                        className={`show-display history-button ${history.redo.length === 0 ? 'history-empty' : ''}`}
+                        onClick={() => handleHistoryStep('redo')}
+                        title={history.redo.length > 0 ? `Redo ${history.redo[0]}` : 'Nothing to redo'}
+                    >
+                        Redo
+                    </div>
                     <div
                         className='show-display'
                         onClick={handleToggleGrid}
@@ -201,11 +298,65 @@ function CombatConstructor() {
                         >
                             Properties
                         </button>
+                        <button
+                            className='properties-button'
+                            onClick={handleDiceButton}
+                            disabled={editorProperties === 'dice'}
+                        >
+                            Dice
+                        </button>
+                        <button
+                            className='properties-button'
+                            onClick={handleLogButton}
+                            disabled={editorProperties === 'log'}
+                        >
+                            Log
+                        </button>
+                        <button
+                            className='properties-button'
+                            onClick={handleSnapshotsButton}
+                            disabled={editorProperties === 'snapshots'}
+                        >
+                            Snapshots
+                        </button>
+                        <button
+                            className='properties-button'
+                            onClick={handleBestiaryButton}
+                            disabled={editorProperties === 'bestiary'}
+                        >
+                            Bestiary
+                        </button>
+                        <button
+                            className='properties-button'
+                            onClick={handleTerrainButton}
+                            disabled={editorProperties === 'terrain'}
+                        >
+                            Terrain
+                        </button>
+                        <button
+                            className='properties-button'
+                            onClick={handleFogButton}
+                            disabled={editorProperties === 'fog'}
+                        >
+                            Fog
+                        </button>
                     </div>
 
                     <div className='editor-properties-container'>
                         {editorProperties === 'editor' ? (
                             <IconEditor battlemap={battlemap} />
+                        ) : editorProperties === 'dice' ? (
+                            <DiceRoller />
+                        ) : editorProperties === 'log' ? (
+                            <CombatLog battlemap={battlemap} />
+                        ) : editorProperties === 'snapshots' ? (
+                            <Snapshots battlemap={battlemap} />
+                        ) : editorProperties === 'bestiary' ? (
+                            <Bestiary battlemap={battlemap} />
+                        ) : editorProperties === 'terrain' ? (
+                            <TerrainEditor combatData={combatData} />
+                        ) : editorProperties === 'fog' ? (
+                            <FogEditor battlemap={battlemap} combatData={combatData} />
                         ) : selectedEntity === '' ? (
                             <div className='icon-properties-placeholder'>
                                 Select entity to view properties
@@ -2,15 +2,27 @@ import { TransformComponent, TransformWrapper } from 'react-zoom-pan-pinch';
 import {
     ChapterData,
     Combat,
+    Coordinates,
     Entity,
+    Fog,
+    FogArea,
+    FogTool,
+    Hex,
+    MovePath,
+    ReachableHex,
+    RollRecord,
+    Terrain,
+    TerrainTool,
+    TurnChangedPayload,
     defaultCombat,
+    formatRollBreakdown,
 } from './GlobalStateContext';
 import { useEffect, useRef, useState, MouseEvent } from 'react';
 import { emit, listen } from '@tauri-apps/api/event';
 import { useParams } from 'react-router-dom';
 import { invoke } from '@tauri-apps/api/core';
 import GridEntity from './GridEntity';
-import { generateGridCenters } from '../hexgrid';
+import { generateGridCenters, hexEdge, hexPolygonPoints, hexToPercent, hexToPixel } from '../hexgrid';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import Button from './Button';
 import '../styles/components/CombatDisplay.css';
@@ -46,10 +58,22 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
     const [isTransformEnabled] = useState(true);
     const [selectedEntity, setSelectedEntity] = useState<Entity | null>(null);
     const [hoveredEntity, setHoveredEntity] = useState<Entity | null>(null);
-    const [arrow, setArrow] = useState<{startGrid: {x: number, y: number}, endGrid: {x: number, y: number}} | null>(null);
+    const [arrow, setArrow] = useState<{startGrid: Hex, endGrid: Hex} | null>(null);
+    const [arrowLength, setArrowLength] = useState(0);
     const [isDrawingArrow, setIsDrawingArrow] = useState(false);
-    const [arrowStartGrid, setArrowStartGrid] = useState<{x: number, y: number} | null>(null);
+    const [arrowStartGrid, setArrowStartGrid] = useState<Hex | null>(null);
+    const [reachableHexes, setReachableHexes] = useState<ReachableHex[]>([]);
+    const [hoveredHex, setHoveredHex] = useState<Hex | null>(null);
+    const [movePath, setMovePath] = useState<MovePath | null>(null);
+    const [terrainTool, setTerrainTool] = useState<TerrainTool | null>(null);
+    const [wallStart, setWallStart] = useState<Hex | null>(null);
+    const [fogTool, setFogTool] = useState<FogTool | null>(null);
+    const [fogPoints, setFogPoints] = useState<Coordinates[]>([]);
+    const [fogMask, setFogMask] = useState<string | null>(null);
+    const [fogVersion, setFogVersion] = useState(0);
     const [flashingEntities, setFlashingEntities] = useState<Map<string, number>>(new Map());
+    const [activeEntity, setActiveEntity] = useState<string | null>(null);
+    const [publicRoll, setPublicRoll] = useState<RollRecord | null>(null);
 
     const containerRef = useRef<HTMLDivElement>(null);
     const [fullscreen, setFullscreen] = useState(false);
@@ -92,6 +116,78 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
         };
     }, []); // Empty dependency array - only run once
 
+    // Highlight the token whose turn it is
+    useEffect(() => {
+        setActiveEntity(combatData.initiative.active);
+    }, [combatData]);
+
+    useEffect(() => {
+        const unlistenTurnChanged = listen<TurnChangedPayload>('turnChanged', (event) => {
+            if (event.payload.chapterId === chapterId && event.payload.battlemap === battlemapId) {
+                setActiveEntity(event.payload.active);
+            }
+        });
+
+        return () => {
+            unlistenTurnChanged.then((unsub) => unsub());
+        };
+    }, [chapterId, battlemapId]);
+
+    // The DM picks terrain tools in the constructor; while one is active, clicks paint instead of moving tokens
+    useEffect(() => {
+        const unlistenTerrainTool = listen<TerrainTool | null>('terrainTool', (event) => {
+            setTerrainTool(event.payload);
+            setWallStart(null);
+            setSelectedEntity(null);
+        });
+
+        return () => {
+            unlistenTerrainTool.then((unsub) => unsub());
+        };
+    }, []);
+
+    // Likewise for the fog of war, whose tools reveal or hide hexes instead
+    useEffect(() => {
+        const unlistenFogTool = listen<FogTool | null>('fogTool', (event) => {
+            setFogTool(event.payload);
+            setFogPoints([]);
+            setSelectedEntity(null);
+        });
+
+        return () => {
+            unlistenFogTool.then((unsub) => unsub());
+        };
+    }, []);
+
+    // The backend renders the fog into an image; render it again whenever the fog or the grid changes
+    useEffect(() => {
+        if (!combatData.fog.enabled) {
+            setFogMask(null);
+            return;
+        }
+        invoke<string>('render_fog_mask', { chapterId: chapterId, battlemap: battlemapId })
+            .then((path) => {
+                setFogMask(path);
+                setFogVersion((previous) => previous + 1);
+            })
+            .catch((error) => console.error('Failed to render fog of war:', error));
+    }, [JSON.stringify(combatData.fog), combatData.gridsize, combatData.gridoffset.x, combatData.gridoffset.y]);
+
+    // Show public rolls to the players for a few seconds
+    useEffect(() => {
+        let hideTimeout: ReturnType<typeof setTimeout> | undefined;
+        const unlistenPublicRoll = listen<RollRecord>('publicRoll', (event) => {
+            setPublicRoll(event.payload);
+            clearTimeout(hideTimeout);
+            hideTimeout = setTimeout(() => setPublicRoll(null), 6000);
+        });
+
+        return () => {
+            clearTimeout(hideTimeout);
+            unlistenPublicRoll.then((unsub) => unsub());
+        };
+    }, []);
+
     useEffect(() => {
         const unlistenChapterData = listen('chapterData', (event) => {
             console.log('chapter data received', event.payload);
@@ -183,44 +279,73 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
         }
     };
 
-    const calculateGridCoordinates = (clientX: number, clientY: number) => {
+    // The display is laid out in pixels of a 1667x953 reference container
+    const referencePoint = (clientX: number, clientY: number): Coordinates => {
         if (!containerRef.current) return { x: 0, y: 0 };
-        
+
         const { left, top, width, height } = containerRef.current.getBoundingClientRect();
-        const percentX = ((clientX - left) / width) * 100;
-        const percentY = ((clientY - top) / height) * 100;
-
-        const y = (() => {
-            let min = Infinity;
-            let nearest = 0;
-            for (let i = 0; i < percentageCenters.y.length; i++) {
-                const distance = Math.abs(percentageCenters.y[i] - percentY);
-                if (distance < min) {
-                    min = distance;
-                    nearest = i;
-                }
-            }
-            return nearest;
-        })();
-
-        const x = (() => {
-            let min = Infinity;
-            let nearest = 0;
-            for (let i = 0; i < percentageCenters.x.length; i++) {
-                const distance = Math.abs(
-                    percentageCenters.x[i] - (percentX - (y % 2 !== 0 ? percentageCenters.offset : 0))
-                );
-                if (distance < min) {
-                    min = distance;
-                    nearest = i;
-                }
-            }
-            return nearest;
-        })();
+        return {
+            x: ((clientX - left) / width) * 1667,
+            y: ((clientY - top) / height) * 953,
+        };
+    };
+
+    // The hex under the cursor
+    const calculateGridCoordinates = (clientX: number, clientY: number): Promise<Hex> => {
+        if (!containerRef.current) return Promise.resolve({ q: 0, r: 0 });
 
-        return { x, y };
+        const point = referencePoint(clientX, clientY);
+        return invoke<Hex>('hex_at', { chapterId: chapterId, battlemap: battlemapId, point: point });
     };
 
+    // While the fog is on, entities on hexes the players haven't seen are hidden, except from the DM editing it
+    const revealedHexes = new Set(combatData.fog.revealed.map((hex) => `${hex.q},${hex.r}`));
+    const isRevealed = (hex: Hex) =>
+        !combatData.fog.enabled || fogTool !== null || revealedHexes.has(`${hex.q},${hex.r}`);
+
+    const hexPosition = (hex: Hex) =>
+        hexToPercent(hex, combatData.gridsize, combatData.gridoffset, 1667, 953);
+
+    // Show where the selected entity can move with its speed
+    useEffect(() => {
+        setHoveredHex(null);
+        if (!selectedEntity) {
+            setReachableHexes([]);
+            return;
+        }
+        invoke<ReachableHex[]>('get_reachable_hexes', {
+            chapterId: chapterId,
+            battlemap: battlemapId,
+            entity: selectedEntity.icon,
+        })
+            .then(setReachableHexes)
+            .catch(() => setReachableHexes([]));
+    }, [selectedEntity]);
+
+    // Preview the way to the hex under the cursor
+    useEffect(() => {
+        if (!selectedEntity || !hoveredHex) {
+            setMovePath(null);
+            return;
+        }
+        invoke<MovePath | null>('find_path', {
+            chapterId: chapterId,
+            battlemap: battlemapId,
+            entity: selectedEntity.icon,
+            to: hoveredHex,
+        })
+            .then(setMovePath)
+            .catch(() => setMovePath(null));
+    }, [hoveredHex]);
+
+    // Show how many hexes the arrow spans
+    useEffect(() => {
+        if (!arrow) return;
+        invoke<Hex[]>('hex_line', { from: arrow.startGrid, to: arrow.endGrid })
+            .then((line) => setArrowLength(line.length - 1))
+            .catch((error) => console.error('Failed to measure arrow:', error));
+    }, [arrow]);
+
     const handleRightClick = (event: MouseEvent<HTMLDivElement>) => {
         // Always prevent default context menu
         event.preventDefault();
@@ -230,8 +355,9 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
         if (event.button === 2 && isDrawingArrow) { // Right mouse button up
             // Finalize arrow
             if (arrowStartGrid) {
-                const endGrid = calculateGridCoordinates(event.clientX, event.clientY);
-                setArrow({ startGrid: arrowStartGrid, endGrid });
+                calculateGridCoordinates(event.clientX, event.clientY).then((endGrid) => {
+                    setArrow({ startGrid: arrowStartGrid, endGrid });
+                });
                 setIsDrawingArrow(false);
                 setArrowStartGrid(null);
             }
@@ -251,8 +377,9 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
             
             // Start drawing new arrow if not currently drawing
             if (!isDrawingArrow) {
-                const startGrid = calculateGridCoordinates(event.clientX, event.clientY);
-                setArrowStartGrid(startGrid);
+                calculateGridCoordinates(event.clientX, event.clientY).then((startGrid) => {
+                    setArrowStartGrid(startGrid);
+                });
                 setIsDrawingArrow(true);
                 setSelectedEntity(null);
             }
@@ -261,21 +388,105 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
 
     const handleMouseMove = (event: MouseEvent<HTMLDivElement>) => {
         if (isDrawingArrow && arrowStartGrid) {
-            const currentGrid = calculateGridCoordinates(event.clientX, event.clientY);
-            setArrow({ startGrid: arrowStartGrid, endGrid: currentGrid });
+            calculateGridCoordinates(event.clientX, event.clientY).then((currentGrid) => {
+                setArrow({ startGrid: arrowStartGrid, endGrid: currentGrid });
+            });
+        } else if (selectedEntity) {
+            calculateGridCoordinates(event.clientX, event.clientY).then((hex) => {
+                setHoveredHex((previous) =>
+                    previous && previous.q === hex.q && previous.r === hex.r ? previous : hex
+                );
+            });
+        }
+    };
+
+    const handleTerrainClick = (tool: TerrainTool, hex: Hex) => {
+        let change: Promise<Terrain> | null = null;
+        if (tool.mode === 'paint') {
+            change = invoke<Terrain>('paint_terrain', { chapterId, battlemap: battlemapId, hexes: [hex], kind: tool.kind });
+        } else if (tool.mode === 'erase') {
+            change = invoke<Terrain>('erase_terrain', { chapterId, battlemap: battlemapId, hexes: [hex] });
+        } else if (!wallStart) {
+            setWallStart(hex);
+        } else {
+            const command = tool.mode === 'wall' ? 'add_walls' : 'remove_walls';
+            change = invoke<Terrain>(command, { chapterId, battlemap: battlemapId, walls: [[wallStart, hex]] });
+            setWallStart(null);
+        }
+
+        change
+            ?.then((terrain) => {
+                setChapterData({
+                    ...chapterData,
+                    combat: chapterData.combat.map((combat) =>
+                        combat.battlemap === battlemapId ? { ...combat, terrain } : combat
+                    ),
+                });
+                emit('terrainChanged');
+            })
+            .catch((error) => console.error('Failed to change terrain:', error));
+    };
+
+    const changeFog = (tool: FogTool, area: FogArea) => {
+        invoke<Fog>(tool.mode === 'reveal' ? 'reveal_fog' : 'hide_fog', { chapterId, battlemap: battlemapId, area })
+            .then((fog) => {
+                setChapterData({
+                    ...chapterData,
+                    combat: chapterData.combat.map((combat) =>
+                        combat.battlemap === battlemapId ? { ...combat, fog } : combat
+                    ),
+                });
+                emit('fogChanged');
+            })
+            .catch((error) => console.error('Failed to change fog of war:', error));
+    };
+
+    // Polygons are drawn corner by corner; clicking near the first corner again closes them
+    const handleFogClick = (tool: FogTool, clientX: number, clientY: number) => {
+        if (tool.shape === 'polygon') {
+            const point = referencePoint(clientX, clientY);
+            const first = fogPoints[0];
+            if (fogPoints.length >= 3 && Math.hypot(point.x - first.x, point.y - first.y) < combatData.gridsize / 2) {
+                changeFog(tool, { shape: 'polygon', points: fogPoints });
+                setFogPoints([]);
+            } else {
+                setFogPoints([...fogPoints, point]);
+            }
+            return;
         }
+        calculateGridCoordinates(clientX, clientY).then((hex) =>
+            changeFog(
+                tool,
+                tool.shape === 'radius' ? { shape: 'radius', center: hex, radius: tool.radius } : { shape: 'hex', hex }
+            )
+        );
     };
 
     const handleLeftClick = (event: MouseEvent<HTMLDivElement>) => {
         if (event.button !== 0) return;
+        if (fogTool !== null) {
+            handleFogClick(fogTool, event.clientX, event.clientY);
+            return;
+        }
+        if (terrainTool !== null) {
+            const tool = terrainTool;
+            calculateGridCoordinates(event.clientX, event.clientY).then((hex) => handleTerrainClick(tool, hex));
+            return;
+        }
         if (selectedEntity !== null) {
             console.log('entity selected:', selectedEntity);
             
-            const newLocation = calculateGridCoordinates(event.clientX, event.clientY);
-            console.log(`Clicked at: ${newLocation.x}, ${newLocation.y}`);
-
-            selectedEntity.location = newLocation;
-            emit('entityLocationUpdate', selectedEntity);
+            calculateGridCoordinates(event.clientX, event.clientY).then((newLocation) => {
+                console.log(`Clicked at: ${newLocation.q}, ${newLocation.r}`);
+
+                selectedEntity.location = newLocation;
+                emit('entityLocationUpdate', {
+                    chapterId: chapterId,
+                    battlemap: battlemapId,
+                    entity: selectedEntity.icon,
+                    location: newLocation,
+                });
+            });
             setSelectedEntity(null);
         }
     };
@@ -307,6 +518,13 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
 
     return (
         <div className='no-anti-alias'>
+            {publicRoll && (
+                <div className='public-roll'>
+                    <div className='public-roll-total'>{publicRoll.roll.total}</div>
+                    <div>{publicRoll.roll.expression}</div>
+                    <div className='public-roll-breakdown'>{formatRollBreakdown(publicRoll.roll)}</div>
+                </div>
+            )}
             <TransformWrapper 
                 disabled={!isTransformEnabled}
                 panning={{ 
@@ -341,16 +559,111 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
                                         alt=''
                                     />
                                     {gridVisible && (
-                                        <div className='hexgrid-container'>
+                                        <div
+                                            className='hexgrid-container'
+                                            style={{
+                                                transform: `translate(${
+                                                    (combatData.gridoffset.x / 1667) * 100
+                                                }%, ${(combatData.gridoffset.y / 953) * 100}%)`,
+                                            }}
+                                        >
                                             <img
                                                 src={`../tableau/assets/hexgrids/${combatData.battlemap}?reload=${reload}`}
                                                 className='hexgrid-image'
                                             />
                                         </div>
                                     )}
+                                    {/* Terrain Overlay, only while the DM edits it */}
+                                    {terrainTool && (
+                                        <svg
+                                            className='terrain-overlay'
+                                            viewBox='0 0 1667 953'
+                                            preserveAspectRatio='none'
+                                        >
+                                            {combatData.terrain.tiles.map((tile) => (
+                                                <polygon
+                                                    key={`terrain-${tile.hex.q}-${tile.hex.r}`}
+                                                    points={hexPolygonPoints(tile.hex, combatData.gridsize, combatData.gridoffset)}
+                                                    className={`terrain-${tile.kind}`}
+                                                />
+                                            ))}
+                                            {wallStart && (
+                                                <polygon
+                                                    points={hexPolygonPoints(wallStart, combatData.gridsize, combatData.gridoffset)}
+                                                    className='terrain-wall-start'
+                                                />
+                                            )}
+                                            {combatData.terrain.walls.map(([a, b]) => {
+                                                const edge = hexEdge(a, b, combatData.gridsize, combatData.gridoffset);
+                                                return (
+                                                    <line
+                                                        key={`wall-${a.q}-${a.r}-${b.q}-${b.r}`}
+                                                        {...edge}
+                                                        className='terrain-wall'
+                                                    />
+                                                );
+                                            })}
+                                        </svg>
+                                    )}
+                                    {/* Fog of War, see-through while the DM edits it */}
+                                    {fogMask && (
+                                        <img
+                                            src={`../tableau/assets/fog/${fogMask}?v=${fogVersion}`}
+                                            className={`fog-overlay ${fogTool ? 'fog-overlay-editing' : ''}`}
+                                            alt=''
+                                        />
+                                    )}
+                                    {fogTool && fogPoints.length > 0 && (
+                                        <svg
+                                            className='fog-polygon-overlay'
+                                            viewBox='0 0 1667 953'
+                                            preserveAspectRatio='none'
+                                        >
+                                            <polyline
+                                                points={fogPoints.map((point) => `${point.x},${point.y}`).join(' ')}
+                                                className='fog-polygon'
+                                            />
+                                            <circle cx={fogPoints[0].x} cy={fogPoints[0].y} r={combatData.gridsize / 2} className='fog-polygon-start' />
+                                        </svg>
+                                    )}
+                                    {/* Movement Overlay */}
+                                    {selectedEntity && (
+                                        <svg
+                                            className='movement-overlay'
+                                            viewBox='0 0 1667 953'
+                                            preserveAspectRatio='none'
+                                        >
+                                            {reachableHexes.map((reachable) => (
+                                                <polygon
+                                                    key={`reachable-${reachable.hex.q}-${reachable.hex.r}`}
+                                                    points={hexPolygonPoints(reachable.hex, combatData.gridsize, combatData.gridoffset)}
+                                                    className='movement-reachable'
+                                                />
+                                            ))}
+                                            {movePath && movePath.hexes.length > 1 && (
+                                                <>
+                                                    <polyline
+                                                        points={movePath.hexes
+                                                            .map((hex) => hexToPixel(hex, combatData.gridsize, combatData.gridoffset))
+                                                            .map((point) => `${point.x},${point.y}`)
+                                                            .join(' ')}
+                                                        className='movement-path'
+                                                    />
+                                                    <text
+                                                        x={hexToPixel(movePath.hexes[movePath.hexes.length - 1], combatData.gridsize, combatData.gridoffset).x}
+                                                        y={hexToPixel(movePath.hexes[movePath.hexes.length - 1], combatData.gridsize, combatData.gridoffset).y}
+                                                        dy='-1em'
+                                                        className='arrow-length'
+                                                    >
+                                                        {movePath.feet} ft
+                                                    </text>
+                                                </>
+                                            )}
+                                        </svg>
+                                    )}
                                     {entitiesVisible && entityData
                                         .filter(
-                                            (entity) => entity.visible === true
+                                            (entity) => entity.visible === true && isRevealed(entity.location)
                                         )
                                         .map((entity, index) => (
                                             <div
@@ -362,21 +675,8 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
                                                         : ''
                                                 }`}
                                                 style={{
-                                                    left: `${
-                                                        percentageCenters.x[
-                                                            entity.location.x
-                                                        ] +
-                                                        (entity.location.y %
-                                                            2 !==
-                                                        0
-                                                            ? percentageCenters.offset
-                                                            : 0)
-                                                    }%`,
-                                                    top: `${
-                                                        percentageCenters.y[
-                                                            entity.location.y
-                                                        ]
-                                                    }%`,
+                                                    left: `${hexPosition(entity.location).x}%`,
+                                                    top: `${hexPosition(entity.location).y}%`,
                                                     height: `${
                                                         gridsizePercentage * 2.15
                                                     }%`,
@@ -391,13 +691,16 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
                                                 onMouseLeave={() => setHoveredEntity(null)}
                                                 onContextMenu={handleRightClick}
                                             >
-                                                <GridEntity entity={entity} />
+                                                <GridEntity
+                                                    entity={entity}
+                                                    active={activeEntity === entity.icon.replace(/\.png$/, '.json')}
+                                                />
                                             </div>
                                         ))}
                                     {/* Health Bar Overlay Layer */}
                                     {entitiesVisible && entityData
                                         .filter(
-                                            (entity) => entity.visible === true
+                                            (entity) => entity.visible === true && isRevealed(entity.location)
                                         )
                                         .map((entity, index) => {
                                             const hp = entity.hitpoints.current / entity.hitpoints.max;
@@ -439,20 +742,10 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
                                                     className='healthbar'
                                                     style={{
                                                         position: 'absolute',
-                                                        left: `${
-                                                            percentageCenters.x[
-                                                                entity.location.x
-                                                            ] +
-                                                            (entity.location.y %
-                                                                2 !==
-                                                            0
-                                                                ? percentageCenters.offset
-                                                                : 0)
-                                                        }%`,
+                                                        left: `${hexPosition(entity.location).x}%`,
                                                         top: `${
-                                                            percentageCenters.y[
-                                                                entity.location.y
-                                                            ] + (gridsizePercentage * 2.15 * 0.55)
+                                                            hexPosition(entity.location).y +
+                                                            (gridsizePercentage * 2.15 * 0.55)
                                                         }%`,
                                                         width: `${
                                                             (entity.hitpoints.current / entity.hitpoints.max) *
@@ -499,20 +792,22 @@ function CombatDisplay({ chapterId: propChapterId, battlemapId: propBattlemapId
                                                 </marker>
                                             </defs>
                                             <line
-                                                x1={`${
-                                                    percentageCenters.x[arrow.startGrid.x] +
-                                                    (arrow.startGrid.y % 2 !== 0 ? percentageCenters.offset : 0)
-                                                }%`}
-                                                y1={`${percentageCenters.y[arrow.startGrid.y]}%`}
-                                                x2={`${
-                                                    percentageCenters.x[arrow.endGrid.x] +
-                                                    (arrow.endGrid.y % 2 !== 0 ? percentageCenters.offset : 0)
-                                                }%`}
-                                                y2={`${percentageCenters.y[arrow.endGrid.y]}%`}
+                                                x1={`${hexPosition(arrow.startGrid).x}%`}
+                                                y1={`${hexPosition(arrow.startGrid).y}%`}
+                                                x2={`${hexPosition(arrow.endGrid).x}%`}
+                                                y2={`${hexPosition(arrow.endGrid).y}%`}
                                                 stroke="white"
                                                 strokeWidth="3"
                                                 markerEnd="url(#arrowhead)"
                                             />
+                                            <text
+                                                x={`${hexPosition(arrow.endGrid).x}%`}
+                                                y={`${hexPosition(arrow.endGrid).y}%`}
+                                                dy="-1em"
+                                                className='arrow-length'
+                                            >
+                                                {arrowLength}
+                                            </text>
                                         </svg>
                                     )}
                                 </div>
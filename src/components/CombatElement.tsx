@@ -41,7 +41,7 @@ function CombatElement({ filename, reloadChapterData }: { filename: string, relo
   }, [menuVisible]);
 
   const handleRemove = () => {
-    invoke('remove_combat', { chapterId, battlemap: filename })
+    invoke('remove_combat', { chapterId, battlemap: filename, deleteEntities: true })
       .then(() => reloadChapterData())
       .catch(error => console.error(error));
   };
@@ -0,0 +1,109 @@
+import { invoke } from '@tauri-apps/api/core';
+import { useEffect, useState } from 'react';
+import { CombatEvent, CombatSummary, TableauError, useGlobalState } from './GlobalStateContext';
+import '../styles/components/CombatLog.css';
+
+interface props {
+    battlemap: string;
+}
+
+const entityLabel = (entity: string, name: string) => name || entity.replace(/\.json$/, '');
+
+// One line per event, e.g. `Goblin took 7 fire damage (resistant)`
+const describeEvent = (event: CombatEvent): string => {
+    switch (event.type) {
+        case 'movement':
+            return `${entityLabel(event.entity, event.name)} moved to (${event.to.q}, ${event.to.r})`;
+        case 'damage': {
+            const type = event.change.damageType ? ` ${event.change.damageType}` : '';
+            const modifier = event.change.modifier ? ` (${event.change.modifier})` : '';
+            return `${entityLabel(event.entity, event.name)} took ${event.change.effective}${type} damage${modifier}`;
+        }
+        case 'healing':
+            return `${entityLabel(event.entity, event.name)} was healed for ${event.change.effective}`;
+        case 'death':
+            return `${entityLabel(event.entity, event.name)} died`;
+        case 'condition_applied':
+            return `${entityLabel(event.entity, event.name)} is ${event.condition.label || event.condition.kind}`;
+        case 'condition_removed':
+            return `${entityLabel(event.entity, event.name)} is no longer ${event.condition.label || event.condition.kind}${event.expired ? ' (expired)' : ''}`;
+        case 'turn_change':
+            return event.active ? `Turn: ${event.active.replace(/\.json$/, '')}` : 'Initiative cleared';
+    }
+};
+
+function CombatLog({ battlemap }: props) {
+    const { chapterId } = useGlobalState();
+    const [events, setEvents] = useState<CombatEvent[]>([]);
+    const [summary, setSummary] = useState<CombatSummary | null>(null);
+    const [message, setMessage] = useState('');
+
+    const reload = () => {
+        invoke<CombatEvent[]>('get_combat_log', { chapterId, battlemap }).then((log) => {
+            setEvents(log.reverse());
+        });
+        invoke<CombatSummary>('get_combat_summary', { chapterId, battlemap }).then(setSummary);
+    };
+
+    useEffect(reload, [chapterId, battlemap]);
+
+    const handleExport = () => {
+        invoke<string>('export_combat_summary', { chapterId, battlemap })
+            .then((path) => setMessage(`Saved to ${path}`))
+            .catch((err: TableauError) => {
+                if (err.code !== 'cancelled') {
+                    setMessage(err.message);
+                }
+            });
+    };
+
+    return (
+        <div className='combat-log'>
+            <div className='combat-log-buttons'>
+                <div className='show-display' onClick={reload}>
+                    Refresh
+                </div>
+                <div className='show-display' onClick={handleExport}>
+                    Export Summary
+                </div>
+            </div>
+            {message !== '' && <div className='combat-log-message'>{message}</div>}
+            {summary && (
+                <table className='combat-log-summary'>
+                    <thead>
+                        <tr>
+                            <th>Entity</th>
+                            <th>Dealt</th>
+                            <th>Taken</th>
+                            <th>Healed</th>
+                            <th>Kills</th>
+                            <th>Moved</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {summary.entities.map((entity) => (
+                            <tr key={entity.entity} className={entity.died ? 'combat-log-died' : ''}>
+                                <td>{entityLabel(entity.entity, entity.name)}</td>
+                                <td>{entity.damageDealt}</td>
+                                <td>{entity.damageTaken}</td>
+                                <td>{entity.healingReceived}</td>
+                                <td>{entity.kills}</td>
+                                <td>{entity.hexesMoved}</td>
+                            </tr>
+                        ))}
+                    </tbody>
+                </table>
+            )}
+            <div className='combat-log-events'>
+                {events.map((event, index) => (
+                    <div key={`${event.timestamp}-${index}`} className='combat-log-entry'>
+                        <span className='combat-log-round'>R{event.round}</span>
+                        <span>{describeEvent(event)}</span>
+                    </div>
+                ))}
+            </div>
+        </div>
+    );
+}
+
+export default CombatLog;
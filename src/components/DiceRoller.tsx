@@ -0,0 +1,83 @@
+import { invoke } from '@tauri-apps/api/core';
+import { useEffect, useState } from 'react';
+import { RollRecord, TableauError, formatRollBreakdown, useGlobalState } from './GlobalStateContext';
+import '../styles/components/DiceRoller.css';
+
+function DiceRoller() {
+    const { chapterId } = useGlobalState();
+    const [expression, setExpression] = useState('');
+    const [isPublic, setIsPublic] = useState(false);
+    const [rolls, setRolls] = useState<RollRecord[]>([]);
+    const [error, setError] = useState('');
+
+    // Rolls made in this chapter during the current session
+    useEffect(() => {
+        invoke<RollRecord[]>('get_roll_log', { chapterId }).then((log) => {
+            setRolls(log.reverse());
+        });
+    }, [chapterId]);
+
+    const handleRoll = () => {
+        if (expression.trim() === '') {
+            return;
+        }
+        invoke<RollRecord>('roll_dice', { chapterId, expression, public: isPublic })
+            .then((record) => {
+                setRolls([record, ...rolls]);
+                setError('');
+            })
+            .catch((err: TableauError) => {
+                setError(err.message);
+            });
+    };
+
+    const handleKeyDown = (event: React.KeyboardEvent<HTMLInputElement>) => {
+        if (event.key === 'Enter') {
+            handleRoll();
+        }
+    };
+
+    return (
+        <div className='dice-roller'>
+            <div className='dice-roller-inputs'>
+                <input
+                    type='text'
+                    className='dice-roller-expression'
+                    value={expression}
+                    onChange={(event) => setExpression(event.target.value)}
+                    onKeyDown={handleKeyDown}
+                    placeholder='2d6+3, 1d20 adv, 8d6 fire'
+                />
+                <label className='dice-roller-public'>
+                    <input
+                        type='checkbox'
+                        checked={isPublic}
+                        onChange={() => setIsPublic(!isPublic)}
+                    />
+                    Public
+                </label>
+                <div
+                    className='show-display'
+                    onClick={handleRoll}
+                >
+                    Roll
+                </div>
+            </div>
+            {error !== '' && <div className='dice-roller-error'>{error}</div>}
+            <div className='dice-roller-log'>
+                {rolls.map((record) => (
+                    <div
+                        key={`${record.timestamp}-${record.seed}`}
+                        className='dice-roller-entry'
+                    >
+                        <span className='dice-roller-total'>{record.roll.total}</span>
+                        <span>{record.roll.expression}</span>
+                        <span className='dice-roller-breakdown'>{formatRollBreakdown(record.roll)}</span>
+                    </div>
+                ))}
+            </div>
+        </div>
+    );
+}
+
+export default DiceRoller;
@@ -0,0 +1,153 @@
+import { invoke } from '@tauri-apps/api/core';
+import { emit, listen } from '@tauri-apps/api/event';
+import { useEffect, useState } from 'react';
+import { Combat, Fog, FogMode, FogTool, TableauError, useGlobalState, useReloadChapterData } from './GlobalStateContext';
+import '../styles/components/FogEditor.css';
+
+interface props {
+    battlemap: string;
+    combatData: Combat;
+}
+
+const MODES: { name: FogMode; label: string }[] = [
+    { name: 'manual', label: 'Manual' },
+    { name: 'dynamic', label: 'Dynamic' },
+    { name: 'sticky', label: 'Sticky' },
+];
+
+const SHAPES: { name: FogTool['shape']; label: string }[] = [
+    { name: 'hex', label: 'Hex' },
+    { name: 'radius', label: 'Radius' },
+    { name: 'polygon', label: 'Polygon' },
+];
+
+// Turns the fog of war on and picks what clicks on the display reveal or hide
+function FogEditor({ battlemap, combatData }: props) {
+    const { chapterId } = useGlobalState();
+    const reloadChapterData = useReloadChapterData();
+    const [mode, setMode] = useState<FogTool['mode'] | null>(null);
+    const [shape, setShape] = useState<FogTool['shape']>('hex');
+    const [radius, setRadius] = useState(3);
+    const [visionRadius, setVisionRadius] = useState(combatData.fog.vision_radius);
+    const [error, setError] = useState('');
+
+    useEffect(() => {
+        emit('fogTool', mode === null ? null : { mode, shape, radius });
+    }, [mode, shape, radius]);
+
+    // Leave the display in its normal mode when the tab is closed
+    useEffect(() => {
+        const unlistenFogChanged = listen('fogChanged', () => reloadChapterData());
+
+        return () => {
+            emit('fogTool', null);
+            unlistenFogChanged.then((unsub) => unsub());
+        };
+    }, []);
+
+    const handleToggleFog = () => {
+        invoke<Fog>('set_fog_enabled', { chapterId, battlemap, enabled: !combatData.fog.enabled })
+            .then(() => {
+                reloadChapterData();
+                setError('');
+            })
+            .catch((err: TableauError) => setError(err.message));
+    };
+
+    // Dynamic and sticky fog follow what friendly entities see as they move
+    const changeVision = (mode: FogMode, visionRadius: number) => {
+        invoke<Fog>('set_fog_vision', { chapterId, battlemap, mode, visionRadius })
+            .then(() => {
+                reloadChapterData();
+                setError('');
+            })
+            .catch((err: TableauError) => setError(err.message));
+    };
+
+    return (
+        <div className='fog-editor'>
+            <div className='fog-editor-tools'>
+                <div
+                    className={`show-display fog-tool ${combatData.fog.enabled ? 'fog-tool-active' : ''}`}
+                    onClick={handleToggleFog}
+                >
+                    {combatData.fog.enabled ? 'Fog On' : 'Fog Off'}
+                </div>
+            </div>
+            <div className='fog-editor-tools'>
+                {MODES.map(({ name, label }) => (
+                    <div
+                        key={name}
+                        className={`show-display fog-tool ${combatData.fog.mode === name ? 'fog-tool-active' : ''}`}
+                        onClick={() => changeVision(name, visionRadius)}
+                    >
+                        {label}
+                    </div>
+                ))}
+            </div>
+            {combatData.fog.mode !== 'manual' && (
+                <label className='fog-editor-vision'>
+                    Vision (ft):
+                    <input
+                        type='number'
+                        className='fog-editor-radius'
+                        min={0}
+                        max={250}
+                        step={5}
+                        value={visionRadius}
+                        onChange={(event) => setVisionRadius(Math.max(0, parseInt(event.target.value) || 0))}
+                        onBlur={() => changeVision(combatData.fog.mode, visionRadius)}
+                    />
+                </label>
+            )}
+            <div className='fog-editor-tools'>
+                <div
+                    className={`show-display fog-tool ${mode === 'reveal' ? 'fog-tool-active' : ''}`}
+                    onClick={() => setMode(mode === 'reveal' ? null : 'reveal')}
+                >
+                    Reveal
+                </div>
+                <div
+                    className={`show-display fog-tool ${mode === 'hide' ? 'fog-tool-active' : ''}`}
+                    onClick={() => setMode(mode === 'hide' ? null : 'hide')}
+                >
+                    Hide
+                </div>
+            </div>
+            <div className='fog-editor-tools'>
+                {SHAPES.map(({ name, label }) => (
+                    <div
+                        key={name}
+                        className={`show-display fog-tool ${shape === name ? 'fog-tool-active' : ''}`}
+                        onClick={() => setShape(name)}
+                    >
+                        {label}
+                    </div>
+                ))}
+            </div>
+            {shape === 'radius' && (
+                <input
+                    type='number'
+                    className='fog-editor-radius'
+                    min={0}
+                    max={50}
+                    value={radius}
+                    onChange={(event) => setRadius(Math.max(0, parseInt(event.target.value) || 0))}
+                />
+            )}
+            <div className='fog-editor-hint'>
+                {mode === null
+                    ? 'Pick Reveal or Hide, then click on the display.'
+                    : shape === 'polygon'
+                    ? 'Click the corners on the display, then click the first one again to close the polygon.'
+                    : 'Click hexes on the display.'}
+            </div>
+            {error && <div className='fog-editor-error'>{error}</div>}
+            <div className='fog-editor-summary'>
+                {combatData.fog.revealed.length} revealed hexes
+            </div>
+        </div>
+    );
+}
+
+export default FogEditor;
@@ -13,9 +13,38 @@ export interface Coordinates {
   y: number;
 }
 
+// A hex of a battlemap's grid in axial coordinates: `r` is the row, `q` counts hexes along it
+export interface Hex {
+  q: number;
+  r: number;
+}
+
 export interface Hitpoints {
   current: number;
   max: number;
+  temporary: number;
+}
+
+export interface AbilityScores {
+  strength: number;
+  dexterity: number;
+  constitution: number;
+  intelligence: number;
+  wisdom: number;
+  charisma: number;
+}
+
+export type TableauErrorCode = 'not_found' | 'already_exists' | 'invalid_data' | 'io' | 'cancelled' | 'validation';
+
+// Error object rejected by every backend command
+export interface TableauError {
+  code: TableauErrorCode;
+  message: string;
+  context: {
+    chapterId?: string;
+    battlemap?: string;
+    path?: string;
+  };
 }
 
 export interface Combat {
@@ -25,6 +54,77 @@ export interface Combat {
   gridsize: number;
   gridoffset: Coordinates;
   entities: string[];
+  initiative: Initiative;
+  terrain: Terrain;
+  fog: Fog;
+}
+
+// What is painted on a hex; hazards deal `damage`, a dice expression, to whoever enters them
+export type TerrainKind =
+  | { kind: 'blocked' }
+  | { kind: 'difficult' }
+  | { kind: 'water' }
+  | { kind: 'hazard'; damage: string };
+
+export type TerrainTile = { hex: Hex } & TerrainKind;
+
+export interface Terrain {
+  tiles: TerrainTile[];
+  walls: [Hex, Hex][];      // each between two neighboring hexes
+}
+
+// What clicking the display does while the DM edits terrain
+export type TerrainTool =
+  | { mode: 'paint'; kind: TerrainKind }
+  | { mode: 'erase' }
+  | { mode: 'wall' }
+  | { mode: 'remove_wall' };
+
+// While enabled, the display hides every hex that has not been revealed
+export interface Fog {
+  enabled: boolean;
+  revealed: Hex[];
+  mode: FogMode;
+  vision_radius: number;    // feet the party sees in the combat's light
+}
+
+// manual: only the DM reveals hexes; dynamic: exactly what the party sees now; sticky: what it has ever seen
+export type FogMode = 'manual' | 'dynamic' | 'sticky';
+
+// Hexes to reveal or hide; polygon points are pixels of the display's reference container
+export type FogArea =
+  | { shape: 'hex'; hex: Hex }
+  | { shape: 'radius'; center: Hex; radius: number }
+  | { shape: 'polygon'; points: Coordinates[] };
+
+// What clicking the display does while the DM edits the fog of war
+export interface FogTool {
+  mode: 'reveal' | 'hide';
+  shape: 'hex' | 'radius' | 'polygon';
+  radius: number;
+}
+
+export interface InitiativeEntry {
+  entity: string;       // entity file, `<id>.json`
+  initiative: number;
+  bonus: number;
+  delayed: boolean;
+  tiebreak?: number;    // keeps a resumed entity right before the one it interrupted
+}
+
+export interface Initiative {
+  order: InitiativeEntry[];
+  active: string | null;
+  round: number;
+}
+
+export interface TurnChangedPayload {
+  chapterId: string;
+  battlemap: string;
+  previous: string | null;
+  active: string | null;
+  round: number;
+  roundsStarted: number;
 }
 
 export const defaultCombat:Combat = {
@@ -39,18 +139,247 @@ export const defaultCombat:Combat = {
     x: 0,
     y: 0
   },
-  entities: []          // empty array for entities
+  entities: [],         // empty array for entities
+  initiative: {         // no turn order until initiative is rolled
+    order: [],
+    active: null,
+    round: 0
+  },
+  terrain: {            // nothing painted
+    tiles: [],
+    walls: []
+  },
+  fog: {                // no fog of war
+    enabled: false,
+    revealed: [],
+    mode: 'manual',
+    vision_radius: 60
+  }
+};
+
+export type ConditionKind =
+  | 'blinded'
+  | 'charmed'
+  | 'deafened'
+  | 'exhaustion'
+  | 'frightened'
+  | 'grappled'
+  | 'incapacitated'
+  | 'invisible'
+  | 'paralyzed'
+  | 'petrified'
+  | 'poisoned'
+  | 'prone'
+  | 'restrained'
+  | 'stunned'
+  | 'unconscious'
+  | 'custom';
+
+export type ExpiryTrigger = 'start_of_turn' | 'end_of_turn';
+
+export interface Condition {
+  kind: ConditionKind;
+  label: string;            // name of a custom condition, empty otherwise
+  duration: number | null;  // rounds left, null until removed by hand
+  source: string;
+  expires: ExpiryTrigger;
+}
+
+export interface ConditionExpiredPayload {
+  entity: string;           // entity file, `<id>.json`
+  name: string;
+  condition: Condition;
+}
+
+export const DAMAGE_TYPES = [
+  'acid',
+  'bludgeoning',
+  'cold',
+  'fire',
+  'force',
+  'lightning',
+  'necrotic',
+  'piercing',
+  'poison',
+  'psychic',
+  'radiant',
+  'slashing',
+  'thunder',
+] as const;
+
+export type DamageType = (typeof DAMAGE_TYPES)[number];
+
+export interface HitpointChange {
+  amount: number;           // before resistances
+  damageType: DamageType | null;
+  modifier: 'immune' | 'resistant' | 'vulnerable' | null;
+  effective: number;        // damage taken or hitpoints restored
+  temporaryAbsorbed: number;
+  before: Hitpoints;
+  after: Hitpoints;
+  died: boolean;
+  fellDying: boolean;
+  revived: boolean;
+}
+
+export interface EntityLocationUpdate {
+  chapterId: string;
+  battlemap: string;
+  entity: string;           // icon file, `<id>.png`
+  location: Hex;
+}
+
+// A hex an entity can end its move on; `cost` is in hexes of movement
+export interface ReachableHex {
+  hex: Hex;
+  cost: number;
+}
+
+export interface MovePath {
+  hexes: Hex[];             // both ends included
+  cost: number;
+  feet: number;
+}
+
+// Entries of a combat's log; `entity` and `source` are entity files, `<id>.json`
+export type CombatEventKind =
+  | { type: 'movement'; entity: string; name: string; from: Hex; to: Hex }
+  | { type: 'damage'; entity: string; name: string; source: string | null; change: HitpointChange }
+  | { type: 'healing'; entity: string; name: string; source: string | null; change: HitpointChange }
+  | { type: 'death'; entity: string; name: string; source: string | null }
+  | { type: 'condition_applied'; entity: string; name: string; condition: Condition }
+  | { type: 'condition_removed'; entity: string; name: string; condition: Condition; expired: boolean }
+  | { type: 'turn_change'; previous: string | null; active: string | null };
+
+export type CombatEvent = CombatEventKind & {
+  timestamp: number;        // milliseconds since the Unix epoch
+  round: number;
+  step?: number;            // undo step the event was recorded with
 };
 
+export interface EntitySummary {
+  entity: string;
+  name: string;
+  damageDealt: number;
+  damageTaken: number;
+  healingReceived: number;
+  kills: number;
+  died: boolean;
+  hexesMoved: number;
+}
+
+export interface CombatSummary {
+  battlemap: string;
+  rounds: number;
+  entities: EntitySummary[];
+  deaths: { round: number; entity: string; source: string | null }[];
+}
+
+export interface SnapshotInfo {
+  name: string;
+  created: number;          // milliseconds since the Unix epoch
+  entityCount: number;
+  round: number;
+}
+
+// Labels of the chapter's undoable and redoable changes, most recent first
+export interface HistoryInfo {
+  undo: string[];
+  redo: string[];
+}
+
+export interface DieRoll {
+  sides: number;
+  value: number;
+  kept: boolean;            // false for dice dropped by kh/kl or advantage
+}
+
+export interface TermRoll {
+  term: string;             // e.g. `4d6kh3` or `3`
+  sign: number;
+  dice: DieRoll[];
+  value: number;
+}
+
+export interface RollResult {
+  expression: string;
+  total: number;
+  terms: TermRoll[];
+  advantage: 'advantage' | 'disadvantage' | null;
+  damage_type: string | null;
+}
+
+export interface RollRecord {
+  session: string;
+  timestamp: number;        // milliseconds since the Unix epoch
+  seed: number;
+  public: boolean;
+  roll: RollResult;
+}
+
+// Per-die breakdown such as `4d6kh3 [4, (2), 4, 6] + 3`, with dropped dice in parentheses
+export const formatRollBreakdown = (roll: RollResult): string =>
+  roll.terms
+    .map((term, index) => {
+      const sign = term.sign < 0 ? '- ' : index > 0 ? '+ ' : '';
+      const dice = term.dice.map((die) => (die.kept ? `${die.value}` : `(${die.value})`)).join(', ');
+      return term.dice.length > 0 ? `${sign}${term.term} [${dice}]` : `${sign}${term.term}`;
+    })
+    .join(' ');
+
 export interface Entity {
+  name: string;
   icon: string;
   allegiance: string;
   size: string;
-  location: Coordinates;
+  location: Hex;
   hitpoints: Hitpoints;
+  armor_class: number;
+  speed: number;
+  darkvision: number;       // feet seen in the dark, 0 without darkvision
+  initiative_bonus: number;
+  abilities: AbilityScores;
   visible: boolean;
   dead: boolean;
-  modifiers: string;
+  player: boolean;          // player characters at 0 hitpoints are dying rather than dead
+  dying: boolean;
+  resistances: DamageType[];
+  vulnerabilities: DamageType[];
+  immunities: DamageType[];
+  conditions: Condition[];
+  dm_notes: string; // DM only, never shown on the player display
+  template: string | null;  // bestiary template the entity was made from
+}
+
+// The properties update_entity changes; fields left out stay as they are
+export type EntityPatch = Partial<
+  Omit<Entity, 'icon' | 'location' | 'hitpoints' | 'dying' | 'conditions' | 'template'>
+> & {
+  hitpoints?: Partial<Hitpoints>;
+};
+
+// What an instance takes from its template and keeps in step with it
+export interface TemplateStats {
+  max_hitpoints: number;
+  armor_class: number;
+  speed: number;
+  darkvision: number;
+  initiative_bonus: number;
+  abilities: AbilityScores;
+  player: boolean;
+  resistances: DamageType[];
+  vulnerabilities: DamageType[];
+  immunities: DamageType[];
+  dm_notes: string;
+}
+
+export interface Template {
+  id: string;
+  icon: string;             // file in the campaign's bestiary/icons
+  name: string;
+  allegiance: string;
+  size: string;
+  stats: TemplateStats;
 }
 
 export interface EntityData {
@@ -227,11 +556,12 @@ export const GlobalStateProvider = ({ children }: { children: ReactNode }) => {
       emit('battlemapId', battlemapId);
     });
 
-    const unlistenEntityLocationUpdate = listen('entityLocationUpdate', (event) => {
-      const newEntityData = event.payload as Entity;
-      invoke('update_entity', { entity: newEntityData })
+    // Moves go through the backend so they end up in the combat log
+    const unlistenEntityLocationUpdate = listen<EntityLocationUpdate>('entityLocationUpdate', (event) => {
+      invoke('move_entity', { ...event.payload })
         .then(() => {
-          reloadEntityData(battlemapId);
+          reloadEntityData(event.payload.battlemap);
+          reloadChapterData(); // the fog of war may follow the party's vision
         });
     });
 
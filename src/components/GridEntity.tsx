@@ -4,9 +4,10 @@ import '../styles/components/GridEntity.css';
 
 interface Props {
     entity: Entity;
+    active?: boolean;
 }
 
-function GridEntity({ entity }: Props) {
+function GridEntity({ entity, active = false }: Props) {
     const [isHovering, setIsHovering] = useState(false);
     const [hpColour, setHpColour] = useState('rgba(0, 0, 0, 0)');
 
@@ -43,7 +44,7 @@ function GridEntity({ entity }: Props) {
 
     return (
         <div
-            className={`grid-entity-container`}
+            className={`grid-entity-container${active ? ' grid-entity-active' : ''}`}
             style={{
                 filter: entity.dead ? 'grayscale(100%)' : 'none',
             }}
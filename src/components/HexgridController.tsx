@@ -21,9 +21,9 @@ function HexgridController({ combatData }: props) {
     };
 
     const sendSizeChange = (size: number) => {
-        invoke('update_grid_size', { chapterId, battlemap: combatData.battlemap, size: size })
+        invoke('patch_combat', { chapterId, battlemap: combatData.battlemap, patch: { gridsize: size } })
             .then(()=> {
-                invoke('generate_hexgrid', {containerWidth: 1667, containerHeight: 953, hexSize: size, overflow: 3, outputPath: `../tableau/assets/hexgrids/${combatData.battlemap}`})
+                invoke('generate_hexgrid', {battlemap: combatData.battlemap, containerWidth: 1667, containerHeight: 953, hexSize: size, overflow: 3})
                     .then(() => reloadChapterData());
             });
     };
@@ -39,7 +39,7 @@ function HexgridController({ combatData }: props) {
     };
 
     const sendXOffsetChange = (xoffset: number) => {
-        invoke('update_grid_xoffset', { chapterId, battlemap: combatData.battlemap, xoffset: xoffset })
+        invoke('patch_combat', { chapterId, battlemap: combatData.battlemap, patch: { gridoffset: { x: xoffset } } })
             .then(()=> {
                 reloadChapterData();
             });
@@ -56,7 +56,7 @@ function HexgridController({ combatData }: props) {
     };
 
     const sendYOffsetChange = (yoffset: number) => {
-        invoke('update_grid_yoffset', { chapterId, battlemap: combatData.battlemap, yoffset: yoffset })
+        invoke('patch_combat', { chapterId, battlemap: combatData.battlemap, patch: { gridoffset: { y: yoffset } } })
             .then(()=> {
                 reloadChapterData();
             });
@@ -80,7 +80,7 @@ function HexgridController({ combatData }: props) {
                         <p>Size: {size}</p>
                         <input
                             type="range"
-                            min="0"
+                            min="1"
                             max="200"
                             value={size}
                             onChange={handleSizeChange}
@@ -1,4 +1,4 @@
-import { useRef, useState } from 'react';
+import { useEffect, useRef, useState } from 'react';
 import { TransformWrapper, TransformComponent, useTransformComponent} from 'react-zoom-pan-pinch';
 import { invoke } from '@tauri-apps/api/core';
 import { useGlobalState, useReloadChapterData } from './GlobalStateContext';
@@ -44,6 +44,15 @@ function IconEditor({battlemap}: props) {
   const { chapterId } = useGlobalState();
   const reloadChapterData = useReloadChapterData();
 
+  // Once the image is reset or the editor closes, the uploaded image may be garbage collected
+  useEffect(() => {
+    if (iconImage === "") return;
+    return () => {
+      invoke('finish_icon_crop', { imageFilename: iconImage })
+        .catch((error) => console.error('Failed to finish icon crop:', error));
+    };
+  }, [iconImage]);
+
   const handleCreateEntity = () => {
     try {
       invoke<string>('upload_icon_image')
@@ -71,6 +80,17 @@ function IconEditor({battlemap}: props) {
         })
   }
 
+  const handleSaveTemplateClick = () => {
+    invoke('create_template', {
+      chapterId: chapterId,
+      imageFilename: iconImage,
+      transformState: transformStateRef.current,
+      name: '',
+      allegiance: allegiance,
+      entitySize: 'small'})
+        .catch((error) => console.error('Failed to create template:', error));
+  }
+
   const handleAllegianceClick = (allegiance: string) => {
     setAllegiance(allegiance);
   }
@@ -111,6 +131,7 @@ function IconEditor({battlemap}: props) {
             <div className="alt-controls-inner-container">
               <div className="alt-controls" onClick={() => setIconImage("")}>Reset</div>
               <div className="alt-controls"onClick={handleSaveClick}>Save</div>
+              <div className="alt-controls" onClick={handleSaveTemplateClick}>Save as Template</div>
             </div>
           </div>
         </div>
@@ -1,14 +1,57 @@
 import { invoke } from '@tauri-apps/api/core';
 import {
+    Condition,
+    ConditionKind,
+    DAMAGE_TYPES,
+    DamageType,
     Entity,
+    EntityPatch,
+    ExpiryTrigger,
     Coordinates,
     Hitpoints,
+    HitpointChange,
     useGlobalState,
+    useReloadChapterData,
     useReloadEntityData,
 } from './GlobalStateContext';
 import { useState } from 'react';
 import '../styles/components/PropertiesEditor.css';
 
+const CONDITION_KINDS: ConditionKind[] = [
+    'blinded',
+    'charmed',
+    'deafened',
+    'exhaustion',
+    'frightened',
+    'grappled',
+    'incapacitated',
+    'invisible',
+    'paralyzed',
+    'petrified',
+    'poisoned',
+    'prone',
+    'restrained',
+    'stunned',
+    'unconscious',
+    'custom',
+];
+
+type DamageTypeList = 'resistances' | 'vulnerabilities' | 'immunities';
+
+const DAMAGE_TYPE_LISTS: { key: DamageTypeList; label: string }[] = [
+    { key: 'resistances', label: 'Resistances' },
+    { key: 'vulnerabilities', label: 'Vulnerabilities' },
+    { key: 'immunities', label: 'Immunities' },
+];
+
+const newCondition = (): Condition => ({
+    kind: 'poisoned',
+    label: '',
+    duration: null,
+    source: '',
+    expires: 'end_of_turn',
+});
+
 interface props {
     entity: Entity;
     battlemap: string;
@@ -16,29 +59,46 @@ interface props {
 }
 
 function PropertiesEditor({ entity, battlemap, setEditorProperties }: props) {
-    const { chapterId } = useGlobalState();
+    const { chapterId, entityData } = useGlobalState();
     const [entityCoordinates, setEntityCoordinates] = useState({
-        x: entity.location.x,
-        y: entity.location.y,
+        q: entity.location.q,
+        r: entity.location.r,
     });
     const [entityHitpoints, setEntityHitpoints] = useState({
         current: entity.hitpoints.current,
         max: entity.hitpoints.max,
+        temporary: entity.hitpoints.temporary,
     });
-    const [entityModifiers, setEntityModifiers] = useState(entity.modifiers);
+    const [conditionDraft, setConditionDraft] = useState<Condition>(newCondition());
+    const [hitpointAmount, setHitpointAmount] = useState(0);
+    const [damageType, setDamageType] = useState<DamageType | ''>('');
+    const [hitpointSource, setHitpointSource] = useState('');
+    const [duplicateCount, setDuplicateCount] = useState(1);
+    const [darkvision, setDarkvision] = useState(entity.darkvision);
     const reloadEntityData = useReloadEntityData();
+    const reloadChapterData = useReloadChapterData();
+
+    // Only the fields that changed are sent, so the edit can't undo damage or moves made elsewhere
+    const sendEntityUpdate = (patch: EntityPatch) => {
+        invoke('update_entity', { chapterId: chapterId, entity: entity.icon, patch: patch }).then(() => {
+            reloadEntityData(battlemap);
+            reloadChapterData(); // the fog of war may follow the party's vision
+        });
+    };
 
-    const sendEntityUpdate = (updatedEntity = null) => {
-        const entityToUpdate = updatedEntity || entity;
-        // Only update location if coordinates were explicitly changed in this component
-        // Don't overwrite location when updating other properties like hitpoints
-        if (!updatedEntity) {
-            entityToUpdate.location = entityCoordinates;
-            entityToUpdate.hitpoints = entityHitpoints;
+    // Moves go through the backend so they end up in the combat log
+    const sendEntityMove = () => {
+        if (entityCoordinates.q === entity.location.q && entityCoordinates.r === entity.location.r) {
+            return;
         }
-        entityToUpdate.modifiers = entityModifiers;
-        invoke('update_entity', { entity: entityToUpdate }).then(() => {
+        invoke('move_entity', {
+            chapterId: chapterId,
+            battlemap: battlemap,
+            entity: entity.icon,
+            location: entityCoordinates,
+        }).then(() => {
             reloadEntityData(battlemap);
+            reloadChapterData(); // the fog of war may follow the party's vision
         });
     };
 
@@ -46,7 +106,7 @@ function PropertiesEditor({ entity, battlemap, setEditorProperties }: props) {
         let newAllegiance =
             entity.allegiance === 'neutral' ? 'evil' : 'neutral';
         entity.allegiance = newAllegiance;
-        sendEntityUpdate();
+        sendEntityUpdate({ allegiance: newAllegiance });
     };
 
     const handleRemoveEntity = () => {
@@ -56,20 +116,43 @@ function PropertiesEditor({ entity, battlemap, setEditorProperties }: props) {
             iconId: entity.icon,
         }).then(() => {
             setEditorProperties('editor');
+            reloadChapterData(); // the fog of war may follow the party's vision
         });
     };
 
-    const handleXChange = (event: React.ChangeEvent<HTMLInputElement>) => {
+    const handleDuplicateEntity = () => {
+        if (duplicateCount <= 0) {
+            return;
+        }
+        invoke<Entity[]>('duplicate_entity', {
+            chapterId: chapterId,
+            battlemapId: battlemap,
+            entity: entity.icon,
+            count: duplicateCount,
+        }).then(() => {
+            setDuplicateCount(1);
+            reloadEntityData(battlemap);
+            reloadChapterData(); // the fog of war may follow the party's vision
+        });
+    };
+
+    const handleSaveAsTemplate = () => {
+        invoke('create_template_from_entity', { chapterId: chapterId, entity: entity.icon }).then(() => {
+            reloadEntityData(battlemap);
+        });
+    };
+
+    const handleQChange = (event: React.ChangeEvent<HTMLInputElement>) => {
         setEntityCoordinates({
-            x: Number(event.target.value),
-            y: entityCoordinates.y,
+            q: Number(event.target.value),
+            r: entityCoordinates.r,
         });
     };
 
-    const handleYChange = (event: React.ChangeEvent<HTMLInputElement>) => {
+    const handleRChange = (event: React.ChangeEvent<HTMLInputElement>) => {
         setEntityCoordinates({
-            x: entityCoordinates.x,
-            y: Number(event.target.value),
+            q: entityCoordinates.q,
+            r: Number(event.target.value),
         });
     };
 
@@ -78,43 +161,108 @@ function PropertiesEditor({ entity, battlemap, setEditorProperties }: props) {
     ) => {
         const newCurrentHP = Number(event.target.value) || 0;
         const newHitpoints = {
+            ...entityHitpoints,
             current: newCurrentHP,
-            max: entityHitpoints.max,
         };
         setEntityHitpoints(newHitpoints);
-        
-        const updatedEntity = { ...entity, hitpoints: newHitpoints };
-        sendEntityUpdate(updatedEntity);
+        sendEntityUpdate({ hitpoints: { current: newCurrentHP } });
     };
 
     const handleHPMaxChange = (event: React.ChangeEvent<HTMLInputElement>) => {
         const newMaxHP = Number(event.target.value) || 0;
         const newHitpoints = {
-            current: entityHitpoints.current,
+            ...entityHitpoints,
             max: newMaxHP,
         };
         setEntityHitpoints(newHitpoints);
-        
-        const updatedEntity = { ...entity, hitpoints: newHitpoints };
-        sendEntityUpdate(updatedEntity);
+        sendEntityUpdate({ hitpoints: { max: newMaxHP } });
     };
 
-    const handleModifiersChange = (
-        event: React.ChangeEvent<HTMLInputElement>
-    ) => {
-        setEntityModifiers(event.target.value);
+    const handleDarkvisionBlur = () => {
+        sendEntityUpdate({ darkvision: darkvision });
+    };
+
+    const applyHitpointChange = (command: 'apply_damage' | 'apply_healing') => {
+        if (hitpointAmount <= 0) {
+            return;
+        }
+        invoke<HitpointChange>(command, {
+            chapterId: chapterId,
+            battlemap: battlemap,
+            entity: entity.icon,
+            amount: hitpointAmount,
+            damageType: command === 'apply_damage' && damageType !== '' ? damageType : null,
+            source: hitpointSource !== '' ? hitpointSource : null,
+        }).then((change) => {
+            setEntityHitpoints(change.after);
+            setHitpointAmount(0);
+            reloadEntityData(battlemap);
+            if (change.died) {
+                reloadChapterData(); // the dead no longer reveal the fog of war
+            }
+        });
+    };
+
+    const handleAddDamageType = (list: DamageTypeList, type: DamageType) => {
+        if (entity[list].includes(type)) {
+            return;
+        }
+        sendEntityUpdate({ [list]: [...entity[list], type] });
+    };
+
+    const handleRemoveDamageType = (list: DamageTypeList, type: DamageType) => {
+        sendEntityUpdate({ [list]: entity[list].filter((t) => t !== type) });
+    };
+
+    const handleAddCondition = () => {
+        if (conditionDraft.kind === 'custom' && conditionDraft.label.trim() === '') {
+            return;
+        }
+        invoke('add_condition', {
+            chapterId: chapterId,
+            battlemap: battlemap,
+            entity: entity.icon,
+            condition: conditionDraft,
+        }).then(() => {
+            setConditionDraft(newCondition());
+            reloadEntityData(battlemap);
+        });
+    };
+
+    const handleRemoveCondition = (index: number) => {
+        invoke('remove_condition', {
+            chapterId: chapterId,
+            battlemap: battlemap,
+            entity: entity.icon,
+            index: index,
+        }).then(() => {
+            reloadEntityData(battlemap);
+        });
+    };
+
+    const handleDurationChange = (event: React.ChangeEvent<HTMLInputElement>) => {
+        const rounds = Number(event.target.value);
+        setConditionDraft({
+            ...conditionDraft,
+            duration: rounds > 0 ? rounds : null,
+        });
     };
 
     const handleVisible = () => {
         let visibility = entity.visible === true ? false : true;
         entity.visible = visibility;
-        sendEntityUpdate();
+        sendEntityUpdate({ visible: visibility });
     };
 
     const handleDead = () => {
         let deadState = entity.dead === true ? false : true;
         entity.dead = deadState;
-        sendEntityUpdate();
+        sendEntityUpdate({ dead: deadState });
+    };
+
+    const handlePlayer = () => {
+        entity.player = !entity.player;
+        sendEntityUpdate({ player: entity.player });
     };
 
     return (
@@ -129,9 +277,9 @@ function PropertiesEditor({ entity, battlemap, setEditorProperties }: props) {
                 </div>
                 <div className='entity-properties-container'>
                     {/* <div className="properties-property">
-                        Starting Coordinates (x, y):
-                        <input type="number" className="properties-number-input" min="0" step="1" value={entityCoordinates.x} onChange={handleXChange} onBlur={sendEntityUpdate} />
-                        <input type="number" className="properties-number-input" min="0" step="1" value={entityCoordinates.y} onChange={handleYChange} onBlur={sendEntityUpdate} />
+                        Starting Hex (q, r):
+                        <input type="number" className="properties-number-input" step="1" value={entityCoordinates.q} onChange={handleQChange} onBlur={sendEntityMove} />
+                        <input type="number" className="properties-number-input" min="0" step="1" value={entityCoordinates.r} onChange={handleRChange} onBlur={sendEntityMove} />
                     </div> */}
                     <div className='properties-property'>
                         Hitpoints (current / max):
@@ -158,15 +306,162 @@ function PropertiesEditor({ entity, battlemap, setEditorProperties }: props) {
                         </div>
                     </div>
                     <div className='properties-property'>
-                        Modifiers:
+                        Darkvision (ft):
                         <input
-                            type='text'
-                            className='modifiers'
-                            value={entityModifiers}
-                            onChange={handleModifiersChange}
-                            onBlur={sendEntityUpdate}
+                            type='number'
+                            className='properties-number-input'
+                            min='0'
+                            step='5'
+                            value={darkvision}
+                            onChange={(event) => setDarkvision(Math.max(0, Number(event.target.value) || 0))}
+                            onBlur={handleDarkvisionBlur}
                         />
                     </div>
+                    <div className='properties-property'>
+                        Damage / Healing:
+                        <div className='hitpoints-inputs'>
+                            <input
+                                type='number'
+                                className='properties-number-input'
+                                min='0'
+                                step='1'
+                                value={hitpointAmount || ''}
+                                onChange={(event) => setHitpointAmount(Number(event.target.value) || 0)}
+                                placeholder='Amount'
+                            />
+                            <select
+                                value={damageType}
+                                onChange={(event) => setDamageType(event.target.value as DamageType | '')}
+                            >
+                                <option value=''>untyped</option>
+                                {DAMAGE_TYPES.map((type) => (
+                                    <option key={type} value={type}>
+                                        {type}
+                                    </option>
+                                ))}
+                            </select>
+                            <select
+                                value={hitpointSource}
+                                onChange={(event) => setHitpointSource(event.target.value)}
+                            >
+                                <option value=''>no source</option>
+                                {entityData
+                                    .filter((other) => other.icon !== entity.icon)
+                                    .map((other) => (
+                                        <option key={other.icon} value={other.icon}>
+                                            {other.name || other.icon}
+                                        </option>
+                                    ))}
+                            </select>
+                            <span className='hitpoints-action' onClick={() => applyHitpointChange('apply_damage')}>
+                                Damage
+                            </span>
+                            <span className='hitpoints-action' onClick={() => applyHitpointChange('apply_healing')}>
+                                Heal
+                            </span>
+                        </div>
+                    </div>
+                    {DAMAGE_TYPE_LISTS.map(({ key, label }) => (
+                        <div className='properties-property' key={key}>
+                            {label}:
+                            <div className='damage-types'>
+                                {entity[key].map((type) => (
+                                    <span
+                                        className='damage-type'
+                                        key={type}
+                                        onClick={() => handleRemoveDamageType(key, type)}
+                                    >
+                                        {type} x
+                                    </span>
+                                ))}
+                                <select
+                                    value=''
+                                    onChange={(event) => handleAddDamageType(key, event.target.value as DamageType)}
+                                >
+                                    <option value=''>add...</option>
+                                    {DAMAGE_TYPES.filter((type) => !entity[key].includes(type)).map((type) => (
+                                        <option key={type} value={type}>
+                                            {type}
+                                        </option>
+                                    ))}
+                                </select>
+                            </div>
+                        </div>
+                    ))}
+                    <div className='properties-property'>
+                        Conditions:
+                        <div className='modifiers'>
+                            {entity.conditions.map((condition, index) => (
+                                <div className='condition' key={index}>
+                                    <span>
+                                        {condition.kind === 'custom' ? condition.label : condition.kind}
+                                        {condition.duration !== null &&
+                                            ` (${condition.duration} rnd, ${condition.expires === 'start_of_turn' ? 'start' : 'end'})`}
+                                        {condition.source && ` - ${condition.source}`}
+                                    </span>
+                                    <span
+                                        className='condition-remove'
+                                        onClick={() => handleRemoveCondition(index)}
+                                    >
+                                        x
+                                    </span>
+                                </div>
+                            ))}
+                            <div className='condition-inputs'>
+                                <select
+                                    value={conditionDraft.kind}
+                                    onChange={(event) =>
+                                        setConditionDraft({ ...conditionDraft, kind: event.target.value as ConditionKind })
+                                    }
+                                >
+                                    {CONDITION_KINDS.map((kind) => (
+                                        <option key={kind} value={kind}>
+                                            {kind}
+                                        </option>
+                                    ))}
+                                </select>
+                                {conditionDraft.kind === 'custom' && (
+                                    <input
+                                        type='text'
+                                        value={conditionDraft.label}
+                                        onChange={(event) =>
+                                            setConditionDraft({ ...conditionDraft, label: event.target.value })
+                                        }
+                                        placeholder='Name'
+                                    />
+                                )}
+                                <input
+                                    type='number'
+                                    className='properties-number-input'
+                                    min='0'
+                                    step='1'
+                                    value={conditionDraft.duration ?? ''}
+                                    onChange={handleDurationChange}
+                                    placeholder='Rounds'
+                                />
+                                <select
+                                    value={conditionDraft.expires}
+                                    onChange={(event) =>
+                                        setConditionDraft({ ...conditionDraft, expires: event.target.value as ExpiryTrigger })
+                                    }
+                                >
+                                    <option value='start_of_turn'>start of turn</option>
+                                    <option value='end_of_turn'>end of turn</option>
+                                </select>
+                                <input
+                                    type='text'
+                                    value={conditionDraft.source}
+                                    onChange={(event) =>
+                                        setConditionDraft({ ...conditionDraft, source: event.target.value })
+                                    }
+                                    placeholder='Source'
+                                />
+                                <span className='condition-add' onClick={handleAddCondition}>
+                                    Add
+                                </span>
+                            </div>
+                        </div>
+                    </div>
                     <div className='properties-property'>
                         <div
                             className='visible-dead'
@@ -180,6 +475,13 @@ function PropertiesEditor({ entity, battlemap, setEditorProperties }: props) {
                         >
                             Dead: {String(entity.dead)}
                         </div>
+                        <div
+                            className='visible-dead'
+                            onClick={handlePlayer}
+                        >
+                            Player: {String(entity.player)}
+                        </div>
+                        {entity.dying && <div className='dying'>Dying</div>}
                     </div>
                 </div>
             </div>
@@ -190,6 +492,29 @@ function PropertiesEditor({ entity, battlemap, setEditorProperties }: props) {
                 >
                     Swap Allegiance
                 </div>
+                <div className='duplicate-entity'>
+                    <input
+                        type='number'
+                        className='properties-number-input'
+                        min='1'
+                        max='50'
+                        step='1'
+                        value={duplicateCount || ''}
+                        onChange={(event) => setDuplicateCount(Number(event.target.value) || 0)}
+                    />
+                    <span
+                        className='duplicate-entity-action'
+                        onClick={handleDuplicateEntity}
+                    >
+                        Duplicate
+                    </span>
+                </div>
+                <div
+                    className='swap-allegiance'
+                    onClick={handleSaveAsTemplate}
+                >
+                    {entity.template === null ? 'Save as Template' : 'New Template'}
+                </div>
                 <div
                     className='remove-entity'
                     onClick={handleRemoveEntity}
@@ -0,0 +1,90 @@
+import { invoke } from '@tauri-apps/api/core';
+import { useEffect, useState } from 'react';
+import {
+    SnapshotInfo,
+    TableauError,
+    useGlobalState,
+    useReloadChapterData,
+    useReloadEntityData,
+} from './GlobalStateContext';
+import '../styles/components/Snapshots.css';
+
+interface props {
+    battlemap: string;
+}
+
+function Snapshots({ battlemap }: props) {
+    const { chapterId } = useGlobalState();
+    const reloadChapterData = useReloadChapterData();
+    const reloadEntityData = useReloadEntityData();
+    const [snapshots, setSnapshots] = useState<SnapshotInfo[]>([]);
+    const [name, setName] = useState('');
+    const [error, setError] = useState('');
+
+    const reload = () => {
+        invoke<SnapshotInfo[]>('list_snapshots', { chapterId, battlemap }).then(setSnapshots);
+    };
+
+    useEffect(reload, [chapterId, battlemap]);
+
+    const handleCreate = () => {
+        invoke<SnapshotInfo>('create_snapshot', { chapterId, battlemap, name })
+            .then((snapshot) => {
+                setSnapshots([...snapshots, snapshot]);
+                setName('');
+                setError('');
+            })
+            .catch((err: TableauError) => setError(err.message));
+    };
+
+    const handleRestore = (snapshot: string) => {
+        invoke('restore_snapshot', { chapterId, battlemap, name: snapshot })
+            .then(() => {
+                reloadChapterData();
+                reloadEntityData(battlemap);
+                setError('');
+            })
+            .catch((err: TableauError) => setError(err.message));
+    };
+
+    const handleRemove = (snapshot: string) => {
+        invoke('remove_snapshot', { chapterId, battlemap, name: snapshot }).then(reload);
+    };
+
+    return (
+        <div className='snapshots'>
+            <div className='snapshots-inputs'>
+                <input
+                    type='text'
+                    className='snapshots-name'
+                    value={name}
+                    onChange={(event) => setName(event.target.value)}
+                    placeholder='Snapshot name, e.g. Setup'
+                />
+                <div className='show-display' onClick={handleCreate}>
+                    Save
+                </div>
+            </div>
+            {error !== '' && <div className='snapshots-error'>{error}</div>}
+            <div className='snapshots-list'>
+                {snapshots.map((snapshot) => (
+                    <div key={snapshot.name} className='snapshots-entry'>
+                        <span className='snapshots-entry-name'>{snapshot.name}</span>
+                        <span className='snapshots-entry-details'>
+                            {new Date(snapshot.created).toLocaleString()}, {snapshot.entityCount} entities
+                            {snapshot.round > 0 && `, round ${snapshot.round}`}
+                        </span>
+                        <span className='snapshots-action' onClick={() => handleRestore(snapshot.name)}>
+                            Restore
+                        </span>
+                        <span className='snapshots-action' onClick={() => handleRemove(snapshot.name)}>
+                            x
+                        </span>
+                    </div>
+                ))}
+            </div>
+        </div>
+    );
+}
+
+export default Snapshots;
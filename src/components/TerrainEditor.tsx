@@ -0,0 +1,86 @@
+import { emit, listen } from '@tauri-apps/api/event';
+import { useEffect, useState } from 'react';
+import { Combat, TerrainTool, useReloadChapterData } from './GlobalStateContext';
+import '../styles/components/TerrainEditor.css';
+
+interface props {
+    combatData: Combat;
+}
+
+type ToolName = 'blocked' | 'difficult' | 'water' | 'hazard' | 'erase' | 'wall' | 'remove_wall';
+
+const TOOLS: { name: ToolName; label: string }[] = [
+    { name: 'blocked', label: 'Blocked' },
+    { name: 'difficult', label: 'Difficult' },
+    { name: 'water', label: 'Water' },
+    { name: 'hazard', label: 'Hazard' },
+    { name: 'erase', label: 'Erase' },
+    { name: 'wall', label: 'Wall' },
+    { name: 'remove_wall', label: 'Remove Wall' },
+];
+
+// Picks what clicks on the display window paint; the display shows the terrain while a tool is active
+function TerrainEditor({ combatData }: props) {
+    const [tool, setTool] = useState<ToolName | null>(null);
+    const [hazardDamage, setHazardDamage] = useState('1d6 fire');
+    const reloadChapterData = useReloadChapterData();
+
+    useEffect(() => {
+        let terrainTool: TerrainTool | null = null;
+        if (tool === 'erase' || tool === 'wall' || tool === 'remove_wall') {
+            terrainTool = { mode: tool };
+        } else if (tool === 'hazard') {
+            terrainTool = { mode: 'paint', kind: { kind: 'hazard', damage: hazardDamage } };
+        } else if (tool !== null) {
+            terrainTool = { mode: 'paint', kind: { kind: tool } };
+        }
+        emit('terrainTool', terrainTool);
+    }, [tool, hazardDamage]);
+
+    // Leave the display in its normal mode when the tab is closed
+    useEffect(() => {
+        const unlistenTerrainChanged = listen('terrainChanged', () => reloadChapterData());
+
+        return () => {
+            emit('terrainTool', null);
+            unlistenTerrainChanged.then((unsub) => unsub());
+        };
+    }, []);
+
+    return (
+        <div className='terrain-editor'>
+            <div className='terrain-editor-tools'>
+                {TOOLS.map(({ name, label }) => (
+                    <div
+                        key={name}
+                        className={`show-display terrain-tool ${tool === name ? 'terrain-tool-active' : ''}`}
+                        onClick={() => setTool(tool === name ? null : name)}
+                    >
+                        {label}
+                    </div>
+                ))}
+            </div>
+            {tool === 'hazard' && (
+                <input
+                    type='text'
+                    className='terrain-editor-damage'
+                    value={hazardDamage}
+                    onChange={(event) => setHazardDamage(event.target.value)}
+                    placeholder='Damage, e.g. 2d6 fire'
+                />
+            )}
+            <div className='terrain-editor-hint'>
+                {tool === null
+                    ? 'Pick a tool, then click hexes on the display.'
+                    : tool === 'wall' || tool === 'remove_wall'
+                    ? 'Click two neighboring hexes on the display.'
+                    : 'Click hexes on the display.'}
+            </div>
+            <div className='terrain-editor-summary'>
+                {combatData.terrain.tiles.length} painted hexes, {combatData.terrain.walls.length} walls
+            </div>
+        </div>
+    );
+}
+
+export default TerrainEditor;
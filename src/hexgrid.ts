@@ -1,3 +1,5 @@
+import { Coordinates, Hex } from './components/GlobalStateContext';
+
 export function generateGridCenters(
     containerWidth: number,
     containerHeight: number,
@@ -36,3 +38,50 @@ export function generateGridCenters(
 
     return { x: centersX, y: centersY, offset: offset };
 }
+
+// Where a hex's center sits on the display, in pixels of the reference container; mirrors `hex::Layout`
+export function hexToPixel(hex: Hex, size: number, offset: Coordinates) {
+    return {
+        x: offset.x + size * Math.sqrt(3) * (hex.q + hex.r / 2),
+        y: offset.y + size * 1.5 * hex.r,
+    };
+}
+
+// Where a hex's center sits on the display, in percent of the reference container
+export function hexToPercent(
+    hex: Hex,
+    size: number,
+    offset: Coordinates,
+    containerWidth: number,
+    containerHeight: number
+) {
+    const { x, y } = hexToPixel(hex, size, offset);
+    return { x: (x / containerWidth) * 100, y: (y / containerHeight) * 100 };
+}
+
+// The edge two neighboring hexes share, e.g. to draw a wall between them, in pixels of the reference container
+export function hexEdge(a: Hex, b: Hex, size: number, offset: Coordinates) {
+    const start = hexToPixel(a, size, offset);
+    const end = hexToPixel(b, size, offset);
+    const length = Math.hypot(end.x - start.x, end.y - start.y);
+    // Half an edge along the perpendicular of the line between the centers; an edge is as long as `size`
+    const across = { x: (-(end.y - start.y) / length) * (size / 2), y: ((end.x - start.x) / length) * (size / 2) };
+    const middle = { x: (start.x + end.x) / 2, y: (start.y + end.y) / 2 };
+    return {
+        x1: middle.x - across.x,
+        y1: middle.y - across.y,
+        x2: middle.x + across.x,
+        y2: middle.y + across.y,
+    };
+}
+
+// A hex's outline as SVG polygon points, in pixels of the reference container
+export function hexPolygonPoints(hex: Hex, size: number, offset: Coordinates) {
+    const center = hexToPixel(hex, size, offset);
+    const points = [];
+    for (let corner = 0; corner < 6; corner++) {
+        const angle = ((60 * corner - 90) * Math.PI) / 180;
+        points.push(`${center.x + size * Math.cos(angle)},${center.y + size * Math.sin(angle)}`);
+    }
+    return points.join(' ');
+}